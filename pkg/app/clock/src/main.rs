@@ -26,12 +26,7 @@ fn clock() -> ! {
     use micromath::F32Ext;
 
     loop {
-        let start = sys_time();
-        let mut current = start;
-
-        while (current - start) < Duration::try_seconds(1).unwrap() {
-            current = sys_time();
-        }
+        sleep_for(Duration::seconds(1));
 
         angle += ANGLE_INCR;
         if angle >= 360.0 {
@@ -47,7 +47,7 @@ fn philosopher(id: usize) -> ! {
     for _ in 0..100 {
         // thinking
         println!("philosopher #{} ({}) is thinking...", id, pid);
-        delay();
+        sleep(10);
 
         // hungry
         WAITER.wait();
@@ -61,12 +61,4 @@ fn philosopher(id: usize) -> ! {
     sys_exit(0);
 }
 
-#[inline(never)]
-#[unsafe(no_mangle)]
-fn delay() {
-    for _ in 0..100 {
-        core::hint::spin_loop();
-    }
-}
-
 entry!(main);
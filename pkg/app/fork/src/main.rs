@@ -12,8 +12,8 @@ fn main() -> isize {
     let mut c = 32;
     let m_ptr = &raw mut M;
 
-    // do not alloc heap before `fork`
-    // which may cause unexpected behavior since we won't copy the heap in `fork`
+    // the address space is copied on write,
+    // changes made by the child are not seen by the parent
     let pid = sys_fork();
 
     if pid == 0 {
@@ -45,7 +45,7 @@ fn main() -> isize {
 
         unsafe {
             println!("parent read value of M: {:#x}", *m_ptr);
-            assert_eq!(*m_ptr, 0x2333);
+            assert_eq!(*m_ptr, 0xdeadbeef);
         }
 
         c += 1024;
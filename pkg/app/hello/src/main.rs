@@ -8,6 +8,14 @@ extern crate lib;
 fn main() -> isize {
     println!("Hello, world!!!");
 
+    for (i, arg) in args().iter().enumerate() {
+        println!("argv[{}] = {}", i, arg);
+    }
+
+    if let Some(pwd) = env_var("PWD") {
+        println!("PWD = {}", pwd);
+    }
+
     let time = lib::sys_time();
     println!("Now at: {}", time);
 
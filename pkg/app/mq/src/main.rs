@@ -54,7 +54,7 @@ fn producer(id: usize) -> ! {
     let pid = sys_get_pid();
     println!("New producer #{}({})", id, pid);
     for _ in 0..10 {
-        delay();
+        sleep(10);
 
         IS_NOT_FULL.wait();
         MUTEX.wait();
@@ -74,7 +74,7 @@ fn consumer(id: usize) -> ! {
     let pid = sys_get_pid();
     println!("New consumer #{}({})", id, pid);
     for _ in 0..10 {
-        delay();
+        sleep(10);
 
         IS_NOT_EMPTY.wait();
         MUTEX.wait();
@@ -90,12 +90,4 @@ fn consumer(id: usize) -> ! {
     sys_exit(0);
 }
 
-#[inline(never)]
-#[unsafe(no_mangle)]
-fn delay() {
-    for _ in 0..0x100 {
-        core::hint::spin_loop();
-    }
-}
-
 entry!(main);
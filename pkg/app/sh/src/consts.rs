@@ -9,11 +9,15 @@ Usage:
     help        | show this help
     ps          | show process list
     ls          | list directory
+    mount       | list mounted filesystems
+    sync        | write cached data to disks
+    lspci       | list pci devices
     cd <path>   | change directory
     cat <file>  | show file content
-    exec <file> | execute file
+    exec <f> ...| execute file with arguments
     nohup <file>| execute file in background
     kill <pid>  | kill process
+    nice <p> <n>| set nice value of process
     clear       | clear screen
     exit        | exit shell
 
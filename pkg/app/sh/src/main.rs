@@ -26,7 +26,14 @@ fn main() -> isize {
                 break;
             }
             "ps" => sys_stat(),
-            "ls" => sys_list_dir(root_dir.as_str()),
+            "ls" => services::ls(root_dir.as_str()),
+            "mount" => services::mount(),
+            "lspci" => services::lspci(),
+            "sync" => {
+                if !sys_sync() {
+                    println!("Failed to sync filesystems");
+                }
+            }
             "cat" => {
                 if line.len() < 2 {
                     println!("Usage: cat <file>");
@@ -45,11 +52,11 @@ fn main() -> isize {
             }
             "exec" => {
                 if line.len() < 2 {
-                    println!("Usage: exec <file>");
+                    println!("Usage: exec <file> [args...]");
                     continue;
                 }
 
-                services::exec(line[1], root_dir.as_str());
+                services::exec(&line[1..], root_dir.as_str());
             }
             "nohup" => {
                 if line.len() < 2 {
@@ -73,6 +80,17 @@ fn main() -> isize {
 
                 services::kill(pid.unwrap());
             }
+            "nice" => {
+                if line.len() < 3 {
+                    println!("Usage: nice <pid> <value>");
+                    continue;
+                }
+
+                match (line[1].parse::<u16>(), line[2].parse::<isize>()) {
+                    (Ok(pid), Ok(value)) => services::nice(pid, value),
+                    _ => errln!("Cannot parse pid or value"),
+                }
+            }
             "help" => print!("{}", consts::help_text()),
             "clear" => print!("\x1b[1;1H\x1b[2J"),
             _ => {
@@ -48,13 +48,81 @@ pub fn show_hex(data: &[u8]) {
     stdout().write(&string);
 }
 
+pub fn ls(root_dir: &str) {
+    let Some(dir) = read_dir(root_dir) else {
+        errln!("Cannot read directory: {}", root_dir);
+        return;
+    };
+
+    println!("  Size | Last Modified       | Name");
+
+    for entry in dir {
+        let (size, unit) = humanized_size(entry.len);
+        let time = DateTime::from_timestamp_nanos(entry.modified);
+
+        println!(
+            "{:>5.1}{} | {:04}/{:02}/{:02} {:02}:{:02}:{:02} | {}{}",
+            size,
+            unit,
+            time.year(),
+            time.month(),
+            time.day(),
+            time.hour(),
+            time.minute(),
+            time.second(),
+            entry.name(),
+            if entry.is_dir() { "/" } else { "" }
+        );
+    }
+}
+
+pub fn mount() {
+    let Some(mounts) = mounts() else {
+        errln!("Cannot list the mounted filesystems");
+        return;
+    };
+
+    println!("  Type | Mount Point");
+
+    for mount in mounts {
+        println!("{:>6} | {}", mount.fs_type(), mount.mount_point());
+    }
+}
+
+pub fn lspci() {
+    let Some(devices) = pci_devices() else {
+        errln!("Cannot list the PCI devices");
+        return;
+    };
+
+    for dev in devices {
+        match dev.irq_pin {
+            0 => println!("{}", dev),
+            _ => println!("{} IRQ {}", dev, dev.irq_line),
+        }
+    }
+}
+
+fn humanized_size(size: u64) -> (f32, &'static str) {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+
+    let mut size = size as f32;
+    let mut unit = 0;
+
+    while size >= 1024f32 && unit < UNITS.len() - 1 {
+        size /= 1024f32;
+        unit += 1;
+    }
+
+    (size, UNITS[unit])
+}
+
 pub fn cat(path: &str, root_dir: &str) {
     let path = if path.starts_with('/') {
         String::from(path)
     } else {
         format!("{}{}", root_dir, path)
-    }
-    .to_ascii_uppercase();
+    };
 
     let fd = sys_open(path.as_str(), FileMode::ReadOnly);
 
@@ -91,41 +159,48 @@ pub fn cat(path: &str, root_dir: &str) {
 
 pub fn cd(path: &str, root_dir: &mut String) {
     if path.starts_with('/') {
-        *root_dir = String::from(path).to_ascii_uppercase();
+        *root_dir = String::from(path);
         if !root_dir.ends_with('/') {
             root_dir.push('/');
         }
     } else {
         root_dir.push_str(path);
         root_dir.push('/');
-        *root_dir = root_dir.to_ascii_uppercase();
     }
     canonicalize(root_dir)
 }
 
-pub fn exec(path: &str, root_dir: &str) {
-    let path = format!("{}{}", root_dir, path).to_ascii_uppercase();
-    let start = sys_time();
+pub fn exec(argv: &[&str], root_dir: &str) {
+    let path = format!("{}{}", root_dir, argv[0]);
+    let start = uptime();
 
-    let pid = sys_spawn(path.as_str());
+    let pid = sys_fork();
 
-    if pid == 0 {
-        errln!("failed to spawn process: {}", path);
+    if pid == u16::MAX {
+        errln!("failed to fork process: {}", path);
         return;
     }
 
+    if pid == 0 {
+        let pwd = format!("PWD={}", root_dir);
+        lib::exec(path.as_str(), argv, &[pwd.as_str()]);
+
+        errln!("failed to exec process: {}", path);
+        sys_exit(-1);
+    }
+
     let ret = sys_wait_pid(pid);
-    let time = sys_time() - start;
+    let time = uptime() - start;
 
     println!(
-        "[+] process exited with code {} @ {}s",
+        "[+] process exited with code {} @ {:.3}s",
         ret,
-        time.num_seconds()
+        time.num_milliseconds() as f64 / 1000.0
     );
 }
 
 pub fn nohup(path: &str, root_dir: &str) {
-    let path = format!("{}{}", root_dir, path).to_ascii_uppercase();
+    let path = format!("{}{}", root_dir, path);
 
     let pid = sys_spawn(path.as_str());
 
@@ -141,6 +216,12 @@ pub fn kill(pid: u16) {
     sys_kill(pid);
 }
 
+pub fn nice(pid: u16, nice: isize) {
+    if !sys_set_priority(pid, nice) {
+        errln!("Failed to set the nice value of #{}", pid);
+    }
+}
+
 pub fn canonicalize(path: &mut String) {
     // If the path is not absolute, return an error
     if !path.starts_with('/') {
@@ -15,6 +15,8 @@ pub struct Config<'a> {
     pub kernel_path: &'a str,
     /// The path of initramfs
     pub initramfs: Option<&'a str>,
+    /// The partition mounted as the root filesystem, e.g. `hda1`
+    pub root: Option<&'a str>,
     /// Kernel command line
     pub cmdline: &'a str,
     /// Load apps into memory, when no fs implemented in kernel
@@ -30,6 +32,7 @@ const DEFAULT_CONFIG: Config = Config {
     kernel_stack_auto_grow: 0,
     kernel_path: "\\KERNEL.ELF",
     initramfs: None,
+    root: None,
     cmdline: "",
     load_apps: false,
     log_level: "info",
@@ -70,6 +73,7 @@ impl<'a> Config<'a> {
             "kernel_path" => self.kernel_path = value,
             "kernel_stack_auto_grow" => self.kernel_stack_auto_grow = r10,
             "initramfs" => self.initramfs = Some(value),
+            "root" => self.root = Some(value),
             "cmdline" => self.cmdline = value,
             "load_apps" => self.load_apps = r10 != 0,
             "log_level" => self.log_level = value,
@@ -38,6 +38,12 @@ pub struct BootInfo {
     /// Loaded apps
     pub loaded_apps: Option<ArrayVec<App<'static>, 16>>,
 
+    /// The initramfs archive
+    pub initramfs: Option<&'static [u8]>,
+
+    /// The name of the root partition
+    pub root: Option<&'static str>,
+
     /// Log Level
     pub log_level: &'static str,
 }
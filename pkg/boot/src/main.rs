@@ -56,6 +56,12 @@ fn main() -> Status {
         None
     };
 
+    let initramfs = config.initramfs.map(|path| {
+        info!("Loading initramfs...");
+        let mut file = open_file(path);
+        &*load_file(&mut file)
+    });
+
     let mmap = uefi::boot::memory_map(MemoryType::LOADER_DATA).expect("Failed to get memory map");
 
     let max_phys_addr = mmap
@@ -125,6 +131,8 @@ fn main() -> Status {
         kernel_pages: get_page_usage(&elf),
         physical_memory_offset: config.physical_memory_offset,
         loaded_apps: apps,
+        initramfs,
+        root: config.root,
         log_level: config.log_level,
         system_table,
         graphic_info,
@@ -0,0 +1,241 @@
+//! AHCI Device Driver
+//!
+//! The SATA drives behind the AHCI controllers are accessed by DMA,
+//! the commands are issued on slot 0 of each port one at a time and
+//! completed by the MSI of the controller, or polled without it.
+//!
+//! reference: https://wiki.osdev.org/AHCI
+//! reference: https://www.intel.com/content/dam/www/public/us/en/documents/technical-specifications/serial-ata-ahci-spec-rev1-3-1.pdf
+
+mod port;
+
+use crate::memory::map_mmio;
+use crate::pci::{Bar, PciDevice};
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use port::*;
+use spin::Mutex;
+use storage::{Block512, BlockDevice};
+
+/// Size of the HBA memory registers with all 32 ports
+const ABAR_SIZE: u64 = 0x1100;
+
+/// Generic host control registers
+const HBA_CAP: u64 = 0x00;
+const HBA_GHC: u64 = 0x04;
+const HBA_IS: u64 = 0x08;
+const HBA_PI: u64 = 0x0C;
+
+/// Supports 64 bits addressing
+const CAP_S64A: u32 = 1 << 31;
+/// Interrupt enable of the controller
+const GHC_IE: u32 = 1 << 1;
+/// AHCI enable, the legacy interface is not used
+const GHC_AE: u32 = 1 << 31;
+
+/// A controller with the interrupts of its ports
+struct Hba {
+    abar: u64,
+    /// Whether the controller interrupts by MSI
+    msi: bool,
+    ports: [PortIrq; 32],
+}
+
+static HBAS: spin::Once<Vec<Hba>> = spin::Once::new();
+static DISKS: spin::Once<Vec<AhciDisk>> = spin::Once::new();
+
+/// Returns the SATA drives on all the AHCI controllers, probed on the first call
+pub fn disks() -> &'static [AhciDisk] {
+    DISKS.call_once(|| {
+        let mut disks = Vec::new();
+
+        // the controllers are registered before the ports interrupt
+        let hbas = HBAS.call_once(|| {
+            // mass storage controller, SATA, AHCI 1.0
+            crate::pci::devices()
+                .iter()
+                .filter(|dev| dev.class == 0x01 && dev.subclass == 0x06 && dev.prog_if == 0x01)
+                .filter_map(init_controller)
+                .collect()
+        });
+
+        for hba in hbas {
+            probe_ports(hba, &mut disks);
+        }
+
+        disks
+    })
+}
+
+/// Called by the interrupt handler of the controllers
+pub fn handle_irq() {
+    for hba in HBAS.get().into_iter().flatten() {
+        let pending = read(hba.abar, HBA_IS);
+
+        for idx in (0..32).filter(|idx| pending & (1 << idx) != 0) {
+            hba.ports[idx].handle(port_base(hba.abar, idx));
+        }
+
+        // cleared after the ports, or it is raised again
+        write(hba.abar, HBA_IS, pending);
+    }
+}
+
+#[inline]
+fn port_base(abar: u64, idx: usize) -> u64 {
+    abar + 0x100 + idx as u64 * 0x80
+}
+
+fn init_controller(controller: &PciDevice) -> Option<Hba> {
+    // BAR5 is the AHCI base memory register
+    let Bar::Memory { addr, .. } = controller.bar(5) else {
+        warn!("AHCI controller {} has no memory registers", controller);
+        return None;
+    };
+
+    controller.enable_bus_master();
+
+    let abar = match map_mmio(addr, ABAR_SIZE) {
+        Ok(abar) => abar,
+        Err(err) => {
+            warn!("Failed to map AHCI registers at {:#x}: {:?}", addr, err);
+            return None;
+        }
+    };
+
+    let msi = controller.enable_msi(crate::interrupt::AHCI_VECTOR);
+
+    let mut ghc = read(abar, HBA_GHC) | GHC_AE;
+    if msi {
+        ghc |= GHC_IE;
+    } else {
+        warn!("AHCI controller {} has no MSI, polling it", controller);
+    }
+    write(abar, HBA_GHC, ghc);
+
+    info!(
+        "Found AHCI controller at {:#x}, ports: {:#010b}.",
+        addr,
+        read(abar, HBA_PI)
+    );
+
+    Some(Hba {
+        abar,
+        msi,
+        ports: core::array::from_fn(|_| PortIrq::default()),
+    })
+}
+
+fn probe_ports(hba: &'static Hba, disks: &mut Vec<AhciDisk>) {
+    let cap = read(hba.abar, HBA_CAP);
+    let implemented = read(hba.abar, HBA_PI);
+
+    for idx in (0..32).filter(|idx| implemented & (1 << idx) != 0) {
+        let irq = hba.msi.then_some(&hba.ports[idx]);
+        let Some(port) = AhciPort::new(port_base(hba.abar, idx), cap & CAP_S64A != 0, irq) else {
+            continue;
+        };
+
+        match AhciDisk::open(port, disks.len()) {
+            Ok(disk) => {
+                info!("Drive {} opened", disk);
+                disks.push(disk);
+            }
+            Err(err) => warn!("Failed to identify AHCI port {}: {:?}", idx, err),
+        }
+    }
+}
+
+/// Clones share the same port
+#[derive(Clone)]
+pub struct AhciDisk {
+    index: usize,
+    blocks: u64,
+    model: Box<str>,
+    serial: Box<str>,
+    port: Arc<Mutex<AhciPort>>,
+}
+
+impl AhciDisk {
+    /// Identifies the drive on the port, `index` is used for its name
+    fn open(mut port: AhciPort, index: usize) -> storage::Result<Self> {
+        let res = port.identify()?;
+
+        let buf = res.map(u16::to_be_bytes).concat();
+        let serial = String::from_utf8_lossy(&buf[20..40]).trim().into();
+        let model = String::from_utf8_lossy(&buf[54..94]).trim().into();
+
+        // words 100 to 103 are the sectors addressable by 48 bits LBA,
+        // words 60 and 61 by 28 bits LBA
+        let lba48 = res[100..104]
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &word| acc << 16 | word as u64);
+        let lba28 = (res[61] as u64) << 16 | res[60] as u64;
+
+        Ok(Self {
+            index,
+            blocks: if lba48 != 0 { lba48 } else { lba28 },
+            model,
+            serial,
+            port: Arc::new(Mutex::new(port)),
+        })
+    }
+
+    /// The name of the drive by its position, `sda`, `sdb`, ...
+    pub fn name(&self) -> String {
+        format!("sd{}", (b'a' + self.index as u8) as char)
+    }
+}
+
+impl core::fmt::Display for AhciDisk {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let (size, unit) = crate::humanized_size(self.blocks * 512);
+        write!(
+            f,
+            "{} {} {} ({} {})",
+            self.name(),
+            self.model,
+            self.serial,
+            size,
+            unit
+        )
+    }
+}
+
+impl BlockDevice<Block512> for AhciDisk {
+    fn block_count(&self) -> storage::Result<usize> {
+        Ok(self.blocks as usize)
+    }
+
+    fn read_block(&self, offset: usize, block: &mut Block512) -> storage::Result<()> {
+        self.read_blocks(offset, core::slice::from_mut(block))
+    }
+
+    fn write_block(&self, offset: usize, block: &Block512) -> storage::Result<()> {
+        self.write_blocks(offset, core::slice::from_ref(block))
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [Block512]) -> storage::Result<()> {
+        let mut port = self.port.lock();
+
+        for (idx, chunk) in blocks.chunks_mut(MAX_SECTORS).enumerate() {
+            let lba = (offset + idx * MAX_SECTORS) as u64;
+            port.command(ATA_CMD_READ_DMA_EXT, lba, chunk.len(), false)?;
+            port.load(chunk);
+        }
+
+        Ok(())
+    }
+
+    fn write_blocks(&self, offset: usize, blocks: &[Block512]) -> storage::Result<()> {
+        let mut port = self.port.lock();
+
+        for (idx, chunk) in blocks.chunks(MAX_SECTORS).enumerate() {
+            let lba = (offset + idx * MAX_SECTORS) as u64;
+            port.store(chunk);
+            port.command(ATA_CMD_WRITE_DMA_EXT, lba, chunk.len(), true)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+//! AHCI Port
+//!
+//! Each port owns a frame holding its command list, its received FIS
+//! area and a single command table, only command slot 0 is used.
+//!
+//! reference: https://wiki.osdev.org/AHCI
+//! reference: https://www.intel.com/content/dam/www/public/us/en/documents/technical-specifications/serial-ata-ahci-spec-rev1-3-1.pdf
+
+use crate::memory::*;
+use crate::proc::{self, ProcessId};
+use crate::tasks::timer;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+use storage::{Block512, DeviceError};
+use x86_64::structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame};
+
+/// Frames of the transfer buffer, each one is described by a PRD entry
+const BUFFER_FRAMES: usize = 16;
+
+/// The most sectors transferred by a single command
+pub const MAX_SECTORS: usize = BUFFER_FRAMES * SECTORS_PER_FRAME;
+
+const SECTORS_PER_FRAME: usize = FRAME_SIZE as usize / 512;
+
+/// Offsets in the frame of the port, with the alignments required
+const COMMAND_LIST_OFFSET: u64 = 0x000; // 1 KiB aligned
+const RECEIVED_FIS_OFFSET: u64 = 0x400; // 256 bytes aligned
+const COMMAND_TABLE_OFFSET: u64 = 0x500; // 128 bytes aligned
+
+/// The PRD table follows the command FIS and the ATAPI command
+const PRDT_OFFSET: u64 = 0x80;
+
+/// Ticks to wait for a command before giving up
+const COMMAND_TIMEOUT: u64 = 10_000;
+
+/// Loops to wait for the engines of the port to stop
+const SPIN_TIMEOUT: usize = 1_000_000;
+
+/// Port registers, relative to the port base
+mod reg {
+    pub const CLB: u64 = 0x00;
+    pub const CLBU: u64 = 0x04;
+    pub const FB: u64 = 0x08;
+    pub const FBU: u64 = 0x0C;
+    pub const IS: u64 = 0x10;
+    pub const IE: u64 = 0x14;
+    pub const CMD: u64 = 0x18;
+    pub const TFD: u64 = 0x20;
+    pub const SIG: u64 = 0x24;
+    pub const SSTS: u64 = 0x28;
+    pub const SERR: u64 = 0x30;
+    pub const CI: u64 = 0x38;
+}
+
+/// Start processing the command list
+const CMD_ST: u32 = 1 << 0;
+/// Receive FIS enable
+const CMD_FRE: u32 = 1 << 4;
+/// The FIS receive DMA engine is running
+const CMD_FR: u32 = 1 << 14;
+/// The command list DMA engine is running
+const CMD_CR: u32 = 1 << 15;
+
+const TFD_ERR: u32 = 1 << 0;
+const TFD_DRQ: u32 = 1 << 3;
+const TFD_BSY: u32 = 1 << 7;
+
+/// Bits of the interrupt status and enable registers
+const IS_DHRS: u32 = 1 << 0;
+const IS_PSS: u32 = 1 << 1;
+const IS_IFS: u32 = 1 << 27;
+const IS_HBDS: u32 = 1 << 28;
+const IS_HBFS: u32 = 1 << 29;
+const IS_TFES: u32 = 1 << 30;
+
+/// The command failed with the task file or a bus error
+const IS_ERRORS: u32 = IS_TFES | IS_HBFS | IS_HBDS | IS_IFS;
+
+/// Interrupts of the port, on the register or PIO setup FIS
+/// that completes the command, and on the errors
+const IE_COMMAND: u32 = IS_DHRS | IS_PSS | IS_ERRORS;
+
+/// Device detected and communication established
+const SSTS_DET_PRESENT: u32 = 3;
+/// Interface in active state
+const SSTS_IPM_ACTIVE: u32 = 1;
+
+/// Signature of the SATA drives, ATAPI and others are not supported
+pub const SIG_ATA: u32 = 0x0000_0101;
+
+/// Register FIS from the host to the device
+const FIS_TYPE_REG_H2D: u8 = 0x27;
+
+pub const ATA_CMD_READ_DMA_EXT: u8 = 0x25;
+pub const ATA_CMD_WRITE_DMA_EXT: u8 = 0x35;
+pub const ATA_CMD_IDENTIFY: u8 = 0xEC;
+
+/// The interrupt of a port, shared with the interrupt handler
+#[derive(Debug, Default)]
+pub struct PortIrq {
+    /// The process blocked for the command, 0 if there is none
+    waiter: AtomicU16,
+    /// Collected by the interrupt handler, cleared before each command
+    status: AtomicU32,
+}
+
+impl PortIrq {
+    /// Acknowledges the interrupt of the port at `base`
+    /// and wakes up the process waiting for it
+    pub fn handle(&self, base: u64) {
+        let status = read(base, reg::IS);
+        write(base, reg::IS, status);
+
+        self.status.fetch_or(status, Ordering::Release);
+
+        match self.waiter.load(Ordering::Acquire) {
+            0 => {}
+            pid => proc::wake_up(ProcessId(pid)),
+        }
+    }
+}
+
+/// The registers and the memory of an implemented port
+#[derive(Debug)]
+pub struct AhciPort {
+    base: u64,
+    frame: PhysFrame,
+    buffer: Vec<PhysFrame>,
+    /// The commands are polled without the interrupt
+    irq: Option<&'static PortIrq>,
+}
+
+impl AhciPort {
+    /// `base` is the virtual address of the port registers, returns
+    /// `None` if no SATA drive is attached or the memory is not available.
+    /// Without 64 bits addressing the memory must be below 4 GiB.
+    pub fn new(base: u64, addr64: bool, irq: Option<&'static PortIrq>) -> Option<Self> {
+        let ssts = read(base, reg::SSTS);
+
+        if ssts & 0xF != SSTS_DET_PRESENT || (ssts >> 8) & 0xF != SSTS_IPM_ACTIVE {
+            return None;
+        }
+
+        if read(base, reg::SIG) != SIG_ATA {
+            debug!("AHCI port {:#x} is not a SATA drive", base);
+            return None;
+        }
+
+        let mut alloc = get_frame_alloc_for_sure();
+
+        let frames: Vec<_> = (0..=BUFFER_FRAMES)
+            .map_while(|_| alloc.allocate_frame())
+            .collect();
+
+        let addressable = addr64
+            || frames
+                .iter()
+                .all(|frame| frame.start_address().as_u64() + FRAME_SIZE <= 1 << 32);
+
+        if frames.len() <= BUFFER_FRAMES || !addressable {
+            for frame in frames {
+                unsafe { alloc.deallocate_frame(frame) };
+            }
+            return None;
+        }
+
+        drop(alloc);
+
+        let mut frames = frames.into_iter();
+
+        let mut port = Self {
+            base,
+            frame: frames.next().unwrap(),
+            buffer: frames.collect(),
+            irq,
+        };
+
+        port.rebase().ok().map(|_| port)
+    }
+
+    #[inline]
+    fn read(&self, offset: u64) -> u32 {
+        read(self.base, offset)
+    }
+
+    #[inline]
+    fn write(&self, offset: u64, value: u32) {
+        write(self.base, offset, value)
+    }
+
+    /// Returns the physical and virtual address in the frame of the port
+    #[inline]
+    fn area(&self, offset: u64) -> (u64, u64) {
+        let phys = self.frame.start_address().as_u64() + offset;
+        (phys, physical_to_virtual(phys))
+    }
+
+    /// Spins until the bits of the register are cleared
+    fn spin_until_clear(&self, offset: u64, bits: u32) -> bool {
+        (0..SPIN_TIMEOUT).any(|_| {
+            let cleared = self.read(offset) & bits == 0;
+            core::hint::spin_loop();
+            cleared
+        })
+    }
+
+    /// The interrupt status of the port, with the one collected
+    /// by the interrupt handler
+    fn status(&self) -> u32 {
+        let collected = self.irq.map_or(0, |irq| irq.status.load(Ordering::Acquire));
+        self.read(reg::IS) | collected
+    }
+
+    /// The command on slot 0 is done or failed
+    fn finished(&self) -> bool {
+        self.read(reg::CI) & 1 == 0 || self.status() & IS_ERRORS != 0
+    }
+
+    /// Stops the engines, points the port at its memory and restarts it
+    fn rebase(&mut self) -> storage::Result<()> {
+        let cmd = self.read(reg::CMD);
+        self.write(reg::CMD, cmd & !(CMD_ST | CMD_FRE));
+
+        if !self.spin_until_clear(reg::CMD, CMD_CR | CMD_FR) {
+            warn!("AHCI port {:#x} failed to stop", self.base);
+            return Err(DeviceError::Busy.into());
+        }
+
+        let (phys, virt) = self.area(0);
+        unsafe { core::ptr::write_bytes(virt as *mut u8, 0, FRAME_SIZE as usize) };
+
+        let clb = phys + COMMAND_LIST_OFFSET;
+        let fb = phys + RECEIVED_FIS_OFFSET;
+
+        self.write(reg::CLB, clb as u32);
+        self.write(reg::CLBU, (clb >> 32) as u32);
+        self.write(reg::FB, fb as u32);
+        self.write(reg::FBU, (fb >> 32) as u32);
+
+        // the command header of slot 0 points to the only command table
+        let ctba = phys + COMMAND_TABLE_OFFSET;
+        let header = (virt + COMMAND_LIST_OFFSET) as *mut u32;
+        unsafe {
+            header.add(2).write_volatile(ctba as u32);
+            header.add(3).write_volatile((ctba >> 32) as u32);
+        }
+
+        // the bits are cleared by writing ones
+        self.write(reg::SERR, u32::MAX);
+        self.write(reg::IS, u32::MAX);
+        self.write(reg::IE, if self.irq.is_some() { IE_COMMAND } else { 0 });
+
+        let cmd = self.read(reg::CMD);
+        self.write(reg::CMD, cmd | CMD_FRE);
+
+        if !self.spin_until_clear(reg::TFD, TFD_BSY | TFD_DRQ) {
+            warn!("AHCI port {:#x} is busy", self.base);
+            return Err(DeviceError::Busy.into());
+        }
+
+        let cmd = self.read(reg::CMD);
+        self.write(reg::CMD, cmd | CMD_ST);
+
+        Ok(())
+    }
+
+    /// Issues the command for `count` sectors from `lba` on slot 0 and
+    /// blocks until it is done, the data is transferred to or from the
+    /// buffer of the port.
+    pub fn command(
+        &mut self,
+        command: u8,
+        lba: u64,
+        count: usize,
+        write: bool,
+    ) -> storage::Result<()> {
+        debug_assert!(count <= MAX_SECTORS);
+
+        let (_, table) = self.area(COMMAND_TABLE_OFFSET);
+
+        let fis = unsafe { core::slice::from_raw_parts_mut(table as *mut u8, 20) };
+        fis.fill(0);
+        fis[0] = FIS_TYPE_REG_H2D;
+        fis[1] = 1 << 7; // the FIS is a command
+        fis[2] = command;
+        fis[4..7].copy_from_slice(&lba.to_le_bytes()[0..3]);
+        fis[7] = 1 << 6; // LBA mode
+        fis[8..11].copy_from_slice(&lba.to_le_bytes()[3..6]);
+        fis[12..14].copy_from_slice(&(count as u16).to_le_bytes());
+
+        let prdt = (table + PRDT_OFFSET) as *mut [u32; 4];
+        let frames = (count * 512).div_ceil(FRAME_SIZE as usize);
+        let mut remain = count * 512;
+
+        for (idx, frame) in self.buffer.iter().take(frames).enumerate() {
+            let len = remain.min(FRAME_SIZE as usize);
+            remain -= len;
+
+            let addr = frame.start_address().as_u64();
+            let entry = [addr as u32, (addr >> 32) as u32, 0, len as u32 - 1];
+            unsafe { prdt.add(idx).write_volatile(entry) };
+        }
+
+        // the command FIS is 5 dwords long
+        let mut flags = 5 | (frames as u32) << 16;
+        if write {
+            flags |= 1 << 6;
+        }
+
+        let (_, header) = self.area(COMMAND_LIST_OFFSET);
+        unsafe {
+            let header = header as *mut u32;
+            header.write_volatile(flags);
+            // the byte count transferred, updated by the HBA
+            header.add(1).write_volatile(0);
+        }
+
+        if !self.spin_until_clear(reg::TFD, TFD_BSY | TFD_DRQ) {
+            return Err(DeviceError::Busy.into());
+        }
+
+        self.write(reg::IS, u32::MAX);
+
+        let deadline = timer::ticks() + COMMAND_TIMEOUT;
+
+        match self.irq {
+            Some(irq) => {
+                irq.status.store(0, Ordering::Release);
+                irq.waiter
+                    .store(proc::current_pid().into(), Ordering::Release);
+
+                self.write(reg::CI, 1);
+                proc::block_until(|| self.finished(), Some(deadline));
+
+                irq.waiter.store(0, Ordering::Release);
+            }
+            None => {
+                // checked after each timer tick without the interrupt
+                self.write(reg::CI, 1);
+                proc::block_until(|| self.finished(), Some(deadline));
+            }
+        }
+
+        let failed = self.read(reg::CI) & 1 != 0
+            || self.status() & IS_ERRORS != 0
+            || self.read(reg::TFD) & TFD_ERR != 0;
+
+        if failed {
+            warn!(
+                "AHCI command {:#x} failed on port {:#x}, tfd: {:#x}",
+                command,
+                self.base,
+                self.read(reg::TFD)
+            );
+            // restart the port to clear the error
+            self.rebase()?;
+            let err = if write {
+                DeviceError::WriteError
+            } else {
+                DeviceError::ReadError
+            };
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the buffer of the sector in the transfer
+    fn sector(&mut self, idx: usize) -> &mut [u8] {
+        let frame = self.buffer[idx / SECTORS_PER_FRAME];
+        let offset = (idx % SECTORS_PER_FRAME) * 512;
+
+        unsafe {
+            let ptr = physical_to_virtual(frame.start_address().as_u64()) as *mut u8;
+            core::slice::from_raw_parts_mut(ptr.add(offset), 512)
+        }
+    }
+
+    /// Copies the blocks into the buffer before writing them
+    pub fn store(&mut self, bufs: &[Block512]) {
+        for (idx, buf) in bufs.iter().enumerate() {
+            self.sector(idx).copy_from_slice(buf.as_ref());
+        }
+    }
+
+    /// Copies the blocks out of the buffer after reading them
+    pub fn load(&mut self, bufs: &mut [Block512]) {
+        for (idx, buf) in bufs.iter_mut().enumerate() {
+            buf.as_mut().copy_from_slice(self.sector(idx));
+        }
+    }
+
+    /// Reads the identify data of the drive
+    pub fn identify(&mut self) -> storage::Result<[u16; 256]> {
+        self.command(ATA_CMD_IDENTIFY, 0, 1, false)?;
+
+        let mut data = [0u16; 256];
+        for (word, bytes) in data.iter_mut().zip(self.sector(0).chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+
+        Ok(data)
+    }
+}
+
+#[inline]
+pub fn read(base: u64, offset: u64) -> u32 {
+    unsafe { ((base + offset) as *const u32).read_volatile() }
+}
+
+#[inline]
+pub fn write(base: u64, offset: u64, value: u32) {
+    unsafe { ((base + offset) as *mut u32).write_volatile(value) }
+}
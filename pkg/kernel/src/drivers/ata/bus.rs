@@ -6,10 +6,15 @@
 //! reference: https://github.com/theseus-os/Theseus/blob/HEAD/kernel/ata/src/lib.rs
 
 use super::consts::*;
+use super::dma::BusMaster;
 use alloc::boxed::Box;
+use storage::Block512;
 use x86_64::instructions::port::*;
 
-#[derive(Debug, Clone)]
+/// The sector count register is 8 bits wide, 0 stands for 256 sectors
+pub const MAX_SECTORS: usize = 256;
+
+#[derive(Debug)]
 #[allow(dead_code)]
 pub struct AtaBus {
     id: u8,
@@ -32,11 +37,13 @@ pub struct AtaBus {
     alternate_status: PortReadOnly<u8>,
     control: PortWriteOnly<u8>,
     drive_blockess: PortReadOnly<u8>,
+    /// The bus master of the controller, `None` if DMA is not available
+    dma: Option<BusMaster>,
 }
 
 impl AtaBus {
-    pub fn new(id: u8, irq: u8, io_base: u16, ctrl_base: u16) -> Self {
-        Self {
+    pub fn new(id: u8, irq: u8, io_base: u16, ctrl_base: u16, bmide: Option<u16>) -> Self {
+        let mut bus = Self {
             id,
             irq,
             io_base,
@@ -55,7 +62,17 @@ impl AtaBus {
             alternate_status: PortReadOnly::new(ctrl_base),
             control: PortWriteOnly::new(ctrl_base),
             drive_blockess: PortReadOnly::new(ctrl_base + 1),
+            dma: bmide.and_then(BusMaster::new),
+        };
+
+        if bus.dma.is_none() {
+            info!("ATA bus {} uses PIO only.", id);
         }
+
+        // clear nIEN to let the drives raise interrupts
+        unsafe { bus.control.write(0) };
+
+        bus
     }
 
     #[inline]
@@ -132,18 +149,30 @@ impl AtaBus {
     /// Writes the given command
     ///
     /// reference: https://wiki.osdev.org/ATA_PIO_Mode#28_bit_PIO
-    fn write_command(&mut self, drive: u8, block: u32, cmd: AtaCommand) -> storage::Result<()> {
+    fn write_command(&mut self, drive: u8, block: u32, count: usize, cmd: AtaCommand) {
+        debug_assert!((1..=MAX_SECTORS).contains(&count));
+
         let bytes = block.to_le_bytes();
 
+        // the interrupt of the last command is not for this one
+        super::clear_irq(self.id);
+
         unsafe {
             self.drive.write(0xE0 | (drive << 4) | (bytes[3] & 0x0F));
-            // just 1 sector for current implementation
-            self.sector_count.write(1);
+            self.sector_count.write(count as u8);
             self.lba_low.write(bytes[0]);
             self.lba_mid.write(bytes[1]);
             self.lba_high.write(bytes[2]);
             self.command.write(cmd as u8);
         }
+    }
+
+    /// Waits until the drive is ready to transfer the next sector,
+    /// blocks for the interrupt of the drive if `irq` is set.
+    fn wait_data_request(&mut self, cmd: AtaCommand, irq: bool) -> storage::Result<()> {
+        if irq {
+            super::wait_irq(self.id)?;
+        }
 
         self.poll(AtaStatus::BUSY, false);
 
@@ -170,8 +199,16 @@ impl AtaBus {
     pub(super) fn identify_drive(&mut self, drive: u8) -> storage::Result<AtaDeviceType> {
         info!("Identifying drive {}", drive);
 
+        // a floating bus reads as 0xFF, there is no drive attached
+        if self.status().is_all() {
+            return Ok(AtaDeviceType::None);
+        }
+
+        // probed by polling, the interrupts may not be enabled yet
+        self.write_command(drive, 0, 1, AtaCommand::IdentifyDevice);
+
         if self
-            .write_command(drive, 0, AtaCommand::IdentifyDevice)
+            .wait_data_request(AtaCommand::IdentifyDevice, false)
             .is_err()
         {
             if self.status().is_empty() {
@@ -192,44 +229,159 @@ impl AtaBus {
         })
     }
 
-    /// Reads a block from the given drive and block number into the given buffer.
-    ///
-    /// reference: https://wiki.osdev.org/ATA_PIO_Mode#28_bit_PIO
-    /// reference: https://wiki.osdev.org/IDE#Read.2FWrite_From_ATA_Drive
-    pub(super) fn read_pio(
+    /// Reads consecutive blocks with DMA if `dma` is set and the bus supports it,
+    /// at most `DMA_SECTORS` or `MAX_SECTORS` blocks at a time.
+    pub(super) fn read(
         &mut self,
         drive: u8,
+        dma: bool,
         block: u32,
-        buf: &mut [u8],
+        bufs: &mut [Block512],
     ) -> storage::Result<()> {
-        self.write_command(drive, block, AtaCommand::ReadPio)?;
+        if !dma || self.dma.is_none() {
+            return self.read_pio(drive, block, bufs);
+        }
+
+        self.read_dma(drive, block, bufs).or_else(|err| {
+            warn!("ATA DMA read failed: {:?}, retry with PIO", err);
+            self.read_pio(drive, block, bufs)
+        })
+    }
 
-        for chunk in buf.chunks_mut(2) {
-            let data = self.read_data().to_le_bytes();
-            chunk.clone_from_slice(&data);
+    /// Writes consecutive blocks with DMA if `dma` is set and the bus supports it
+    pub(super) fn write(
+        &mut self,
+        drive: u8,
+        dma: bool,
+        block: u32,
+        bufs: &[Block512],
+    ) -> storage::Result<()> {
+        if !dma || self.dma.is_none() {
+            return self.write_pio(drive, block, bufs);
         }
 
-        if self.is_error() {
-            debug!("ATA error: data read error");
+        self.write_dma(drive, block, bufs).or_else(|err| {
+            warn!("ATA DMA write failed: {:?}, retry with PIO", err);
+            self.write_pio(drive, block, bufs)
+        })
+    }
+
+    #[inline]
+    pub(super) fn has_dma(&self) -> bool {
+        self.dma.is_some()
+    }
+
+    /// The bus master of the bus, the transfers fall back to PIO without it
+    #[inline]
+    fn bus_master(&mut self) -> storage::Result<&mut BusMaster> {
+        self.dma
+            .as_mut()
+            .ok_or_else(|| storage::DeviceError::InvalidOperation.into())
+    }
+
+    /// Waits for the drive to interrupt at the end of a DMA transfer
+    fn wait_dma(&mut self, cmd: AtaCommand) -> storage::Result<()> {
+        if let Err(err) = super::wait_irq(self.id) {
+            // stop the transfer before retrying with PIO
+            self.bus_master()?.finish();
+            return Err(err);
+        }
+
+        self.poll(AtaStatus::BUSY, false);
+
+        let bus_master_ok = self.bus_master()?.finish();
+
+        if !bus_master_ok || self.is_error() {
+            warn!("ATA error: {:?} transfer error", cmd);
             self.debug();
-            Err(storage::DeviceError::ReadError.into())
-        } else {
-            Ok(())
+            return Err(storage::DeviceError::InvalidOperation.into());
+        }
+
+        Ok(())
+    }
+
+    /// Reads consecutive blocks from the given drive and block number
+    /// into the given buffers by the bus master.
+    ///
+    /// reference: https://wiki.osdev.org/ATA/ATAPI_using_DMA
+    fn read_dma(&mut self, drive: u8, block: u32, bufs: &mut [Block512]) -> storage::Result<()> {
+        self.bus_master()?.prepare(bufs.len(), true);
+        self.write_command(drive, block, bufs.len(), AtaCommand::ReadDma);
+        self.bus_master()?.start();
+
+        self.wait_dma(AtaCommand::ReadDma)?;
+        self.bus_master()?.load(bufs);
+
+        Ok(())
+    }
+
+    /// Writes consecutive blocks to the given drive and block number
+    /// from the given buffers by the bus master.
+    fn write_dma(&mut self, drive: u8, block: u32, bufs: &[Block512]) -> storage::Result<()> {
+        self.bus_master()?.store(bufs);
+        self.bus_master()?.prepare(bufs.len(), false);
+        self.write_command(drive, block, bufs.len(), AtaCommand::WriteDma);
+        self.bus_master()?.start();
+
+        self.wait_dma(AtaCommand::WriteDma)
+    }
+
+    /// Reads consecutive blocks from the given drive and block number
+    /// into the given buffers with a single command.
+    ///
+    /// reference: https://wiki.osdev.org/ATA_PIO_Mode#28_bit_PIO
+    /// reference: https://wiki.osdev.org/IDE#Read.2FWrite_From_ATA_Drive
+    fn read_pio(&mut self, drive: u8, block: u32, bufs: &mut [Block512]) -> storage::Result<()> {
+        self.write_command(drive, block, bufs.len(), AtaCommand::ReadPio);
+
+        for buf in bufs.iter_mut() {
+            // the drive interrupts when each sector is ready to be read
+            self.wait_data_request(AtaCommand::ReadPio, true)?;
+
+            for chunk in buf.as_mut().chunks_mut(2) {
+                let data = self.read_data().to_le_bytes();
+                chunk.clone_from_slice(&data);
+            }
+
+            if self.is_error() {
+                debug!("ATA error: data read error");
+                self.debug();
+                return Err(storage::DeviceError::ReadError.into());
+            }
         }
+
+        Ok(())
     }
 
-    /// Writes a block to the given drive and block number from the given buffer.
+    /// Writes consecutive blocks to the given drive and block number
+    /// from the given buffers with a single command.
     ///
     /// reference: https://wiki.osdev.org/ATA_PIO_Mode#28_bit_PIO
     /// reference: https://wiki.osdev.org/IDE#Read.2FWrite_From_ATA_Drive
-    pub(super) fn write_pio(&mut self, drive: u8, block: u32, buf: &[u8]) -> storage::Result<()> {
-        self.write_command(drive, block, AtaCommand::WritePio)?;
+    fn write_pio(&mut self, drive: u8, block: u32, bufs: &[Block512]) -> storage::Result<()> {
+        self.write_command(drive, block, bufs.len(), AtaCommand::WritePio);
+
+        for (idx, buf) in bufs.iter().enumerate() {
+            // the drive interrupts when each sector is written,
+            // except for the first one which is requested at once
+            self.wait_data_request(AtaCommand::WritePio, idx > 0)?;
+
+            for chunk in buf.as_ref().chunks(2) {
+                let data = u16::from_le_bytes(chunk.try_into().unwrap());
+                self.write_data(data);
+            }
 
-        for chunk in buf.chunks(2) {
-            let data = u16::from_le_bytes(chunk.try_into().unwrap());
-            self.write_data(data);
+            if self.is_error() {
+                debug!("ATA error: data write error");
+                self.debug();
+                return Err(storage::DeviceError::WriteError.into());
+            }
         }
 
+        // wait for the last sector to be written
+        super::wait_irq(self.id)?;
+        self.poll(AtaStatus::BUSY, false);
+
         if self.is_error() {
             debug!("ATA error: data write error");
             self.debug();
@@ -0,0 +1,148 @@
+//! ATA Bus Master DMA
+//!
+//! reference: https://wiki.osdev.org/ATA/ATAPI_using_DMA
+//! reference: https://pdos.csail.mit.edu/6.828/2018/readings/hardware/IDE-BusMaster.pdf
+
+use crate::memory::*;
+use alloc::vec::Vec;
+use storage::Block512;
+use x86_64::instructions::port::Port;
+use x86_64::structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame};
+
+/// Frames of the transfer buffer, each one is described by a PRD entry
+const BUFFER_FRAMES: usize = 16;
+
+/// The most sectors transferred by a single command
+pub const DMA_SECTORS: usize = BUFFER_FRAMES * SECTORS_PER_FRAME;
+
+const SECTORS_PER_FRAME: usize = FRAME_SIZE as usize / 512;
+
+/// Starts the transfer, clearing it aborts the transfer
+const COMMAND_START: u8 = 1 << 0;
+/// Set if the bus master writes to the memory, i.e. reads from the drive
+const COMMAND_READ: u8 = 1 << 3;
+
+const STATUS_ERROR: u8 = 1 << 1;
+const STATUS_INTERRUPT: u8 = 1 << 2;
+
+/// Marks the last entry of the PRD table
+const PRD_END_OF_TABLE: u32 = 1 << 31;
+
+/// The bus master registers of a bus, with the PRD table and the buffer
+#[derive(Debug)]
+pub struct BusMaster {
+    command: Port<u8>,
+    status: Port<u8>,
+    prdt_address: Port<u32>,
+    prdt: PhysFrame,
+    buffer: Vec<PhysFrame>,
+}
+
+impl BusMaster {
+    /// `base` is the I/O port of the bus master registers of the bus,
+    /// returns `None` if the memory for the transfers is not available
+    pub fn new(base: u16) -> Option<Self> {
+        let mut alloc = get_frame_alloc_for_sure();
+
+        let frames: Vec<_> = (0..=BUFFER_FRAMES)
+            .map_while(|_| alloc.allocate_frame())
+            .collect();
+
+        // the PRD entries only hold 32 bits physical addresses
+        let addressable = frames
+            .iter()
+            .all(|frame| frame.start_address().as_u64() + FRAME_SIZE <= 1 << 32);
+
+        if frames.len() <= BUFFER_FRAMES || !addressable {
+            for frame in frames {
+                unsafe { alloc.deallocate_frame(frame) };
+            }
+            return None;
+        }
+
+        let mut frames = frames.into_iter();
+
+        Some(Self {
+            command: Port::new(base),
+            status: Port::new(base + 2),
+            prdt_address: Port::new(base + 4),
+            prdt: frames.next().unwrap(),
+            buffer: frames.collect(),
+        })
+    }
+
+    /// Describes the buffer for `count` sectors in the PRD table and
+    /// sets the direction, the transfer starts after the ATA command.
+    pub fn prepare(&mut self, count: usize, read: bool) {
+        debug_assert!((1..=DMA_SECTORS).contains(&count));
+
+        let prdt = unsafe {
+            let ptr = physical_to_virtual(self.prdt.start_address().as_u64());
+            core::slice::from_raw_parts_mut(ptr as *mut [u32; 2], BUFFER_FRAMES)
+        };
+
+        let frames = count.div_ceil(SECTORS_PER_FRAME);
+        let mut remain = count * 512;
+
+        for (entry, frame) in prdt.iter_mut().zip(&self.buffer).take(frames) {
+            let len = remain.min(FRAME_SIZE as usize);
+            remain -= len;
+
+            let flags = if remain == 0 { PRD_END_OF_TABLE } else { 0 };
+            *entry = [frame.start_address().as_u64() as u32, len as u32 | flags];
+        }
+
+        unsafe {
+            self.command.write(if read { COMMAND_READ } else { 0 });
+            self.prdt_address
+                .write(self.prdt.start_address().as_u64() as u32);
+            // the bits are cleared by writing ones
+            self.status.write(STATUS_ERROR | STATUS_INTERRUPT);
+        }
+    }
+
+    pub fn start(&mut self) {
+        unsafe {
+            let command = self.command.read();
+            self.command.write(command | COMMAND_START);
+        }
+    }
+
+    /// Stops the transfer, returns false if the bus master failed
+    pub fn finish(&mut self) -> bool {
+        unsafe {
+            let command = self.command.read();
+            self.command.write(command & !COMMAND_START);
+
+            let status = self.status.read();
+            self.status.write(STATUS_ERROR | STATUS_INTERRUPT);
+
+            status & STATUS_ERROR == 0
+        }
+    }
+
+    /// Returns the buffer of the sector in the transfer
+    fn sector(&mut self, idx: usize) -> &mut [u8] {
+        let frame = self.buffer[idx / SECTORS_PER_FRAME];
+        let offset = (idx % SECTORS_PER_FRAME) * 512;
+
+        unsafe {
+            let ptr = physical_to_virtual(frame.start_address().as_u64()) as *mut u8;
+            core::slice::from_raw_parts_mut(ptr.add(offset), 512)
+        }
+    }
+
+    /// Copies the blocks into the buffer before writing them
+    pub fn store(&mut self, bufs: &[Block512]) {
+        for (idx, buf) in bufs.iter().enumerate() {
+            self.sector(idx).copy_from_slice(buf.as_ref());
+        }
+    }
+
+    /// Copies the blocks out of the buffer after reading them
+    pub fn load(&mut self, bufs: &mut [Block512]) {
+        for (idx, buf) in bufs.iter_mut().enumerate() {
+            buf.as_mut().copy_from_slice(self.sector(idx));
+        }
+    }
+}
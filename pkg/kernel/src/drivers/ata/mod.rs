@@ -7,17 +7,37 @@
 
 mod bus;
 mod consts;
+mod dma;
 
-use alloc::{boxed::Box, string::String};
-use bus::AtaBus;
+use crate::proc::{self, ProcessId};
+use crate::tasks::timer;
+use alloc::collections::VecDeque;
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use bus::{AtaBus, MAX_SECTORS};
 use consts::AtaDeviceType;
+use core::sync::atomic::{AtomicBool, Ordering};
+use dma::DMA_SECTORS;
 use spin::Mutex;
+use x86_64::instructions::port::PortReadOnly;
+
+/// The I/O ports of the buses, `(io_base, ctrl_base)`
+const PORTS: [(u16, u16); 2] = [(0x1F0, 0x3F6), (0x170, 0x376)];
+
+/// Ticks to wait for the interrupt before the command fails
+const IRQ_TIMEOUT: u64 = 1000;
 
 lazy_static! {
     pub static ref BUSES: [Mutex<AtaBus>; 2] = {
+        let bmide = find_bus_master();
         let buses = [
-            Mutex::new(AtaBus::new(0, 14, 0x1F0, 0x3F6)),
-            Mutex::new(AtaBus::new(1, 15, 0x170, 0x376)),
+            Mutex::new(AtaBus::new(0, 14, PORTS[0].0, PORTS[0].1, bmide)),
+            Mutex::new(AtaBus::new(
+                1,
+                15,
+                PORTS[1].0,
+                PORTS[1].1,
+                bmide.map(|base| base + 8),
+            )),
         ];
 
         info!("Initialized ATA Buses.");
@@ -26,11 +46,135 @@ lazy_static! {
     };
 }
 
+/// Finds the Bus Master IDE registers of the IDE controller,
+/// the registers of the secondary bus follow the primary ones.
+fn find_bus_master() -> Option<u16> {
+    // mass storage controller, IDE interface
+    let Some(controller) = crate::pci::find_class(0x01, 0x01) else {
+        info!("No PCI IDE controller found.");
+        return None;
+    };
+
+    // bit 7 of the programming interface indicates bus mastering
+    if controller.prog_if & 0x80 == 0 {
+        info!("IDE controller does not support bus mastering.");
+        return None;
+    }
+
+    // BAR4 is the I/O space of the bus master registers
+    let crate::pci::Bar::Io(base) = controller.bar(4) else {
+        return None;
+    };
+
+    controller.enable_bus_master();
+
+    info!("Found Bus Master IDE at {:#x}.", base);
+
+    Some(base)
+}
+
+/// The requests of a bus, shared with its interrupt handler
+struct AtaQueue {
+    /// Processes waiting for the bus, the first one is using it
+    requests: Mutex<VecDeque<ProcessId>>,
+    /// Set by the interrupt handler, cleared before each command
+    irq: AtomicBool,
+}
+
+impl AtaQueue {
+    const fn new() -> Self {
+        Self {
+            requests: Mutex::new(VecDeque::new()),
+            irq: AtomicBool::new(false),
+        }
+    }
+
+    /// The process using the bus
+    fn owner(&self) -> Option<ProcessId> {
+        self.requests.lock().front().copied()
+    }
+}
+
+static QUEUES: [AtaQueue; 2] = [AtaQueue::new(), AtaQueue::new()];
+
+/// Called by the interrupt handler of the bus
+pub fn handle_irq(bus: u8) {
+    // reading the status acknowledges the interrupt of the drive,
+    // the bus is locked by the process waiting for it
+    let mut status = PortReadOnly::<u8>::new(PORTS[bus as usize].0 + 7);
+    unsafe { status.read() };
+
+    let queue = &QUEUES[bus as usize];
+    queue.irq.store(true, Ordering::Release);
+
+    if let Some(pid) = queue.owner() {
+        proc::wake_up(pid);
+    }
+}
+
+fn clear_irq(bus: u8) {
+    QUEUES[bus as usize].irq.store(false, Ordering::Release);
+}
+
+/// Blocks the current process until the bus raises its interrupt,
+/// fails if the interrupt is lost
+fn wait_irq(bus: u8) -> storage::Result<()> {
+    let irq = &QUEUES[bus as usize].irq;
+    let deadline = timer::ticks() + IRQ_TIMEOUT;
+
+    if proc::block_until(|| irq.swap(false, Ordering::Acquire), Some(deadline)) {
+        Ok(())
+    } else {
+        warn!("ATA bus {} lost the interrupt of the drive", bus);
+        Err(storage::DeviceError::Timeout.into())
+    }
+}
+
+/// Runs the request on the bus once the earlier requests are done,
+/// the process is blocked until the one before it wakes it up
+fn submit<T>(bus: u8, request: impl FnOnce(&mut AtaBus) -> T) -> T {
+    // the queue is also read by the interrupt handler, and the
+    // interrupts are only taken while blocked for the drive
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let queue = &QUEUES[bus as usize];
+        let pid = proc::current_pid();
+
+        queue.requests.lock().push_back(pid);
+        proc::block_until(|| queue.owner() == Some(pid), None);
+
+        let ret = request(&mut BUSES[bus as usize].lock());
+
+        let mut requests = queue.requests.lock();
+        requests.pop_front();
+
+        if let Some(&next) = requests.front() {
+            drop(requests);
+            proc::wake_up(next);
+        }
+
+        ret
+    })
+}
+
+static DRIVES: spin::Once<Vec<AtaDrive>> = spin::Once::new();
+
+/// Returns the drives found on both buses, probed on the first call
+pub fn drives() -> &'static [AtaDrive] {
+    DRIVES.call_once(|| {
+        [(0, 0), (0, 1), (1, 0), (1, 1)]
+            .into_iter()
+            .filter_map(|(bus, dsk)| AtaDrive::open(bus, dsk))
+            .collect()
+    })
+}
+
 #[derive(Clone)]
 pub struct AtaDrive {
     pub bus: u8,
     pub drive: u8,
     blocks: u32,
+    /// Whether the drive supports DMA transfers
+    dma: bool,
     model: Box<str>,
     serial: Box<str>,
 }
@@ -38,17 +182,25 @@ pub struct AtaDrive {
 impl AtaDrive {
     pub fn open(bus: u8, dsk: u8) -> Option<Self> {
         trace!("Opening drive {}@{}...", bus, dsk);
-        if let Ok(AtaDeviceType::Pata(res)) = BUSES[bus as usize].lock().identify_drive(dsk) {
+        let mut ata_bus = BUSES[bus as usize].lock();
+        let device = ata_bus.identify_drive(dsk);
+        let has_dma = ata_bus.has_dma();
+        drop(ata_bus);
+
+        if let Ok(AtaDeviceType::Pata(res)) = device {
             let buf = res.map(u16::to_be_bytes).concat();
             let serial = String::from_utf8_lossy(&buf[20..40]).trim().into();
             let model = String::from_utf8_lossy(&buf[54..94]).trim().into();
             let blocks = u32::from_be_bytes(buf[120..124].try_into().unwrap()).rotate_left(16);
+            // word 49 bit 8 indicates DMA support
+            let dma = res[49] & (1 << 8) != 0 && has_dma;
             let drive = Self {
                 bus,
                 drive: dsk,
                 model,
                 serial,
                 blocks,
+                dma,
             };
             info!("Drive {} opened", drive);
             Some(drive)
@@ -58,6 +210,16 @@ impl AtaDrive {
         }
     }
 
+    /// The name of the drive by its position, `hda` to `hdd`
+    pub fn name(&self) -> String {
+        format!("hd{}", (b'a' + self.bus * 2 + self.drive) as char)
+    }
+
+    /// The most sectors transferred by a single command
+    fn max_sectors(&self) -> usize {
+        if self.dma { DMA_SECTORS } else { MAX_SECTORS }
+    }
+
     fn humanized_size(&self) -> (f32, &'static str) {
         let size = self.block_size();
         let count = self.block_count().unwrap();
@@ -82,14 +244,28 @@ impl BlockDevice<Block512> for AtaDrive {
     }
 
     fn read_block(&self, offset: usize, block: &mut Block512) -> storage::Result<()> {
-        BUSES[self.bus as usize]
-            .lock()
-            .read_pio(self.drive, offset as u32, block.as_mut())
+        self.read_blocks(offset, core::slice::from_mut(block))
     }
 
     fn write_block(&self, offset: usize, block: &Block512) -> storage::Result<()> {
-        BUSES[self.bus as usize]
-            .lock()
-            .write_pio(self.drive, offset as u32, block.as_ref())
+        self.write_blocks(offset, core::slice::from_ref(block))
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [Block512]) -> storage::Result<()> {
+        for (idx, chunk) in blocks.chunks_mut(self.max_sectors()).enumerate() {
+            let block = (offset + idx * self.max_sectors()) as u32;
+            submit(self.bus, |bus| bus.read(self.drive, self.dma, block, chunk))?;
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&self, offset: usize, blocks: &[Block512]) -> storage::Result<()> {
+        for (idx, chunk) in blocks.chunks(self.max_sectors()).enumerate() {
+            let block = (offset + idx * self.max_sectors()) as u32;
+            submit(self.bus, |bus| {
+                bus.write(self.drive, self.dma, block, chunk)
+            })?;
+        }
+        Ok(())
     }
 }
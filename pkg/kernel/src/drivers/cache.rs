@@ -10,6 +10,8 @@ pub type ATACachedDevice = CachedDevice<Block512, LruCacheImpl>;
 pub type LruValue = Arc<RwLock<ATABlockCache>>;
 pub type LruSharedInner = Arc<Mutex<LruCache<usize, LruValue>>>;
 
+/// Clones share the same cache
+#[derive(Clone)]
 pub struct LruCacheImpl {
     inner: LruSharedInner,
 }
@@ -22,8 +24,10 @@ impl LruCacheImpl {
         }
     }
 
-    pub fn inner(&self) -> LruSharedInner {
-        self.inner.clone()
+    /// Returns the number of cached blocks and the capacity
+    pub fn usage(&self) -> (usize, usize) {
+        let inner = self.inner.lock();
+        (inner.len(), usize::from(inner.cap()))
     }
 }
 
@@ -43,4 +47,19 @@ impl CacheManager<Block512> for LruCacheImpl {
         let mut inner = self.inner.lock();
         inner.put(key, Arc::new(RwLock::new(value)));
     }
+
+    fn evicts_modified(&self) -> bool {
+        let inner = self.inner.lock();
+        inner.len() == usize::from(inner.cap())
+            && inner
+                .peek_lru()
+                .is_some_and(|(_, block)| block.read().is_modified())
+    }
+
+    fn flush_all(&self) -> Result<()> {
+        let inner = self.inner.lock();
+        inner
+            .iter()
+            .try_for_each(|(_, block)| block.write().flush())
+    }
 }
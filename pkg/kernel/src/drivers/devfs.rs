@@ -0,0 +1,128 @@
+use super::filesystem::{cached_disks, get_rootfs};
+use super::serial::get_serial;
+use crate::input::try_get_key;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use pc_keyboard::DecodedKey;
+use storage::devfs::{BlockFile, DevFs};
+use storage::*;
+
+pub fn init() {
+    info!("Initializing devfs...");
+
+    let devfs = DevFs::new();
+
+    devfs.register("console", 0, || Ok(Box::new(Console)));
+    devfs.register("serial0", 0, || Ok(Box::new(Serial)));
+
+    register_drives(&devfs);
+
+    trace!("Device filesystem: {:#?}", devfs);
+
+    get_rootfs()
+        .mount(Box::new(devfs), "/dev")
+        .expect("Failed to mount devfs");
+
+    info!("Initialized devfs.");
+}
+
+/// Registers the ATA drives as hda, hdb, ..., the SATA drives as sda, sdb, ...
+/// and the virtio disks as vda, vdb, ... with their partitions as hda1, hda2, ...
+///
+/// The disks share the caches of the mounted filesystems.
+fn register_drives(devfs: &DevFs) {
+    for (name, disk) in cached_disks() {
+        register_disk(devfs, name, disk);
+    }
+}
+
+fn register_disk<T>(devfs: &DevFs, name: String, disk: T)
+where
+    T: BlockDevice<Block512> + Clone,
+{
+    let parts = match read_partitions(disk.clone()) {
+        Ok(parts) => parts,
+        Err(err) => {
+            warn!("Failed to read partitions of {}: {:?}", name, err);
+            Default::default()
+        }
+    };
+
+    let len = BlockFile::new(disk.clone()).length();
+    devfs.register(&name, len, move || {
+        Ok(Box::new(BlockFile::new(disk.clone())))
+    });
+
+    for (num, part) in parts.into_iter().enumerate() {
+        let len = BlockFile::new(part.clone()).length();
+        devfs.register(&format!("{}{}", name, num + 1), len, move || {
+            Ok(Box::new(BlockFile::new(part.clone())))
+        });
+    }
+}
+
+/// The console, reads from the input buffer and writes to the screen
+struct Console;
+
+impl Read for Console {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.len() < 4 {
+            return Ok(0);
+        }
+
+        match try_get_key() {
+            Some(DecodedKey::Unicode(k)) => Ok(k.encode_utf8(buf).len()),
+            _ => Ok(0),
+        }
+    }
+}
+
+impl Write for Console {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        print!("{}", String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Console {
+    fn seek(&mut self, _pos: SeekFrom) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+/// The first serial port, only for output since the serial
+/// interrupt handler forwards the input to the input buffer
+struct Serial;
+
+impl Read for Serial {
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for Serial {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut serial = get_serial().ok_or(DeviceError::Busy)?;
+
+        for &byte in buf {
+            serial.send_raw(byte);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Serial {
+    fn seek(&mut self, _pos: SeekFrom) -> Result<usize> {
+        Ok(0)
+    }
+}
@@ -1,78 +1,200 @@
+use super::ahci;
 use super::ata::*;
 use super::cache::*;
+use super::virtio;
 use alloc::boxed::Box;
-use chrono::DateTime;
-use storage::fat16::Fat16;
-use storage::mbr::*;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use storage::cpio::CpioFs;
+use storage::fat16::{Fat16, FatType};
+use storage::fat32::Fat32;
+use storage::ramfs::RamFs;
 use storage::*;
 
-pub static ROOTFS: spin::Once<Mount> = spin::Once::new();
+pub static ROOTFS: spin::Once<Vfs> = spin::Once::new();
 
-pub fn get_rootfs() -> &'static Mount {
+pub fn get_rootfs() -> &'static Vfs {
     ROOTFS.get().unwrap()
 }
 
-static CACHE: spin::Once<LruSharedInner> = spin::Once::new();
+/// The caches of the disks
+static CACHES: spin::Mutex<Vec<LruCacheImpl>> = spin::Mutex::new(Vec::new());
+
+/// The disks by name, their partitions are mounted on the same cache
+static DISKS: spin::Mutex<Vec<(String, ATACachedDevice)>> = spin::Mutex::new(Vec::new());
+
+/// Nanoseconds between two flushes
+const FLUSH_INTERVAL: u64 = 5_000_000_000;
+
+/// Returns the cached disks, the raw access to a disk or a partition
+/// goes through the cache as well to keep it coherent with the mounts
+pub fn cached_disks() -> Vec<(String, ATACachedDevice)> {
+    DISKS.lock().clone()
+}
 
 pub fn cache_usage() -> (usize, usize) {
-    let cache = CACHE.get().unwrap().lock();
-    (cache.len(), cache.cap().into())
+    CACHES.lock().iter().fold((0, 0), |(used, total), cache| {
+        let (cache_used, cache_total) = cache.usage();
+        (used + cache_used, total + cache_total)
+    })
 }
 
-pub fn init() {
-    info!("Opening disk device...");
+/// Writes the modified blocks of every cache back to the disks
+pub fn sync() -> Result<()> {
+    let mut result = Ok(());
+
+    for cache in CACHES.lock().iter() {
+        if let Err(err) = cache.flush_all() {
+            warn!("Failed to flush cache: {:?}", err);
+            result = Err(err);
+        }
+    }
+
+    result
+}
 
-    let drive = AtaDrive::open(0, 0).expect("Failed to open disk device");
+/// Kernel task that flushes the caches periodically
+pub async fn flusher() {
+    use crate::tasks::timer;
 
-    // only get the first partition
-    let part = MbrTable::parse(drive)
-        .expect("Failed to parse MBR")
-        .partitions()
-        .expect("Failed to get partitions")
-        .remove(0);
+    loop {
+        // the tick length is calibrated at boot, convert it every time
+        timer::sleep_ticks(timer::nanos_to_ticks(FLUSH_INTERVAL)).await;
 
-    let lru = LruCacheImpl::new();
+        // the caches are also locked by syscalls with interrupts disabled
+        let _ = x86_64::instructions::interrupts::without_interrupts(sync);
+    }
+}
 
-    CACHE.call_once(|| lru.inner());
+pub fn init(boot_info: &'static boot::BootInfo) {
+    storage::set_clock(|| crate::clock::now().and_utc());
 
-    let cache_layer = ATACachedDevice::new(part, lru);
+    let vfs = ROOTFS.call_once(Vfs::new);
 
-    info!("Mounting filesystem...");
+    info!("Mounting filesystems...");
+    // the initramfs is the root if the root partition is not set
+    mount_drives(vfs, boot_info.root, boot_info.initramfs.is_none());
 
-    ROOTFS.call_once(|| Mount::new(Box::new(Fat16::new(cache_layer)), "/".into()));
+    if let Some(archive) = boot_info.initramfs {
+        info!("Mounting initramfs...");
 
-    trace!("Root filesystem: {:#?}", ROOTFS.get().unwrap());
+        // boot from the initramfs if there is no root partition
+        let mount_point = if vfs.is_mount_point("/") {
+            "/initrd"
+        } else {
+            "/"
+        };
+
+        let initramfs = CpioFs::new(archive).expect("Failed to parse initramfs");
+        vfs.mount(Box::new(initramfs), mount_point)
+            .expect("Failed to mount initramfs");
+    }
+
+    if !vfs.is_mount_point("/") {
+        panic!("No root filesystem found, set `root` or `initramfs` in the boot config");
+    }
+
+    vfs.mount(Box::new(RamFs::new()), "/tmp")
+        .expect("Failed to mount tmpfs");
+
+    trace!("Root filesystem: {:#?}", vfs);
 
     info!("Initialized Filesystem.");
 }
 
-pub fn ls(root_path: &str) {
-    let iter = match get_rootfs().read_dir(root_path) {
-        Ok(iter) => iter,
+/// Mounts every partition with a supported filesystem at `/mnt/<name>`,
+/// e.g. `/mnt/hda1`, `/mnt/sda1` or `/mnt/vda1`, except the `root`
+/// partition given by the boot config which is mounted at `/`.
+///
+/// The first partition is mounted at `/` instead if `root` is not set
+/// or not found, unless `fallback` is false.
+fn mount_drives(vfs: &Vfs, root: Option<&str>, fallback: bool) {
+    let mut parts = Vec::new();
+
+    for drive in drives() {
+        open_disk(&mut parts, drive.name(), drive.clone());
+    }
+
+    for disk in ahci::disks() {
+        open_disk(&mut parts, disk.name(), disk.clone());
+    }
+
+    for disk in virtio::block_devices() {
+        open_disk(&mut parts, disk.name(), disk.clone());
+    }
+
+    let mut root_idx = root.and_then(|root| parts.iter().position(|(name, _)| name == root));
+
+    if let Some(root) = root
+        && root_idx.is_none()
+    {
+        warn!("Root partition {} is not found", root);
+    }
+
+    if root_idx.is_none() && fallback && !parts.is_empty() {
+        warn!("Mounting the first partition {} as the root", parts[0].0);
+        root_idx = Some(0);
+    }
+
+    for (idx, (name, fs)) in parts.into_iter().enumerate() {
+        let mount_point = if root_idx == Some(idx) {
+            String::from("/")
+        } else {
+            format!("/mnt/{}", name)
+        };
+
+        match vfs.mount(fs, &mount_point) {
+            Ok(()) => info!("Mounted {} at {}", name, mount_point),
+            Err(err) => warn!("Failed to mount {}: {:?}", name, err),
+        }
+    }
+}
+
+/// Caches the disk and opens its partitions with a supported filesystem
+fn open_disk(
+    parts: &mut Vec<(String, Box<dyn FileSystem>)>,
+    disk_name: String,
+    disk: impl BlockDevice<Block512>,
+) {
+    let lru = LruCacheImpl::new();
+    let disk = ATACachedDevice::new(disk, lru.clone());
+
+    CACHES.lock().push(lru);
+    DISKS.lock().push((disk_name.clone(), disk.clone()));
+
+    let disk_parts = match read_partitions(disk) {
+        Ok(parts) => parts,
         Err(err) => {
-            warn!("{:?}", err);
+            warn!("Failed to read partitions of {}: {:?}", disk_name, err);
             return;
         }
     };
 
-    println!("  Size | Last Modified       | Name");
-
-    for meta in iter {
-        let (size, unit) = crate::humanized_size_short(meta.len as u64);
-        println!(
-            "{:>5.*}{} | {} | {}{}",
-            1,
-            size,
-            unit,
-            meta.modified
-                .map(|t| t.format("%Y/%m/%d %H:%M:%S"))
-                .unwrap_or(
-                    DateTime::from_timestamp_millis(0)
-                        .unwrap()
-                        .format("%Y/%m/%d %H:%M:%S")
-                ),
-            meta.name,
-            if meta.is_dir() { "/" } else { "" }
-        );
+    for (idx, part) in disk_parts.into_iter().enumerate() {
+        let name = format!("{}{}", disk_name, idx + 1);
+
+        match open_partition(part) {
+            Some(fs) => parts.push((name, fs)),
+            None => warn!("Skip {}: no supported filesystem found", name),
+        }
     }
 }
+
+/// Identifies the filesystem by the boot sector of the partition
+fn open_partition(part: Partition<ATACachedDevice, Block512>) -> Option<Box<dyn FileSystem>> {
+    let fs: Box<dyn FileSystem> = match FatType::detect(&part).ok()? {
+        FatType::Fat16 => Box::new(Fat16::new(part)),
+        FatType::Fat32 => Box::new(Fat32::new(part)),
+    };
+
+    Some(fs)
+}
+
+/// Returns the mounted filesystems sorted by their mount points
+pub fn list_mounts() -> Vec<Arc<Mount>> {
+    let mut mounts = get_rootfs().mounts();
+    mounts.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    mounts
+}
@@ -1,14 +1,19 @@
 mod gop;
 mod uart16550;
 
+pub mod ahci;
 pub mod ata;
 pub mod cache;
 pub mod console;
+pub mod devfs;
 pub mod display;
 pub mod filesystem;
 pub mod input;
 pub mod keyboard;
+pub mod pci;
+pub mod pit;
 pub mod serial;
+pub mod virtio;
 
 pub use filesystem::get_rootfs;
 pub use input::{get_key, push_key};
@@ -0,0 +1,77 @@
+//! PCI Configuration Space Access
+//!
+//! reference: https://wiki.osdev.org/PCI#Configuration_Space_Access_Mechanism_.231
+//! reference: https://wiki.osdev.org/PCI_Express#Enhanced_Configuration_Mechanism
+
+use super::mcfg::EcamRegion;
+use crate::memory::physical_to_virtual;
+use x86_64::instructions::port::Port;
+
+const CONFIG_ADDRESS: u16 = 0xCF8;
+const CONFIG_DATA: u16 = 0xCFC;
+
+/// How the configuration space is accessed
+#[derive(Debug, Clone, Copy)]
+pub enum ConfigAccess {
+    /// The legacy mechanism through port 0xCF8 and 0xCFC,
+    /// only the first 256 bytes of each function are accessible
+    Port,
+    /// The memory mapped configuration space of PCI Express
+    Ecam(EcamRegion),
+}
+
+impl ConfigAccess {
+    /// Returns the pointer to the dword in the ECAM region,
+    /// the bus must be covered by the region
+    fn ecam_ptr(region: &EcamRegion, bus: u8, device: u8, function: u8, offset: u16) -> *mut u32 {
+        let offset = ((bus - region.start_bus) as u64) << 20
+            | (device as u64) << 15
+            | (function as u64) << 12
+            | (offset as u64 & 0xFFC);
+
+        physical_to_virtual(region.base + offset) as *mut u32
+    }
+
+    pub fn read(&self, bus: u8, device: u8, function: u8, offset: u16) -> u32 {
+        match self {
+            Self::Ecam(region) if region.contains(bus) => unsafe {
+                Self::ecam_ptr(region, bus, device, function, offset).read_volatile()
+            },
+            _ => {
+                let mut address = Port::<u32>::new(CONFIG_ADDRESS);
+                let mut data = Port::<u32>::new(CONFIG_DATA);
+
+                unsafe {
+                    address.write(port_address(bus, device, function, offset));
+                    data.read()
+                }
+            }
+        }
+    }
+
+    pub fn write(&self, bus: u8, device: u8, function: u8, offset: u16, value: u32) {
+        match self {
+            Self::Ecam(region) if region.contains(bus) => unsafe {
+                Self::ecam_ptr(region, bus, device, function, offset).write_volatile(value)
+            },
+            _ => {
+                let mut address = Port::<u32>::new(CONFIG_ADDRESS);
+                let mut data = Port::<u32>::new(CONFIG_DATA);
+
+                unsafe {
+                    address.write(port_address(bus, device, function, offset));
+                    data.write(value);
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn port_address(bus: u8, device: u8, function: u8, offset: u16) -> u32 {
+    0x8000_0000
+        | (bus as u32) << 16
+        | (device as u32) << 11
+        | (function as u32) << 8
+        | (offset as u32 & 0xFC)
+}
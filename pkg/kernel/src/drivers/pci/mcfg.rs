@@ -0,0 +1,110 @@
+//! ACPI MCFG Table
+//!
+//! Locates the memory mapped configuration space through the RSDP
+//! found in the UEFI configuration table.
+//!
+//! reference: https://wiki.osdev.org/RSDP
+//! reference: https://wiki.osdev.org/PCI_Express#Enhanced_Configuration_Mechanism
+
+use crate::memory::physical_to_virtual;
+
+/// The GUID of the ACPI 2.0 RSDP in the UEFI configuration table
+const ACPI2_GUID: uefi::Guid = uefi::guid!("8868e871-e4f1-11d3-bc22-0080c73c8881");
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+const MCFG_SIGNATURE: &[u8; 4] = b"MCFG";
+
+/// Length of the header shared by all the system description tables
+const SDT_HEADER_LEN: usize = 36;
+
+/// The physical memory is only mapped up to 4 GiB for sure
+const MAPPED_LIMIT: u64 = 0x1_0000_0000;
+
+/// A range of buses in the memory mapped configuration space
+#[derive(Debug, Clone, Copy)]
+pub struct EcamRegion {
+    pub base: u64,
+    pub segment: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+}
+
+impl EcamRegion {
+    #[inline]
+    pub fn contains(&self, bus: u8) -> bool {
+        (self.start_bus..=self.end_bus).contains(&bus)
+    }
+
+    /// Each bus takes 1 MiB of the region
+    fn end(&self) -> u64 {
+        self.base + ((self.end_bus - self.start_bus) as u64 + 1) * 0x10_0000
+    }
+}
+
+#[inline]
+unsafe fn read<T: Copy>(addr: u64) -> T {
+    unsafe { core::ptr::read_unaligned(physical_to_virtual(addr) as *const T) }
+}
+
+/// Finds the ECAM region of the first PCI segment
+pub fn find_ecam() -> Option<EcamRegion> {
+    let rsdp = uefi::system::with_config_table(|tables| {
+        tables
+            .iter()
+            .find(|table| table.guid == ACPI2_GUID)
+            .map(|table| table.address as u64)
+    })?;
+
+    let mcfg = unsafe { find_table(rsdp, MCFG_SIGNATURE)? };
+    let length = unsafe { read::<u32>(mcfg + 4) } as u64;
+
+    // the entries follow the header and 8 reserved bytes
+    let region = (mcfg + SDT_HEADER_LEN as u64 + 8..mcfg + length)
+        .step_by(16)
+        .map(|entry| unsafe {
+            EcamRegion {
+                base: read(entry),
+                segment: read(entry + 8),
+                start_bus: read(entry + 10),
+                end_bus: read(entry + 11),
+            }
+        })
+        .find(|region| region.segment == 0)?;
+
+    if region.end() > MAPPED_LIMIT {
+        warn!("ECAM at {:#x} is not mapped, ignored.", region.base);
+        return None;
+    }
+
+    Some(region)
+}
+
+/// Finds the system description table by its signature through the XSDT,
+/// or the RSDT for ACPI 1.0
+unsafe fn find_table(rsdp: u64, signature: &[u8; 4]) -> Option<u64> {
+    unsafe {
+        if &read::<[u8; 8]>(rsdp) != RSDP_SIGNATURE {
+            warn!("Invalid RSDP at {:#x}.", rsdp);
+            return None;
+        }
+
+        let revision = read::<u8>(rsdp + 15);
+        let xsdt = read::<u64>(rsdp + 24);
+
+        let (root, entry_size) = if revision >= 2 && xsdt != 0 {
+            (xsdt, 8)
+        } else {
+            (read::<u32>(rsdp + 16) as u64, 4)
+        };
+
+        let length = read::<u32>(root + 4) as u64;
+
+        (root + SDT_HEADER_LEN as u64..root + length)
+            .step_by(entry_size)
+            .map(|entry| match entry_size {
+                8 => read::<u64>(entry),
+                _ => read::<u32>(entry) as u64,
+            })
+            .find(|&table| &read::<[u8; 4]>(table) == signature)
+    }
+}
@@ -0,0 +1,372 @@
+//! PCI Bus Enumeration
+//!
+//! Scans the configuration space once and keeps the functions found
+//! in a registry, which the drivers match against by class or id.
+//!
+//! reference: https://wiki.osdev.org/PCI
+
+mod config;
+mod mcfg;
+
+use alloc::vec::Vec;
+use config::ConfigAccess;
+use syscall_def::PciEntry;
+
+static ACCESS: spin::Once<ConfigAccess> = spin::Once::new();
+static DEVICES: spin::Once<Vec<PciDevice>> = spin::Once::new();
+
+/// Bus mastering bit of the command register
+const COMMAND_BUS_MASTER: u16 = 1 << 2;
+
+/// Set in the header type if the device has multiple functions
+const HEADER_MULTI_FUNCTION: u8 = 0x80;
+
+/// Set in the status register if the capability list is present
+const STATUS_CAPABILITIES: u32 = 1 << 20;
+
+/// Capability id of the message signaled interrupts
+const CAP_ID_MSI: u8 = 0x05;
+
+/// Bits of the message control, the upper half of the MSI header
+const MSI_ENABLE: u32 = 1 << 16;
+const MSI_MULTIPLE_ENABLE: u32 = 0x7 << 20;
+const MSI_64BIT: u32 = 1 << 23;
+
+/// The messages are written to the local APIC of the CPU 0
+const MSI_ADDRESS: u32 = 0xFEE0_0000;
+
+#[inline]
+fn access() -> &'static ConfigAccess {
+    ACCESS.call_once(|| match mcfg::find_ecam() {
+        Some(region) => {
+            info!(
+                "PCI ECAM at {:#x}, bus {}..={}.",
+                region.base, region.start_bus, region.end_bus
+            );
+            ConfigAccess::Ecam(region)
+        }
+        None => ConfigAccess::Port,
+    })
+}
+
+/// Reads the dword at `offset` of the configuration space of the function
+pub fn read_config(bus: u8, device: u8, function: u8, offset: u16) -> u32 {
+    access().read(bus, device, function, offset)
+}
+
+/// Writes the dword at `offset` of the configuration space of the function
+pub fn write_config(bus: u8, device: u8, function: u8, offset: u16, value: u32) {
+    access().write(bus, device, function, offset, value)
+}
+
+/// A decoded base address register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bar {
+    None,
+    Io(u16),
+    Memory { addr: u64, prefetchable: bool },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PciDevice {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub prog_if: u8,
+    pub revision: u8,
+    pub header_type: u8,
+    /// Only the general devices (header type 0) have 6 BARs
+    pub bars: [Bar; 6],
+    pub irq_line: u8,
+    pub irq_pin: u8,
+}
+
+impl PciDevice {
+    /// Reads the header of the function, `None` if it does not exist
+    pub fn probe(bus: u8, device: u8, function: u8) -> Option<Self> {
+        let id = read_config(bus, device, function, 0x00);
+        let vendor_id = id as u16;
+
+        if vendor_id == 0xFFFF {
+            return None;
+        }
+
+        let class = read_config(bus, device, function, 0x08);
+        let header = read_config(bus, device, function, 0x0C);
+        let interrupt = read_config(bus, device, function, 0x3C);
+
+        let mut dev = Self {
+            bus,
+            device,
+            function,
+            vendor_id,
+            device_id: (id >> 16) as u16,
+            class: (class >> 24) as u8,
+            subclass: (class >> 16) as u8,
+            prog_if: (class >> 8) as u8,
+            revision: class as u8,
+            header_type: (header >> 16) as u8,
+            bars: [Bar::None; 6],
+            irq_line: interrupt as u8,
+            irq_pin: (interrupt >> 8) as u8,
+        };
+
+        if dev.header_type & !HEADER_MULTI_FUNCTION == 0 {
+            dev.bars = dev.read_bars();
+        }
+
+        Some(dev)
+    }
+
+    fn read_bars(&self) -> [Bar; 6] {
+        decode_bars(core::array::from_fn(|idx| self.read(0x10 + idx as u16 * 4)))
+    }
+
+    #[inline]
+    pub fn read(&self, offset: u16) -> u32 {
+        read_config(self.bus, self.device, self.function, offset)
+    }
+
+    #[inline]
+    pub fn write(&self, offset: u16, value: u32) {
+        write_config(self.bus, self.device, self.function, offset, value)
+    }
+
+    /// Returns the decoded base address register
+    #[inline]
+    pub fn bar(&self, idx: usize) -> Bar {
+        self.bars[idx]
+    }
+
+    #[inline]
+    pub fn is_multi_function(&self) -> bool {
+        self.header_type & HEADER_MULTI_FUNCTION != 0
+    }
+
+    /// Allows the device to access the memory by itself
+    pub fn enable_bus_master(&self) {
+        // the upper half is the status register, writing ones clears it
+        let command = self.read(0x04) & 0xFFFF;
+        self.write(0x04, command | COMMAND_BUS_MASTER as u32);
+    }
+
+    /// Returns the offset of the first capability with the id
+    pub fn find_capability(&self, id: u8) -> Option<u16> {
+        if self.read(0x04) & STATUS_CAPABILITIES == 0 {
+            return None;
+        }
+
+        let mut ptr = (self.read(0x34) & 0xFC) as u16;
+
+        while ptr != 0 {
+            let header = self.read(ptr);
+
+            if header as u8 == id {
+                return Some(ptr);
+            }
+
+            ptr = ((header >> 8) & 0xFC) as u16;
+        }
+
+        None
+    }
+
+    /// Delivers the interrupts of the function to the vector by MSI,
+    /// edge triggered to the CPU 0. Returns false if it is not supported.
+    pub fn enable_msi(&self, vector: u8) -> bool {
+        let Some(ptr) = self.find_capability(CAP_ID_MSI) else {
+            return false;
+        };
+
+        let control = self.read(ptr);
+
+        self.write(ptr + 4, MSI_ADDRESS);
+
+        if control & MSI_64BIT != 0 {
+            self.write(ptr + 8, 0);
+            self.write(ptr + 12, vector as u32);
+        } else {
+            self.write(ptr + 8, vector as u32);
+        }
+
+        // a single message, the interrupt pin is no longer used
+        self.write(ptr, control & !MSI_MULTIPLE_ENABLE | MSI_ENABLE);
+
+        true
+    }
+
+    /// The record of the function returned to user space
+    pub fn entry(&self) -> PciEntry {
+        PciEntry {
+            vendor_id: self.vendor_id,
+            device_id: self.device_id,
+            bus: self.bus,
+            device: self.device,
+            function: self.function,
+            class: self.class,
+            subclass: self.subclass,
+            prog_if: self.prog_if,
+            revision: self.revision,
+            header_type: self.header_type,
+            irq_line: self.irq_line,
+            irq_pin: self.irq_pin,
+        }
+    }
+}
+
+impl core::fmt::Display for PciDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.entry())
+    }
+}
+
+/// Decodes the base address registers, a 64 bits BAR takes the next
+/// one as the upper half, which is left as `Bar::None`
+fn decode_bars(raw: [u32; 6]) -> [Bar; 6] {
+    let mut bars = [Bar::None; 6];
+    let mut idx = 0;
+
+    while idx < 6 {
+        let low = raw[idx];
+        let is_64 = low & 1 == 0 && (low >> 1) & 0x3 == 0x2;
+
+        let bar = if low & 1 == 1 {
+            Bar::Io((low & !0x3) as u16)
+        } else if is_64 {
+            // a broken BAR5 has no upper half to take
+            let high = raw.get(idx + 1).copied().unwrap_or(0) as u64;
+            Bar::Memory {
+                addr: high << 32 | (low & !0xF) as u64,
+                prefetchable: low & 0x8 != 0,
+            }
+        } else if low & !0xF != 0 {
+            Bar::Memory {
+                addr: (low & !0xF) as u64,
+                prefetchable: low & 0x8 != 0,
+            }
+        } else {
+            Bar::None
+        };
+
+        bars[idx] = bar;
+        idx += if is_64 { 2 } else { 1 };
+    }
+
+    bars
+}
+
+/// Scans all the buses, functions other than 0 are only probed
+/// for the multi-function devices
+fn scan() -> Vec<PciDevice> {
+    let mut devices = Vec::new();
+
+    for bus in 0..=255u8 {
+        for device in 0..32u8 {
+            let Some(dev) = PciDevice::probe(bus, device, 0) else {
+                continue;
+            };
+
+            let multi_function = dev.is_multi_function();
+            devices.push(dev);
+
+            if multi_function {
+                devices.extend(
+                    (1..8u8).filter_map(|function| PciDevice::probe(bus, device, function)),
+                );
+            }
+        }
+    }
+
+    devices
+}
+
+pub fn init() {
+    let devices = devices();
+
+    for dev in devices {
+        debug!("PCI {}", dev);
+    }
+
+    info!("Found {} PCI functions.", devices.len());
+}
+
+/// Returns the registry of the functions, scanned on the first call
+pub fn devices() -> &'static [PciDevice] {
+    DEVICES.call_once(scan)
+}
+
+/// Finds the first function matching the predicate
+pub fn find(predicate: impl Fn(&PciDevice) -> bool) -> Option<&'static PciDevice> {
+    devices().iter().find(|dev| predicate(dev))
+}
+
+/// Finds the first function of the class
+pub fn find_class(class: u8, subclass: u8) -> Option<&'static PciDevice> {
+    find(|dev| dev.class == class && dev.subclass == subclass)
+}
+
+/// Finds the first function with the vendor and device id
+pub fn find_id(vendor_id: u16, device_id: u16) -> Option<&'static PciDevice> {
+    find(|dev| dev.vendor_id == vendor_id && dev.device_id == device_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_bars() {
+        let bars = decode_bars([
+            // 32 bits memory
+            0xFEBF_1000,
+            // I/O port
+            0x0000_C041,
+            // 64 bits prefetchable memory and its upper half
+            0xE000_000C,
+            0x0000_0001,
+            // unused
+            0x0000_0000,
+            // 32 bits prefetchable memory
+            0xFD00_0008,
+        ]);
+
+        assert_eq!(
+            bars,
+            [
+                Bar::Memory {
+                    addr: 0xFEBF_1000,
+                    prefetchable: false
+                },
+                Bar::Io(0xC040),
+                Bar::Memory {
+                    addr: 0x1_E000_0000,
+                    prefetchable: true
+                },
+                Bar::None,
+                Bar::None,
+                Bar::Memory {
+                    addr: 0xFD00_0000,
+                    prefetchable: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_64_bits_bar5() {
+        let mut raw = [0; 6];
+        raw[5] = 0xFE00_0004;
+
+        assert_eq!(
+            decode_bars(raw)[5],
+            Bar::Memory {
+                addr: 0xFE00_0000,
+                prefetchable: false
+            }
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Programmable Interval Timer
+//!
+//! Only the channel 2 is used, as a one-shot countdown to calibrate the
+//! other timers. Its gate and output are controlled through port 0x61,
+//! so it is polled without any interrupt.
+//!
+//! reference: https://wiki.osdev.org/Programmable_Interval_Timer
+
+use x86_64::instructions::port::Port;
+
+const PIT_FREQUENCY: u64 = 1_193_182; // Hz
+
+const CHANNEL2: u16 = 0x42;
+const COMMAND: u16 = 0x43;
+/// bit 0: channel 2 gate, bit 1: speaker, bit 5: channel 2 output
+const CONTROL: u16 = 0x61;
+
+const GATE: u8 = 0x01;
+const SPEAKER: u8 = 0x02;
+const OUT2: u8 = 0x20;
+
+/// Channel 2, low then high byte, interrupt on terminal count
+const ONE_SHOT: u8 = 0xB0;
+
+/// Starts counting down `ms` milliseconds, at most 54 of them
+pub fn start(ms: u64) {
+    let reload = (PIT_FREQUENCY * ms / 1000).min(u16::MAX as u64) as u16;
+
+    let mut control = Port::<u8>::new(CONTROL);
+    let mut command = Port::<u8>::new(COMMAND);
+    let mut channel = Port::<u8>::new(CHANNEL2);
+
+    unsafe {
+        let value = control.read() & !(GATE | SPEAKER);
+        control.write(value);
+
+        command.write(ONE_SHOT);
+        channel.write(reload as u8);
+        channel.write((reload >> 8) as u8);
+
+        // the count starts on the rising edge of the gate
+        control.write(value | GATE);
+    }
+}
+
+/// Whether the countdown started by `start` has finished
+#[inline]
+pub fn finished() -> bool {
+    unsafe { Port::<u8>::new(CONTROL).read() & OUT2 != 0 }
+}
+
+/// Lowers the gate to stop the channel
+pub fn stop() {
+    let mut control = Port::<u8>::new(CONTROL);
+
+    unsafe {
+        let value = control.read() & !(GATE | SPEAKER);
+        control.write(value);
+    }
+}
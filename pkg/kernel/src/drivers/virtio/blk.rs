@@ -0,0 +1,224 @@
+//! Virtio Block Device
+//!
+//! reference: https://docs.oasis-open.org/virtio/virtio/v1.2/csd01/virtio-v1.2-csd01.html#x1-2740002
+
+use super::Transport;
+use super::queue::{Buffer, VirtQueue};
+use crate::memory::*;
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use spin::Mutex;
+use storage::{Block512, BlockDevice, DeviceError};
+use x86_64::structures::paging::{FrameAllocator, PhysFrame};
+
+/// The device is read-only
+const VIRTIO_BLK_F_RO: u64 = 1 << 5;
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+
+const VIRTIO_BLK_S_OK: u8 = 0;
+
+/// Frames of the transfer buffer, each one is described by a descriptor
+const BUFFER_FRAMES: usize = 16;
+
+const SECTORS_PER_FRAME: usize = FRAME_SIZE as usize / 512;
+
+/// The request header, the status byte follows it in the same frame
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct RequestHeader {
+    kind: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+const STATUS_OFFSET: u64 = core::mem::size_of::<RequestHeader>() as u64;
+
+struct BlkInner {
+    transport: Box<dyn Transport>,
+    queue: VirtQueue,
+    /// Holds the header and the status of the requests
+    header: PhysFrame,
+    buffer: Vec<PhysFrame>,
+}
+
+impl BlkInner {
+    /// The most sectors transferred by a single request
+    fn max_sectors(&self) -> usize {
+        self.buffer.len() * SECTORS_PER_FRAME
+    }
+
+    /// Returns the buffer of the sector in the transfer
+    fn sector(&mut self, idx: usize) -> &mut [u8] {
+        let frame = self.buffer[idx / SECTORS_PER_FRAME];
+        let offset = (idx % SECTORS_PER_FRAME) * 512;
+
+        unsafe {
+            let ptr = physical_to_virtual(frame.start_address().as_u64()) as *mut u8;
+            core::slice::from_raw_parts_mut(ptr.add(offset), 512)
+        }
+    }
+
+    /// Transfers `count` sectors between the device and the buffer
+    fn request(&mut self, kind: u32, sector: u64, count: usize) -> storage::Result<()> {
+        let header = self.header.start_address().as_u64();
+        let virt = physical_to_virtual(header);
+
+        unsafe {
+            (virt as *mut RequestHeader).write_volatile(RequestHeader {
+                kind,
+                reserved: 0,
+                sector,
+            });
+            ((virt + STATUS_OFFSET) as *mut u8).write_volatile(0xFF);
+        }
+
+        let read = kind == VIRTIO_BLK_T_IN;
+        let mut remain = count * 512;
+        let mut bufs: Vec<Buffer> = Vec::with_capacity(self.buffer.len() + 2);
+
+        bufs.push((header, STATUS_OFFSET as u32, false));
+
+        for frame in &self.buffer {
+            if remain == 0 {
+                break;
+            }
+
+            let len = remain.min(FRAME_SIZE as usize);
+            remain -= len;
+            bufs.push((frame.start_address().as_u64(), len as u32, read));
+        }
+
+        bufs.push((header + STATUS_OFFSET, 1, true));
+
+        self.queue.submit(self.transport.as_mut(), &bufs);
+
+        let status = unsafe { ((virt + STATUS_OFFSET) as *const u8).read_volatile() };
+
+        match status {
+            VIRTIO_BLK_S_OK => Ok(()),
+            _ if read => Err(DeviceError::ReadError.into()),
+            _ => Err(DeviceError::WriteError.into()),
+        }
+    }
+}
+
+/// Clones share the same device
+#[derive(Clone)]
+pub struct VirtioBlk {
+    index: usize,
+    /// The capacity in 512 bytes sectors
+    capacity: u64,
+    read_only: bool,
+    legacy: bool,
+    inner: Arc<Mutex<BlkInner>>,
+}
+
+impl VirtioBlk {
+    /// Initializes the device with a request queue,
+    /// `index` is the position of the device, used for its name
+    pub fn new(mut transport: Box<dyn Transport>, index: usize) -> Option<Self> {
+        let features = transport.begin_init(VIRTIO_BLK_F_RO)?;
+        let queue = VirtQueue::new(transport.as_mut(), 0)?;
+
+        // one descriptor each for the header and the status
+        let frames = BUFFER_FRAMES.min((queue.size() as usize).saturating_sub(2));
+
+        let mut alloc = get_frame_alloc_for_sure();
+        let header = alloc.allocate_frame()?;
+        let buffer: Vec<_> = (0..frames).map_while(|_| alloc.allocate_frame()).collect();
+        drop(alloc);
+
+        if buffer.is_empty() {
+            return None;
+        }
+
+        transport.finish_init();
+
+        let capacity = transport.read_config(0) as u64 | (transport.read_config(4) as u64) << 32;
+        let legacy = transport.is_legacy();
+
+        Some(Self {
+            index,
+            capacity,
+            read_only: features & VIRTIO_BLK_F_RO != 0,
+            legacy,
+            inner: Arc::new(Mutex::new(BlkInner {
+                transport,
+                queue,
+                header,
+                buffer,
+            })),
+        })
+    }
+
+    /// The name of the device by its position, `vda`, `vdb`, ...
+    pub fn name(&self) -> String {
+        format!("vd{}", (b'a' + self.index as u8) as char)
+    }
+}
+
+impl core::fmt::Display for VirtioBlk {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let (size, unit) = crate::humanized_size(self.capacity * 512);
+        write!(
+            f,
+            "{} {}{} ({} {})",
+            self.name(),
+            if self.legacy { "legacy" } else { "modern" },
+            if self.read_only { " read-only" } else { "" },
+            size,
+            unit
+        )
+    }
+}
+
+impl BlockDevice<Block512> for VirtioBlk {
+    fn block_count(&self) -> storage::Result<usize> {
+        Ok(self.capacity as usize)
+    }
+
+    fn read_block(&self, offset: usize, block: &mut Block512) -> storage::Result<()> {
+        self.read_blocks(offset, core::slice::from_mut(block))
+    }
+
+    fn write_block(&self, offset: usize, block: &Block512) -> storage::Result<()> {
+        self.write_blocks(offset, core::slice::from_ref(block))
+    }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [Block512]) -> storage::Result<()> {
+        let mut inner = self.inner.lock();
+        let max_sectors = inner.max_sectors();
+
+        for (idx, chunk) in blocks.chunks_mut(max_sectors).enumerate() {
+            let sector = (offset + idx * max_sectors) as u64;
+            inner.request(VIRTIO_BLK_T_IN, sector, chunk.len())?;
+
+            for (idx, buf) in chunk.iter_mut().enumerate() {
+                buf.as_mut().copy_from_slice(inner.sector(idx));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_blocks(&self, offset: usize, blocks: &[Block512]) -> storage::Result<()> {
+        if self.read_only {
+            return Err(DeviceError::InvalidOperation.into());
+        }
+
+        let mut inner = self.inner.lock();
+        let max_sectors = inner.max_sectors();
+
+        for (idx, chunk) in blocks.chunks(max_sectors).enumerate() {
+            for (idx, buf) in chunk.iter().enumerate() {
+                inner.sector(idx).copy_from_slice(buf.as_ref());
+            }
+
+            let sector = (offset + idx * max_sectors) as u64;
+            inner.request(VIRTIO_BLK_T_OUT, sector, chunk.len())?;
+        }
+
+        Ok(())
+    }
+}
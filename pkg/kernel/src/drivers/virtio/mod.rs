@@ -0,0 +1,135 @@
+//! Virtio Device Drivers
+//!
+//! Only the block device over the PCI transport is supported, both the
+//! modern (virtio 1.0) and the legacy interface of transitional devices.
+//!
+//! reference: https://docs.oasis-open.org/virtio/virtio/v1.2/virtio-v1.2.html
+//! reference: https://wiki.osdev.org/Virtio
+
+mod blk;
+mod pci;
+mod queue;
+
+pub use blk::VirtioBlk;
+
+use alloc::{boxed::Box, vec::Vec};
+use pci::{LegacyTransport, ModernTransport};
+
+const VIRTIO_VENDOR_ID: u16 = 0x1AF4;
+
+/// The transitional devices have ids from 0x1000 to 0x103F
+const LEGACY_BLK_DEVICE_ID: u16 = 0x1001;
+/// The modern devices have ids of 0x1040 plus the virtio device type
+const MODERN_BLK_DEVICE_ID: u16 = 0x1042;
+
+bitflags! {
+    /// The bits of the device status field
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub struct DeviceStatus: u8 {
+        const ACKNOWLEDGE = 1;
+        const DRIVER = 2;
+        const DRIVER_OK = 4;
+        const FEATURES_OK = 8;
+        const DEVICE_NEEDS_RESET = 64;
+        const FAILED = 128;
+    }
+}
+
+/// The device complies with the virtio 1.0 specification
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+/// The register interface of a device, implemented by the
+/// modern and the legacy PCI transports
+pub trait Transport: Send {
+    fn device_features(&mut self) -> u64;
+
+    fn set_driver_features(&mut self, features: u64);
+
+    fn status(&mut self) -> DeviceStatus;
+
+    fn set_status(&mut self, status: DeviceStatus);
+
+    /// Returns the size of the queue, 0 if the queue is not available
+    fn max_queue_size(&mut self, queue: u16) -> u16;
+
+    /// Sets the size and the physical addresses of the areas of the queue,
+    /// the legacy interface only takes the size chosen by the device and
+    /// requires the areas to be laid out contiguously from `desc`
+    fn setup_queue(&mut self, queue: u16, size: u16, desc: u64, driver: u64, device: u64);
+
+    fn notify(&mut self, queue: u16);
+
+    /// Reads the dword at `offset` of the device specific configuration
+    fn read_config(&mut self, offset: usize) -> u32;
+
+    /// Whether the layout of the queues is fixed by the legacy interface
+    fn is_legacy(&self) -> bool;
+
+    /// Resets the device and negotiates the features, returns the accepted ones
+    fn begin_init(&mut self, supported: u64) -> Option<u64> {
+        self.set_status(DeviceStatus::empty());
+        self.set_status(DeviceStatus::ACKNOWLEDGE);
+        self.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER);
+
+        let mut supported = supported;
+        if !self.is_legacy() {
+            supported |= VIRTIO_F_VERSION_1;
+        }
+
+        let features = self.device_features() & supported;
+        self.set_driver_features(features);
+
+        if !self.is_legacy() {
+            let status = self.status() | DeviceStatus::FEATURES_OK;
+            self.set_status(status);
+
+            if !self.status().contains(DeviceStatus::FEATURES_OK) {
+                self.set_status(DeviceStatus::FAILED);
+                return None;
+            }
+        }
+
+        Some(features)
+    }
+
+    /// Tells the device that the driver is ready, after the queues are set
+    fn finish_init(&mut self) {
+        let status = self.status() | DeviceStatus::DRIVER_OK;
+        self.set_status(status);
+    }
+}
+
+static BLOCK_DEVICES: spin::Once<Vec<VirtioBlk>> = spin::Once::new();
+
+/// Returns the virtio block devices on the PCI bus, probed on the first call
+pub fn block_devices() -> &'static [VirtioBlk] {
+    BLOCK_DEVICES.call_once(|| {
+        let mut devices = Vec::new();
+
+        let found = crate::pci::devices().iter().filter(|dev| {
+            dev.vendor_id == VIRTIO_VENDOR_ID
+                && matches!(dev.device_id, LEGACY_BLK_DEVICE_ID | MODERN_BLK_DEVICE_ID)
+        });
+
+        for dev in found {
+            // prefer the modern interface of the transitional devices
+            let transport: Box<dyn Transport> = match ModernTransport::new(dev) {
+                Some(modern) => Box::new(modern),
+                None => match LegacyTransport::new(dev) {
+                    Some(legacy) => Box::new(legacy),
+                    None => continue,
+                },
+            };
+
+            match VirtioBlk::new(transport, devices.len()) {
+                Some(blk) => {
+                    info!("Virtio block device {} opened", blk);
+                    devices.push(blk);
+                }
+                None => warn!("Failed to initialize virtio block device {}", dev),
+            }
+        }
+
+        devices
+    })
+}
@@ -0,0 +1,270 @@
+//! Virtio over PCI Bus
+//!
+//! reference: https://docs.oasis-open.org/virtio/virtio/v1.2/csd01/virtio-v1.2-csd01.html#x1-1150001
+//! reference: https://docs.oasis-open.org/virtio/virtio/v1.0/cs04/virtio-v1.0-cs04.html#x1-1120003
+
+use super::{DeviceStatus, Transport};
+use crate::memory::map_mmio;
+use crate::pci::{Bar, PciDevice};
+use alloc::collections::BTreeMap;
+use x86_64::instructions::port::Port;
+
+/// Set in the status register if the capabilities list is present
+const STATUS_CAPABILITIES: u32 = 1 << 20;
+const CAP_ID_VENDOR: u8 = 0x09;
+
+const CFG_TYPE_COMMON: u8 = 1;
+const CFG_TYPE_NOTIFY: u8 = 2;
+const CFG_TYPE_DEVICE: u8 = 4;
+
+/// Registers of the legacy interface in the I/O BAR0
+mod legacy {
+    pub const DEVICE_FEATURES: u16 = 0x00;
+    pub const GUEST_FEATURES: u16 = 0x04;
+    pub const QUEUE_PFN: u16 = 0x08;
+    pub const QUEUE_SIZE: u16 = 0x0C;
+    pub const QUEUE_SELECT: u16 = 0x0E;
+    pub const QUEUE_NOTIFY: u16 = 0x10;
+    pub const DEVICE_STATUS: u16 = 0x12;
+    /// Without MSI-X, the device configuration follows the ISR status
+    pub const DEVICE_CONFIG: u16 = 0x14;
+}
+
+/// Fields of the common configuration structure of the modern interface
+mod common {
+    pub const DEVICE_FEATURE_SELECT: usize = 0x00;
+    pub const DEVICE_FEATURE: usize = 0x04;
+    pub const DRIVER_FEATURE_SELECT: usize = 0x08;
+    pub const DRIVER_FEATURE: usize = 0x0C;
+    pub const DEVICE_STATUS: usize = 0x14;
+    pub const QUEUE_SELECT: usize = 0x16;
+    pub const QUEUE_SIZE: usize = 0x18;
+    pub const QUEUE_ENABLE: usize = 0x1C;
+    pub const QUEUE_NOTIFY_OFF: usize = 0x1E;
+    pub const QUEUE_DESC: usize = 0x20;
+    pub const QUEUE_DRIVER: usize = 0x28;
+    pub const QUEUE_DEVICE: usize = 0x30;
+}
+
+/// The legacy interface of the transitional devices, in the I/O space
+#[derive(Debug)]
+pub struct LegacyTransport {
+    base: u16,
+}
+
+impl LegacyTransport {
+    /// Returns `None` if the BAR0 of the device is not in the I/O space
+    pub fn new(dev: &PciDevice) -> Option<Self> {
+        let Bar::Io(base) = dev.bar(0) else {
+            return None;
+        };
+
+        dev.enable_bus_master();
+
+        Some(Self { base })
+    }
+
+    #[inline]
+    fn port<T>(&self, offset: u16) -> Port<T> {
+        Port::new(self.base + offset)
+    }
+}
+
+impl Transport for LegacyTransport {
+    fn device_features(&mut self) -> u64 {
+        unsafe { self.port::<u32>(legacy::DEVICE_FEATURES).read() as u64 }
+    }
+
+    fn set_driver_features(&mut self, features: u64) {
+        unsafe {
+            self.port::<u32>(legacy::GUEST_FEATURES)
+                .write(features as u32)
+        }
+    }
+
+    fn status(&mut self) -> DeviceStatus {
+        let status = unsafe { self.port::<u8>(legacy::DEVICE_STATUS).read() };
+        DeviceStatus::from_bits_retain(status)
+    }
+
+    fn set_status(&mut self, status: DeviceStatus) {
+        unsafe { self.port::<u8>(legacy::DEVICE_STATUS).write(status.bits()) }
+    }
+
+    fn max_queue_size(&mut self, queue: u16) -> u16 {
+        unsafe {
+            self.port::<u16>(legacy::QUEUE_SELECT).write(queue);
+            self.port::<u16>(legacy::QUEUE_SIZE).read()
+        }
+    }
+
+    fn setup_queue(&mut self, queue: u16, _size: u16, desc: u64, _driver: u64, _device: u64) {
+        unsafe {
+            self.port::<u16>(legacy::QUEUE_SELECT).write(queue);
+            self.port::<u32>(legacy::QUEUE_PFN)
+                .write((desc >> 12) as u32);
+        }
+    }
+
+    fn notify(&mut self, queue: u16) {
+        unsafe { self.port::<u16>(legacy::QUEUE_NOTIFY).write(queue) }
+    }
+
+    fn read_config(&mut self, offset: usize) -> u32 {
+        unsafe {
+            self.port::<u32>(legacy::DEVICE_CONFIG + offset as u16)
+                .read()
+        }
+    }
+
+    fn is_legacy(&self) -> bool {
+        true
+    }
+}
+
+/// The modern interface, the structures are located by the vendor
+/// specific capabilities and mapped in the memory BARs
+#[derive(Debug)]
+pub struct ModernTransport {
+    common: u64,
+    notify: u64,
+    notify_multiplier: u32,
+    device: u64,
+    /// Notification addresses of the queues set up
+    queue_notify: BTreeMap<u16, u64>,
+}
+
+impl ModernTransport {
+    /// Returns `None` if the device does not have the modern interface
+    pub fn new(dev: &PciDevice) -> Option<Self> {
+        if dev.read(0x04) & STATUS_CAPABILITIES == 0 {
+            return None;
+        }
+
+        let mut common = None;
+        let mut notify = None;
+        let mut device = None;
+
+        let mut ptr = (dev.read(0x34) & 0xFC) as u16;
+
+        while ptr != 0 {
+            let header = dev.read(ptr);
+            let next = ((header >> 8) & 0xFC) as u16;
+
+            if header as u8 == CAP_ID_VENDOR {
+                let cfg_type = (header >> 24) as u8;
+                let bar = dev.read(ptr + 4) as u8;
+                let offset = dev.read(ptr + 8) as u64;
+                let length = dev.read(ptr + 12) as u64;
+
+                // the first structure of each type is preferred
+                let region = || match dev.bars.get(bar as usize) {
+                    Some(Bar::Memory { addr, .. }) => map_mmio(addr + offset, length)
+                        .inspect_err(|err| warn!("Failed to map virtio BAR{}: {:?}", bar, err))
+                        .ok(),
+                    _ => None,
+                };
+
+                match cfg_type {
+                    CFG_TYPE_COMMON if common.is_none() => common = region(),
+                    CFG_TYPE_NOTIFY if notify.is_none() => {
+                        let multiplier = dev.read(ptr + 16);
+                        notify = region().map(|addr| (addr, multiplier));
+                    }
+                    CFG_TYPE_DEVICE if device.is_none() => device = region(),
+                    _ => {}
+                }
+            }
+
+            ptr = next;
+        }
+
+        let (notify, notify_multiplier) = notify?;
+
+        dev.enable_bus_master();
+
+        Some(Self {
+            common: common?,
+            notify,
+            notify_multiplier,
+            device: device?,
+            queue_notify: BTreeMap::new(),
+        })
+    }
+
+    #[inline]
+    fn read<T>(&self, offset: usize) -> T {
+        unsafe { ((self.common as usize + offset) as *const T).read_volatile() }
+    }
+
+    #[inline]
+    fn write<T>(&self, offset: usize, value: T) {
+        unsafe { ((self.common as usize + offset) as *mut T).write_volatile(value) }
+    }
+
+    /// The 64 bits fields are written as two dwords, low half first
+    #[inline]
+    fn write_u64(&self, offset: usize, value: u64) {
+        self.write::<u32>(offset, value as u32);
+        self.write::<u32>(offset + 4, (value >> 32) as u32);
+    }
+}
+
+impl Transport for ModernTransport {
+    fn device_features(&mut self) -> u64 {
+        self.write::<u32>(common::DEVICE_FEATURE_SELECT, 0);
+        let low = self.read::<u32>(common::DEVICE_FEATURE) as u64;
+        self.write::<u32>(common::DEVICE_FEATURE_SELECT, 1);
+        let high = self.read::<u32>(common::DEVICE_FEATURE) as u64;
+
+        high << 32 | low
+    }
+
+    fn set_driver_features(&mut self, features: u64) {
+        self.write::<u32>(common::DRIVER_FEATURE_SELECT, 0);
+        self.write::<u32>(common::DRIVER_FEATURE, features as u32);
+        self.write::<u32>(common::DRIVER_FEATURE_SELECT, 1);
+        self.write::<u32>(common::DRIVER_FEATURE, (features >> 32) as u32);
+    }
+
+    fn status(&mut self) -> DeviceStatus {
+        DeviceStatus::from_bits_retain(self.read::<u8>(common::DEVICE_STATUS))
+    }
+
+    fn set_status(&mut self, status: DeviceStatus) {
+        self.write::<u8>(common::DEVICE_STATUS, status.bits());
+    }
+
+    fn max_queue_size(&mut self, queue: u16) -> u16 {
+        self.write::<u16>(common::QUEUE_SELECT, queue);
+        self.read::<u16>(common::QUEUE_SIZE)
+    }
+
+    fn setup_queue(&mut self, queue: u16, size: u16, desc: u64, driver: u64, device: u64) {
+        self.write::<u16>(common::QUEUE_SELECT, queue);
+        self.write::<u16>(common::QUEUE_SIZE, size);
+        self.write_u64(common::QUEUE_DESC, desc);
+        self.write_u64(common::QUEUE_DRIVER, driver);
+        self.write_u64(common::QUEUE_DEVICE, device);
+
+        let offset = self.read::<u16>(common::QUEUE_NOTIFY_OFF) as u64;
+        let addr = self.notify + offset * self.notify_multiplier as u64;
+        self.queue_notify.insert(queue, addr);
+
+        self.write::<u16>(common::QUEUE_ENABLE, 1);
+    }
+
+    fn notify(&mut self, queue: u16) {
+        if let Some(&addr) = self.queue_notify.get(&queue) {
+            unsafe { (addr as *mut u16).write_volatile(queue) }
+        }
+    }
+
+    fn read_config(&mut self, offset: usize) -> u32 {
+        unsafe { ((self.device as usize + offset) as *const u32).read_volatile() }
+    }
+
+    fn is_legacy(&self) -> bool {
+        false
+    }
+}
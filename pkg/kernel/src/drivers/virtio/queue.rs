@@ -0,0 +1,161 @@
+//! Split Virtqueue
+//!
+//! The queue is laid out as required by the legacy interface, which the
+//! modern interface also accepts: the descriptor table and the available
+//! ring, then the used ring from the next page.
+//!
+//! reference: https://docs.oasis-open.org/virtio/virtio/v1.2/csd01/virtio-v1.2-csd01.html#x1-350007
+
+use super::Transport;
+use crate::memory::*;
+use crate::proc;
+use core::sync::atomic::{Ordering, fence};
+use x86_64::structures::paging::PhysFrame;
+
+/// The buffer continues via the next field
+const DESC_F_NEXT: u16 = 1;
+/// The buffer is written by the device
+const DESC_F_WRITE: u16 = 2;
+
+/// Tells the device not to interrupt, the used ring is polled instead
+const AVAIL_F_NO_INTERRUPT: u16 = 1;
+
+/// The largest queue used with the modern interface
+const MAX_QUEUE_SIZE: u16 = 256;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+/// A buffer of a request, `(physical address, length, written by device)`
+pub type Buffer = (u64, u32, bool);
+
+#[derive(Debug)]
+pub struct VirtQueue {
+    index: u16,
+    size: u16,
+    frames: PhysFrame,
+    /// Virtual addresses of the areas
+    desc: u64,
+    avail: u64,
+    used: u64,
+    /// The index of the used ring seen by the last request
+    last_used: u16,
+}
+
+impl VirtQueue {
+    /// Allocates the queue and sets it up on the device,
+    /// returns `None` if the queue is not available
+    pub fn new(transport: &mut dyn Transport, index: u16) -> Option<Self> {
+        let mut size = transport.max_queue_size(index);
+
+        if size == 0 {
+            return None;
+        }
+
+        // the legacy interface cannot change the queue size
+        if !transport.is_legacy() {
+            size = size.min(MAX_QUEUE_SIZE);
+        }
+
+        let (used_offset, total) = Self::layout(size);
+        let count = (total / FRAME_SIZE) as usize;
+        let frames = get_frame_alloc_for_sure().allocate_contiguous(count)?;
+
+        let phys = frames.start_address().as_u64();
+        let base = physical_to_virtual(phys);
+
+        unsafe { core::ptr::write_bytes(base as *mut u8, 0, total as usize) };
+
+        let avail_offset = 16 * size as u64;
+
+        transport.setup_queue(index, size, phys, phys + avail_offset, phys + used_offset);
+
+        let queue = Self {
+            index,
+            size,
+            frames,
+            desc: base,
+            avail: base + avail_offset,
+            used: base + used_offset,
+            last_used: 0,
+        };
+
+        unsafe { (queue.avail as *mut u16).write_volatile(AVAIL_F_NO_INTERRUPT) };
+
+        Some(queue)
+    }
+
+    /// Returns the offset of the used ring and the total size of the queue
+    fn layout(size: u16) -> (u64, u64) {
+        let size = size as u64;
+        let align = |len: u64| len.div_ceil(FRAME_SIZE) * FRAME_SIZE;
+
+        let used_offset = align(16 * size + 6 + 2 * size);
+        (used_offset, used_offset + align(6 + 8 * size))
+    }
+
+    #[inline]
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    #[inline]
+    fn used_idx(&self) -> u16 {
+        unsafe { ((self.used + 2) as *const u16).read_volatile() }
+    }
+
+    /// Submits the buffers as a chain of descriptors and blocks until
+    /// the device uses it. Only one request is in flight at a time, so
+    /// the chain always starts at the first descriptor.
+    pub fn submit(&mut self, transport: &mut dyn Transport, bufs: &[Buffer]) {
+        debug_assert!(!bufs.is_empty() && bufs.len() <= self.size as usize);
+
+        let desc = self.desc as *mut Descriptor;
+
+        for (idx, &(addr, len, write)) in bufs.iter().enumerate() {
+            let mut flags = if write { DESC_F_WRITE } else { 0 };
+
+            if idx + 1 < bufs.len() {
+                flags |= DESC_F_NEXT;
+            }
+
+            let entry = Descriptor {
+                addr,
+                len,
+                flags,
+                next: idx as u16 + 1,
+            };
+
+            unsafe { desc.add(idx).write_volatile(entry) };
+        }
+
+        let avail_idx = (self.avail + 2) as *mut u16;
+
+        unsafe {
+            let idx = avail_idx.read_volatile();
+            let ring = (self.avail + 4) as *mut u16;
+            ring.add((idx % self.size) as usize).write_volatile(0);
+
+            // the descriptors must be visible before the index
+            fence(Ordering::SeqCst);
+            avail_idx.write_volatile(idx.wrapping_add(1));
+            fence(Ordering::SeqCst);
+        }
+
+        transport.notify(self.index);
+
+        // the interrupts are suppressed, the used ring is polled
+        // while the other processes run
+        let expected = self.last_used.wrapping_add(1);
+        proc::block_until(|| self.used_idx() == expected, None);
+        fence(Ordering::SeqCst);
+
+        self.last_used = expected;
+    }
+}
@@ -0,0 +1,11 @@
+use super::consts::*;
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+
+pub unsafe fn reg_idt(idt: &mut InterruptDescriptorTable) {
+    idt[Interrupts::Ahci as u8].set_handler_fn(ahci_handler);
+}
+
+pub extern "x86-interrupt" fn ahci_handler(_st: InterruptStackFrame) {
+    crate::drivers::ahci::handle_irq();
+    super::ack();
+}
@@ -1,4 +1,5 @@
 use super::LocalApic;
+use crate::drivers::pit;
 use bit_field::BitField;
 use core::fmt::{Debug, Error, Formatter};
 use core::ptr::{read_volatile, write_volatile};
@@ -25,6 +26,30 @@ impl XApic {
     pub unsafe fn new(addr: u64) -> Self {
         XApic { addr }
     }
+
+    /// Returns the counts of the timer in `CALIBRATE_MS` measured by the PIT
+    unsafe fn calibrate(&mut self) -> u32 {
+        unsafe {
+            // one-shot and masked
+            self.write(TDCR, X1);
+            self.write(TIMER, MASKED);
+
+            pit::start(CALIBRATE_MS);
+            self.write(TICR, u32::MAX);
+
+            // the timer running out means the PIT is not there
+            while !pit::finished() && self.read(TCCR) != 0 {
+                core::hint::spin_loop();
+            }
+
+            let counts = u32::MAX - self.read(TCCR);
+
+            self.write(TICR, 0);
+            pit::stop();
+
+            counts
+        }
+    }
 }
 
 impl LocalApic for XApic {
@@ -39,11 +64,16 @@ impl LocalApic for XApic {
 
             // The timer repeatedly counts down at bus frequency
             // from lapic[TICR] and then issues an interrupt.
-            // If xv6 cared more about precise timekeeping,
-            // TICR would be calibrated using an external time source.
+            // The bus frequency is measured with the PIT to know
+            // the period of the timer.
+            let counts = self.calibrate().max(1) as u64;
+            let period = TIMER_COUNT as u64 * CALIBRATE_MS * 1_000_000 / counts;
+            crate::tasks::timer::set_tick_nanos(period);
+            info!("APIC timer period: {} ns", period);
+
             self.write(TDCR, X1);
             self.write(TIMER, PERIODIC | (T_IRQ0 + IRQ_TIMER));
-            self.write(TICR, 0x20000);
+            self.write(TICR, TIMER_COUNT);
 
             // Disable logical interrupt lines.
             self.write(LINT0, MASKED);
@@ -156,6 +186,10 @@ const TICR: u32 = 0x0380; // Timer Initial Count
 const TCCR: u32 = 0x0390; // Timer Current Count
 const TDCR: u32 = 0x03E0; // Timer Divide Configuration
 
+const TIMER_COUNT: u32 = 0x20000; // Initial count of the periodic timer
+
+const CALIBRATE_MS: u64 = 10; // Time measured by the PIT to calibrate
+
 const T_IRQ0: u32 = 32; // IRQ 0 corresponds to int T_IRQ
 const IRQ_TIMER: u32 = 0;
 const IRQ_KBD: u32 = 1;
@@ -11,7 +11,11 @@ pub unsafe fn reg_idt(idt: &mut InterruptDescriptorTable) {
 }
 
 pub extern "C" fn clock(mut context: ProcessContext) {
+    crate::tasks::timer::tick();
+    crate::proc::wake_up_sleepers();
+
     crate::proc::switch(&mut context);
+
     super::ack();
 }
 
@@ -27,6 +27,8 @@ pub enum Interrupts {
     SecurityException = 30,
 
     IrqBase = 0x20,
+    /// MSI of the AHCI controllers, above the IRQs of the I/O APIC
+    Ahci = 0x40,
     Syscall = 0x80,
 }
 
@@ -1,4 +1,6 @@
+mod ahci;
 mod apic;
+mod ata;
 mod clock;
 mod consts;
 mod exception;
@@ -21,6 +23,8 @@ lazy_static! {
             clock::reg_idt(&mut idt);
             syscall::reg_idt(&mut idt);
             keyboard::reg_idt(&mut idt);
+            ata::reg_idt(&mut idt);
+            ahci::reg_idt(&mut idt);
         }
         idt
     };
@@ -36,10 +40,14 @@ pub fn init() {
 
     serial::init();
     keyboard::init();
+    ata::init();
 
     info!("Interrupts Initialized.");
 }
 
+/// The vector the AHCI controllers send their MSI to
+pub const AHCI_VECTOR: u8 = consts::Interrupts::Ahci as u8;
+
 #[inline(always)]
 pub fn enable_irq(irq: u8, cpuid: u8) {
     let mut ioapic = unsafe { IoApic::new(physical_to_virtual(IOAPIC_ADDR)) };
@@ -48,12 +48,23 @@ pub fn dispatcher(context: &mut ProcessContext) {
         Syscall::Open => context.set_rax(sys_open(&args)),
         // fd: arg0 as u8 -> success: bool
         Syscall::Close => context.set_rax(sys_close(&args)),
+        // path: &str (arg0 as *const u8, arg1 as len), stat: arg2 as *mut FileStat -> result: isize
+        Syscall::StatPath => context.set_rax(sys_stat_path(&args)),
+        // fd: arg0 as u8, stat: arg1 as *mut FileStat -> result: isize
+        Syscall::FStat => context.set_rax(sys_fstat(&args)),
+        // fd: arg0 as u8, offset: arg1 as isize, whence: arg2 as u8 -> offset: isize
+        Syscall::Seek => context.set_rax(sys_seek(&args)),
         // addr: usize -> success: bool
         Syscall::Brk => context.set_rax(sys_brk(&args)),
+        // nanos: arg0 as u64 -> result: isize
+        Syscall::Sleep => sys_sleep(&args, context),
         // None -> pid: u16
         Syscall::GetPid => context.set_rax(sys_get_pid() as usize),
-        // None -> pid: u16 (diff from parent and child)
-        Syscall::VFork => sys_fork(context),
+        // None -> pid: u16 (diff from parent and child), -1 if failed
+        // VFork is kept for the old programs, it copies on write as well
+        Syscall::Fork | Syscall::VFork => sys_fork(context),
+        // path: &str (arg0 as *const u8, arg1 as len), args: arg2 as *const ExecArgs -> result: isize
+        Syscall::Exec => sys_exec(&args, context),
         // path: &str (arg0 as *const u8, arg1 as len) -> pid: u16
         Syscall::Spawn => context.set_rax(spawn_process(&args) as usize),
         // pid: arg0 as u16
@@ -62,16 +73,26 @@ pub fn dispatcher(context: &mut ProcessContext) {
         Syscall::WaitPid => sys_wait_pid(&args, context),
         // pid: arg0 as u16
         Syscall::Kill => sys_kill(&args, context),
+        // pid: arg0 as u16 (0 for self), nice: arg1 as isize -> result: isize
+        Syscall::SetPriority => context.set_rax(sys_set_priority(&args)),
+        // fd: arg0 as u8, entries: arg1 as *mut DirEntry, capacity: arg2 -> count: isize
+        Syscall::GetDents => context.set_rax(sys_get_dents(&args)),
         // op: u8, key: u32, val: usize -> ret: any
         Syscall::Sem => sys_sem(&args, context),
+        // None -> result: isize
+        Syscall::Sync => context.set_rax(sys_sync()),
         // None -> time: usize
         Syscall::Time => context.set_rax(sys_clock() as usize),
+        // clock: arg0 as u8 -> nanos: isize
+        Syscall::ClockGetTime => context.set_rax(sys_clock_get_time(&args)),
         // x: arg0 as i32, y: arg1 as i32, color: arg2 as u32
         Syscall::Draw => sys_draw(&args),
         // None
         Syscall::Stat => list_process(),
-        // path: &str (arg0 as *const u8, arg1 as len)
-        Syscall::ListDir => list_dir(&args),
+        // entries: arg0 as *mut MountEntry, capacity: arg1 -> count: isize
+        Syscall::ListMounts => context.set_rax(sys_list_mounts(&args)),
+        // entries: arg0 as *mut PciEntry, capacity: arg1 -> count: isize
+        Syscall::ListPci => context.set_rax(sys_list_pci(&args)),
         // layout: arg0 as *const Layout -> ptr: *mut u8
         Syscall::Allocate => context.set_rax(sys_allocate(&args)),
         // ptr: arg0 as *mut u8
@@ -1,19 +1,31 @@
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::alloc::Layout;
 
 use embedded_graphics::geometry::Point;
+use storage::{FileSystem, SeekFrom};
+use syscall_def::{
+    ClockId, DirEntry, ExecArgs, FileMode, FileStat, MountEntry, PciEntry, SeekWhence, StrRef,
+};
 
 use crate::display::get_display_for_sure;
 use crate::memory::*;
 use crate::proc::*;
+use crate::utils::resource::{file_stat, mount_entry};
 use crate::utils::*;
 
 use super::SyscallArgs;
 
 pub fn sys_clock() -> i64 {
-    clock::now()
-        .and_utc()
-        .timestamp_nanos_opt()
-        .unwrap_or_default()
+    clock::realtime_nanos()
+}
+
+pub fn sys_clock_get_time(args: &SyscallArgs) -> usize {
+    match ClockId::try_from(args.arg0 as u8) {
+        Ok(ClockId::Realtime) => clock::realtime_nanos() as usize,
+        Ok(ClockId::Monotonic) => clock::monotonic_nanos() as usize,
+        Err(_) => usize::MAX,
+    }
 }
 
 pub fn sys_draw(args: &SyscallArgs) {
@@ -125,13 +137,75 @@ pub fn sys_fork(context: &mut ProcessContext) {
     fork(context)
 }
 
+pub fn sys_exec(args: &SyscallArgs, context: &mut ProcessContext) {
+    // the context is replaced if the image is loaded
+    context.set_rax(usize::MAX);
+
+    let Some(path) = as_user_str(args.arg0, args.arg1) else {
+        return;
+    };
+
+    let Some(exec_args) = as_user_slice(args.arg2, core::mem::size_of::<ExecArgs>()) else {
+        return;
+    };
+
+    let exec_args = unsafe { (exec_args.as_ptr() as *const ExecArgs).read_unaligned() };
+
+    // copied before the memory of the process is freed
+    let (Some(argv), Some(envp)) = (
+        user_strs(exec_args.argv, exec_args.argc),
+        user_strs(exec_args.envp, exec_args.envc),
+    ) else {
+        return;
+    };
+
+    let size: usize = argv
+        .iter()
+        .chain(envp.iter())
+        .map(|s| s.len() + core::mem::size_of::<StrRef>())
+        .sum();
+
+    if size + core::mem::size_of::<ExecArgs>() > stack::EXEC_ARGS_MAX {
+        warn!("sys_exec: arguments too long");
+        return;
+    }
+
+    if !exec(path, &argv, &envp, context) {
+        warn!("sys_exec: failed to exec: {}", path);
+    }
+}
+
+/// Copies the strings passed by `Exec`
+fn user_strs(ptr: *const StrRef, len: usize) -> Option<Vec<String>> {
+    if len == 0 {
+        return Some(Vec::new());
+    }
+
+    let refs = as_user_slice(
+        ptr as usize,
+        len.saturating_mul(core::mem::size_of::<StrRef>()),
+    )?;
+
+    refs.chunks_exact(core::mem::size_of::<StrRef>())
+        .map(|r| {
+            let r = unsafe { (r.as_ptr() as *const StrRef).read_unaligned() };
+            as_user_str(r.ptr as usize, r.len).map(String::from)
+        })
+        .collect()
+}
+
 pub fn sys_open(args: &SyscallArgs) -> usize {
     let path = match as_user_str(args.arg0, args.arg1) {
         Some(path) => path,
         None => return 0,
     };
 
-    match open(path) {
+    let Ok(mode) = FileMode::try_from(args.arg2 as u8) else {
+        warn!("sys_open: invalid mode: {}", args.arg2);
+        return 0;
+    };
+
+    match open(path, mode) {
         Some(fd) => fd as usize,
         None => {
             warn!("sys_open: failed to open: {}", path);
@@ -140,6 +214,52 @@ pub fn sys_open(args: &SyscallArgs) -> usize {
     }
 }
 
+pub fn sys_seek(args: &SyscallArgs) -> usize {
+    let offset = args.arg1 as isize;
+
+    let pos = match SeekWhence::try_from(args.arg2 as u8) {
+        Ok(SeekWhence::Start) if offset >= 0 => SeekFrom::Start(offset as usize),
+        Ok(SeekWhence::Current) => SeekFrom::Current(offset),
+        Ok(SeekWhence::End) => SeekFrom::End(offset),
+        _ => return usize::MAX,
+    };
+
+    seek(args.arg0 as u8, pos) as usize
+}
+
+pub fn sys_fstat(args: &SyscallArgs) -> usize {
+    copy_file_stat(args.arg1, fstat(args.arg0 as u8))
+}
+
+pub fn sys_stat_path(args: &SyscallArgs) -> usize {
+    let path = match as_user_str(args.arg0, args.arg1) {
+        Some(path) => path,
+        None => return usize::MAX,
+    };
+
+    let stat = crate::filesystem::get_rootfs()
+        .metadata(path)
+        .ok()
+        .map(|meta| file_stat(&meta));
+
+    copy_file_stat(args.arg2, stat)
+}
+
+/// Copies the metadata to the user buffer, returns 0 on success
+fn copy_file_stat(ptr: usize, stat: Option<FileStat>) -> usize {
+    let Some(stat) = stat else {
+        return usize::MAX;
+    };
+
+    let Some(buf) = as_user_slice_mut(ptr, core::mem::size_of::<FileStat>()) else {
+        return usize::MAX;
+    };
+
+    unsafe { (buf.as_mut_ptr() as *mut FileStat).write_unaligned(stat) };
+
+    0
+}
+
 pub fn sys_brk(args: &SyscallArgs) -> usize {
     let new_heap_end = if args.arg0 == 0 {
         None
@@ -161,18 +281,80 @@ pub fn list_process() {
     print_process_list();
 }
 
-pub fn list_dir(args: &SyscallArgs) {
-    if args.arg1 > 0x100 {
-        warn!("sys_list_dir: path too long");
-        return;
+pub fn sys_get_dents(args: &SyscallArgs) -> usize {
+    let size = args.arg2.saturating_mul(core::mem::size_of::<DirEntry>());
+    let Some(entries) = as_user_slice_mut(args.arg1, size) else {
+        return usize::MAX;
+    };
+
+    if !entries.as_ptr().cast::<DirEntry>().is_aligned() {
+        return usize::MAX;
     }
 
-    let path = match as_user_str(args.arg0, args.arg1) {
-        Some(path) => path,
-        None => return,
+    let entries = unsafe {
+        core::slice::from_raw_parts_mut(entries.as_mut_ptr() as *mut DirEntry, args.arg2)
+    };
+
+    get_dents(args.arg0 as u8, entries).unwrap_or(usize::MAX)
+}
+
+pub fn sys_sync() -> usize {
+    match crate::filesystem::sync() {
+        Ok(()) => 0,
+        Err(_) => usize::MAX,
+    }
+}
+
+pub fn sys_list_mounts(args: &SyscallArgs) -> usize {
+    let size = args.arg1.saturating_mul(core::mem::size_of::<MountEntry>());
+    let Some(entries) = as_user_slice_mut(args.arg0, size) else {
+        return usize::MAX;
+    };
+
+    if !entries.as_ptr().cast::<MountEntry>().is_aligned() {
+        return usize::MAX;
+    }
+
+    let entries = unsafe {
+        core::slice::from_raw_parts_mut(entries.as_mut_ptr() as *mut MountEntry, args.arg1)
     };
 
-    crate::filesystem::ls(path);
+    let mounts = crate::filesystem::list_mounts();
+
+    for (entry, mount) in entries.iter_mut().zip(mounts.iter()) {
+        *entry = mount_entry(mount);
+    }
+
+    // the count of every mount, more than filled if the buffer is too small
+    mounts.len()
+}
+
+pub fn sys_list_pci(args: &SyscallArgs) -> usize {
+    let size = args.arg1.saturating_mul(core::mem::size_of::<PciEntry>());
+    let Some(entries) = as_user_slice_mut(args.arg0, size) else {
+        return usize::MAX;
+    };
+
+    if !entries.as_ptr().cast::<PciEntry>().is_aligned() {
+        return usize::MAX;
+    }
+
+    let entries = unsafe {
+        core::slice::from_raw_parts_mut(entries.as_mut_ptr() as *mut PciEntry, args.arg1)
+    };
+
+    let devices = crate::pci::devices();
+
+    for (entry, dev) in entries.iter_mut().zip(devices.iter()) {
+        *entry = dev.entry();
+    }
+
+    // the count of every function, more than filled if the buffer is too small
+    devices.len()
+}
+
+pub fn sys_sleep(args: &SyscallArgs, context: &mut ProcessContext) {
+    sleep(args.arg0 as u64, context);
 }
 
 pub fn sys_wait_pid(args: &SyscallArgs, context: &mut ProcessContext) {
@@ -189,6 +371,19 @@ pub fn sys_kill(args: &SyscallArgs, context: &mut ProcessContext) {
     kill(ProcessId(args.arg0 as u16), context);
 }
 
+pub fn sys_set_priority(args: &SyscallArgs) -> usize {
+    let pid = match args.arg0 as u16 {
+        0 => current_pid(),
+        pid => ProcessId(pid),
+    };
+
+    if set_nice(pid, args.arg1 as isize) {
+        0
+    } else {
+        usize::MAX
+    }
+}
+
 pub fn sys_sem(args: &SyscallArgs, context: &mut ProcessContext) {
     match args.arg0 {
         0 => context.set_rax(new_sem(args.arg1 as u32, args.arg2)),
@@ -52,11 +52,14 @@ pub fn init(boot_info: &'static BootInfo) {
     display::init(boot_info); // init vga display
     console::init(); // init graphic console
     interrupt::init(); // init interrupts
+    clock::init(); // init clocks
     memory::init(boot_info); // init memory manager
     memory::user::init(); // init user heap allocator
     proc::init(boot_info); // init process manager
     keyboard::init(); // init keyboard
-    filesystem::init(); // init filesystem
+    pci::init(); // init pci devices
+    filesystem::init(boot_info); // init filesystem
+    devfs::init(); // init device filesystem
 
     x86_64::instructions::interrupts::enable();
     info!("Interrupts Enabled.");
@@ -75,5 +78,10 @@ pub fn init(boot_info: &'static BootInfo) {
 
 pub fn shutdown() -> ! {
     info!("GGOS shutting down.");
+
+    if filesystem::sync().is_err() {
+        error!("Failed to write back the filesystem caches.");
+    }
+
     uefi::runtime::reset(ResetType::SHUTDOWN, Status::SUCCESS, None);
 }
@@ -18,6 +18,7 @@ pub fn kernel_main(boot_info: &'static boot::BootInfo) -> ! {
     let init = spawn_init(boot_info);
 
     // use executor.spawn() to spawn kernel tasks
+    executor.spawn(filesystem::flusher());
     executor.run(init);
     ggos::shutdown();
 }
@@ -1,7 +1,9 @@
-use x86_64::VirtAddr;
 use x86_64::structures::paging::mapper::TranslateResult::*;
+use x86_64::structures::paging::mapper::{FlagUpdateError, MapToError};
 use x86_64::structures::paging::*;
+use x86_64::{PhysAddr, VirtAddr};
 
+use super::get_frame_alloc_for_sure;
 use crate::proc::PageTableContext;
 
 pub const PAGE_SIZE: u64 = 4096;
@@ -44,9 +46,31 @@ pub fn as_user_str(ptr: usize, len: usize) -> Option<&'static str> {
     }
 }
 
+/// The end of the lower half of the address space, where user memory is
+const USER_SPACE_END: usize = 0x0000_8000_0000_0000;
+
+/// Checks that every page of `len` bytes at `ptr` is user accessible
+pub fn is_user_range_accessible(ptr: usize, len: usize) -> bool {
+    let Some(end) = ptr.checked_add(len) else {
+        return false;
+    };
+
+    if end > USER_SPACE_END {
+        return false;
+    }
+
+    let page_size = PAGE_SIZE as usize;
+    let start = ptr & !(page_size - 1);
+
+    // the first page is checked for an empty slice as well
+    (start..end.max(ptr + 1))
+        .step_by(page_size)
+        .all(is_user_accessible)
+}
+
 pub fn as_user_slice<'a>(ptr: usize, len: usize) -> Option<&'a [u8]> {
-    if !is_user_accessible(ptr) {
-        warn!("syscall: invalid access to {:#x}", ptr);
+    if !is_user_range_accessible(ptr, len) {
+        warn!("syscall: invalid access to {:#x}, len: {:#x}", ptr, len);
         return None;
     }
 
@@ -54,10 +78,65 @@ pub fn as_user_slice<'a>(ptr: usize, len: usize) -> Option<&'a [u8]> {
 }
 
 pub fn as_user_slice_mut<'a>(ptr: usize, len: usize) -> Option<&'a mut [u8]> {
-    if !is_user_accessible(ptr) {
-        warn!("syscall: invalid access to {:#x}", ptr);
+    if !is_user_range_accessible(ptr, len) {
+        warn!("syscall: invalid access to {:#x}, len: {:#x}", ptr, len);
         return None;
     }
 
     unsafe { Some(core::slice::from_raw_parts_mut(ptr as *mut u8, len)) }
 }
+
+/// The page table error of `map_mmio`
+#[derive(Debug)]
+pub enum MmioError {
+    Map(MapToError<Size4KiB>),
+    Update(FlagUpdateError),
+}
+
+/// Maps the MMIO region at the physical memory offset uncached and returns
+/// its virtual address, for the regions beyond the mapped physical memory
+/// such as 64-bit BARs. The pages already mapped, by the huge pages of the
+/// physical memory as well, are made uncached in place.
+pub fn map_mmio(addr: u64, size: u64) -> Result<u64, MmioError> {
+    let mut mapper = PageTableContext::new().mapper();
+    let mut alloc = get_frame_alloc_for_sure();
+
+    let flags = PageTableFlags::PRESENT
+        | PageTableFlags::WRITABLE
+        | PageTableFlags::NO_CACHE
+        | PageTableFlags::WRITE_THROUGH;
+    let start = PhysFrame::<Size4KiB>::containing_address(PhysAddr::new(addr));
+    let end = PhysFrame::containing_address(PhysAddr::new(addr + size.max(1) - 1));
+
+    for frame in PhysFrame::range_inclusive(start, end) {
+        let virt = VirtAddr::new(physical_to_virtual(frame.start_address().as_u64()));
+        let page = Page::<Size4KiB>::containing_address(virt);
+
+        match unsafe { mapper.map_to(page, frame, flags, &mut *alloc) } {
+            Ok(flush) => flush.flush(),
+            Err(MapToError::PageAlreadyMapped(_)) => unsafe {
+                mapper
+                    .update_flags(page, flags)
+                    .map_err(MmioError::Update)?
+                    .flush();
+            },
+            Err(MapToError::ParentEntryHugePage) => {
+                let page = Page::<Size2MiB>::containing_address(virt);
+
+                match unsafe { mapper.update_flags(page, flags) } {
+                    Ok(flush) => flush.flush(),
+                    Err(FlagUpdateError::ParentEntryHugePage) => unsafe {
+                        mapper
+                            .update_flags(Page::<Size1GiB>::containing_address(virt), flags)
+                            .map_err(MmioError::Update)?
+                            .flush();
+                    },
+                    Err(err) => return Err(MmioError::Update(err)),
+                }
+            }
+            Err(err) => return Err(MmioError::Map(err)),
+        }
+    }
+
+    Ok(physical_to_virtual(addr))
+}
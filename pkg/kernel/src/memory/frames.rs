@@ -2,9 +2,11 @@
 // reference: https://github.com/xfoxfu/rust-xos/blob/main/kernel/src/memory.rs
 
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use boot::{MemoryMap, MemoryType};
 use roaring::RoaringBitmap;
 use x86_64::PhysAddr;
+use x86_64::structures::paging::frame::PhysFrameRange;
 use x86_64::structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame, Size4KiB};
 
 once_mutex!(pub FRAME_ALLOCATOR: BootInfoFrameAllocator);
@@ -21,6 +23,8 @@ pub struct BootInfoFrameAllocator {
     frames: BootInfoFrameIter,
     used: usize,
     recycled: RoaringBitmap,
+    /// Reference counts of the frames mapped by more than one page table
+    shared: BTreeMap<u32, usize>,
 }
 
 impl BootInfoFrameAllocator {
@@ -36,6 +40,7 @@ impl BootInfoFrameAllocator {
                 frames: create_frame_iter(memory_map),
                 used,
                 recycled: RoaringBitmap::new(),
+                shared: BTreeMap::new(),
             }
         }
     }
@@ -51,6 +56,53 @@ impl BootInfoFrameAllocator {
     pub fn frames_recycled(&self) -> usize {
         self.recycled.len() as usize
     }
+
+    /// Adds a reference to the frame, which is mapped by one more page
+    /// table. It is only recycled after all the references are dropped.
+    pub fn share_frame(&mut self, frame: PhysFrame) {
+        *self.shared.entry(phys_frame_to_u32(frame)).or_insert(1) += 1;
+    }
+
+    /// Returns the number of page tables mapping the frame
+    pub fn frame_refs(&self, frame: PhysFrame) -> usize {
+        self.shared
+            .get(&phys_frame_to_u32(frame))
+            .copied()
+            .unwrap_or(1)
+    }
+
+    /// Allocates physically contiguous frames for the devices, only from
+    /// the frames never used, the skipped ones are recycled.
+    pub fn allocate_contiguous(&mut self, count: usize) -> Option<PhysFrame> {
+        let mut start = self.frames.next()?;
+        let mut len = 1u64;
+        self.used += 1;
+
+        while len < count as u64 {
+            let Some(frame) = self.frames.next() else {
+                // out of the frames never used, gives back the ones taken
+                self.recycle_range(PhysFrame::range(start, start + len));
+                return None;
+            };
+            self.used += 1;
+
+            if frame == start + len {
+                len += 1;
+            } else {
+                self.recycle_range(PhysFrame::range(start, start + len));
+                start = frame;
+                len = 1;
+            }
+        }
+
+        Some(start)
+    }
+
+    fn recycle_range(&mut self, range: PhysFrameRange) {
+        for frame in range {
+            self.recycled.insert(phys_frame_to_u32(frame));
+        }
+    }
 }
 
 unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
@@ -66,8 +118,18 @@ unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
 }
 
 impl FrameDeallocator<Size4KiB> for BootInfoFrameAllocator {
+    /// Drops a reference to the frame, recycles it with the last one
     unsafe fn deallocate_frame(&mut self, frame: PhysFrame) {
         let key = phys_frame_to_u32(frame);
+
+        if let Some(refs) = self.shared.get_mut(&key) {
+            *refs -= 1;
+            if *refs == 1 {
+                self.shared.remove(&key);
+            }
+            return;
+        }
+
         self.recycled.insert(key);
     }
 }
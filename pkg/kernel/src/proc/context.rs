@@ -37,8 +37,8 @@ impl ProcessContext {
     }
 
     #[inline]
-    pub fn set_stack_offset(&mut self, offset: u64) {
-        self.value.stack_frame.stack_pointer += offset;
+    pub fn set_rdi(&mut self, value: usize) {
+        self.value.regs.rdi = value;
     }
 
     #[inline]
@@ -43,6 +43,18 @@ impl ProcessData {
         self.resources.read().write(fd, buf)
     }
 
+    pub fn seek(&self, fd: u8, pos: SeekFrom) -> isize {
+        self.resources.read().seek(fd, pos)
+    }
+
+    pub fn stat(&self, fd: u8) -> Option<FileStat> {
+        self.resources.read().stat(fd)
+    }
+
+    pub fn get_dents(&self, fd: u8, entries: &mut [DirEntry]) -> Option<usize> {
+        self.resources.read().get_dents(fd, entries)
+    }
+
     pub fn env(&self, key: &str) -> Option<String> {
         self.env.read().get(key).cloned()
     }
@@ -1,5 +1,4 @@
 use alloc::collections::BTreeSet;
-use storage::random::Random;
 
 use super::*;
 use crate::{
@@ -12,14 +11,14 @@ use crate::{
     },
     utils::humanized_size,
 };
-use alloc::{collections::BTreeMap, collections::VecDeque, format, sync::Weak};
+use alloc::{boxed::Box, collections::BTreeMap, format, sync::Weak};
 use spin::{Mutex, RwLock};
 
 pub static PROCESS_MANAGER: spin::Once<ProcessManager> = spin::Once::new();
 
 pub fn init(init: Arc<Process>) {
     processor::set_pid(init.pid());
-    PROCESS_MANAGER.call_once(|| ProcessManager::new(init));
+    PROCESS_MANAGER.call_once(|| ProcessManager::new(init, Box::new(Mlfq::new())));
 }
 
 pub fn get_process_manager() -> &'static ProcessManager {
@@ -30,25 +29,77 @@ pub fn get_process_manager() -> &'static ProcessManager {
 
 pub struct ProcessManager {
     processes: RwLock<BTreeMap<ProcessId, Arc<Process>>>,
-    ready_queue: Mutex<VecDeque<ProcessId>>,
+    scheduler: Mutex<Box<dyn Scheduler>>,
     wait_queue: Mutex<BTreeMap<ProcessId, BTreeSet<ProcessId>>>,
+    /// Sleeping processes ordered by the tick to wake them up at
+    sleep_queue: Mutex<BTreeSet<(u64, ProcessId)>>,
 }
 
 impl ProcessManager {
-    pub fn new(init: Arc<Process>) -> Self {
+    pub fn new(init: Arc<Process>, mut scheduler: Box<dyn Scheduler>) -> Self {
         let mut processes = BTreeMap::new();
         let pid = init.pid();
+        scheduler.admit(init.write().sched_mut());
         processes.insert(pid, init);
         Self {
             processes: RwLock::new(processes),
-            ready_queue: Mutex::new(VecDeque::new()),
+            scheduler: Mutex::new(scheduler),
             wait_queue: Mutex::new(BTreeMap::new()),
+            sleep_queue: Mutex::new(BTreeSet::new()),
         }
     }
 
-    #[inline]
     pub fn push_ready(&self, pid: ProcessId) {
-        self.ready_queue.lock().push_back(pid);
+        if let Some(proc) = self.get_proc(&pid) {
+            let mut inner = proc.write();
+            self.scheduler.lock().push(pid, inner.sched_mut());
+        }
+    }
+
+    #[inline]
+    fn pop_ready(&self) -> Option<ProcessId> {
+        self.scheduler.lock().pop()
+    }
+
+    /// Accounts the timer tick to the current process, returns true if
+    /// the scheduler wants to switch it out. A process blocked in the
+    /// kernel stays on the CPU until it is woken up.
+    pub fn tick_current(&self) -> bool {
+        let current = self.current();
+        let mut current = current.write();
+        current.tick();
+
+        current.status() == ProgramStatus::Running
+            && self.scheduler.lock().tick(current.sched_mut())
+    }
+
+    /// Only the current process and its children can be changed
+    pub fn set_nice(&self, pid: ProcessId, nice: i8) -> bool {
+        let current = self.current();
+
+        if pid != current.pid() && !current.read().children().iter().any(|c| c.pid() == pid) {
+            warn!(
+                "Process #{} cannot set the nice of process #{}",
+                current.pid(),
+                pid
+            );
+            return false;
+        }
+
+        let Some(proc) = self.get_proc(&pid) else {
+            return false;
+        };
+
+        let mut inner = proc.write();
+
+        if inner.status() == ProgramStatus::Dead {
+            return false;
+        }
+
+        debug!("Set nice of process #{} to {}", pid, nice);
+        self.scheduler.lock().set_nice(inner.sched_mut(), nice);
+
+        true
     }
 
     #[inline]
@@ -73,6 +124,34 @@ impl ProcessManager {
         entry.insert(processor::current_pid());
     }
 
+    pub fn sleep(&self, deadline: u64) {
+        self.sleep_queue
+            .lock()
+            .insert((deadline, processor::current_pid()));
+    }
+
+    /// Wakes up the processes whose deadline has passed,
+    /// the processes killed while sleeping are dropped
+    pub fn wake_up_sleepers(&self, ticks: u64) {
+        loop {
+            let mut queue = self.sleep_queue.lock();
+
+            let Some(&(deadline, pid)) = queue.first() else {
+                break;
+            };
+
+            if deadline > ticks {
+                break;
+            }
+
+            queue.pop_first();
+            drop(queue);
+
+            // the return value of the sleep syscall is set before
+            self.wake_up(pid, None);
+        }
+    }
+
     pub(super) fn get_exit_code(&self, pid: ProcessId) -> Option<isize> {
         self.get_proc(&pid).and_then(|p| p.read().exit_code())
     }
@@ -81,9 +160,7 @@ impl ProcessManager {
         let current = self.current();
         let pid = current.pid();
 
-        let mut current = current.write();
-        current.tick();
-        current.save(context);
+        current.write().save(context);
 
         // debug!("Save process {} #{}", current.name(), pid);
 
@@ -93,7 +170,7 @@ impl ProcessManager {
     pub fn switch_next(&self, context: &mut ProcessContext) -> ProcessId {
         let mut pid = processor::current_pid();
 
-        while let Some(next) = self.ready_queue.lock().pop_front() {
+        while let Some(next) = self.pop_ready() {
             let map = self.processes.read();
             let proc = map.get(&next).expect("Process not found");
 
@@ -114,39 +191,62 @@ impl ProcessManager {
         pid
     }
 
-    pub fn open(&self, path: &str) -> Option<u8> {
-        let res = match path {
-            // as a special case, we can open "/dev/random" to get random numbers
-            "/dev/random" => Resource::Random(Random::new()),
-            _ => match get_rootfs().open_file(path) {
-                Ok(file) => Resource::File(file),
-                Err(_) => return None,
-            },
-        };
+    pub fn open(&self, path: &str, mode: FileMode) -> Option<u8> {
+        trace!("Opening {} as {:?}...", path, mode);
 
-        trace!("Opening {}...", path);
+        let res = match open_resource(path, mode) {
+            Ok(res) => res,
+            Err(err) => {
+                debug!("Failed to open {}: {:?}", path, err);
+                return None;
+            }
+        };
 
-        let fd = self.current().write().open(res);
+        let mut data = self.current().read().proc_data();
 
-        Some(fd)
+        Some(data.open(res))
     }
 
     pub fn close(&self, fd: u8) -> bool {
         if fd < 3 {
             false // stdin, stdout, stderr are reserved
         } else {
-            self.current().write().close(fd)
+            let mut data = self.current().read().proc_data();
+            data.close(fd)
         }
     }
 
+    // the resources are used without holding the current process,
+    // which is blocked in the middle of the disk operations
+
     #[inline]
     pub fn read(&self, fd: u8, buf: &mut [u8]) -> isize {
-        self.current().read().read(fd, buf)
+        let data = self.current().read().proc_data();
+        data.read(fd, buf)
     }
 
     #[inline]
     pub fn write(&self, fd: u8, buf: &[u8]) -> isize {
-        self.current().read().write(fd, buf)
+        let data = self.current().read().proc_data();
+        data.write(fd, buf)
+    }
+
+    #[inline]
+    pub fn seek(&self, fd: u8, pos: SeekFrom) -> isize {
+        let data = self.current().read().proc_data();
+        data.seek(fd, pos)
+    }
+
+    #[inline]
+    pub fn fstat(&self, fd: u8) -> Option<FileStat> {
+        let data = self.current().read().proc_data();
+        data.stat(fd)
+    }
+
+    #[inline]
+    pub fn get_dents(&self, fd: u8, entries: &mut [DirEntry]) -> Option<usize> {
+        let data = self.current().read().proc_data();
+        data.get_dents(fd, entries)
     }
 
     pub fn spawn(
@@ -155,7 +255,7 @@ impl ProcessManager {
         name: String,
         parent: Option<Weak<Process>>,
         proc_data: Option<ProcessData>,
-    ) -> ProcessId {
+    ) -> Option<ProcessId> {
         let kproc = self.get_proc(&KERNEL_PID).unwrap();
         let page_table = kproc.read().clone_page_table();
         let proc_vm = Some(ProcessVm::new(page_table));
@@ -163,7 +263,8 @@ impl ProcessManager {
 
         let mut inner = proc.write();
         inner.pause();
-        inner.load_elf(elf);
+        self.scheduler.lock().admit(inner.sched_mut());
+        inner.load_elf(elf).ok()?;
         inner.init_stack_frame(
             VirtAddr::new_truncate(elf.header.pt2.entry_point()),
             VirtAddr::new_truncate(super::stack::STACK_INIT_TOP),
@@ -176,28 +277,88 @@ impl ProcessManager {
         self.add_proc(pid, proc);
         self.push_ready(pid);
 
-        pid
+        Some(pid)
     }
 
-    pub fn fork(&self) {
-        let proc = self.current().fork();
+    /// Forks the current process, returns the pid of the child,
+    /// or None if its memory cannot be allocated
+    pub fn fork(&self) -> Option<ProcessId> {
+        let kproc = self.get_proc(&KERNEL_PID).unwrap();
+        let page_table = kproc.read().clone_page_table();
+        let proc = self
+            .current()
+            .fork(page_table)
+            .inspect_err(|err| warn!("Cannot fork process: {:?}", err))
+            .ok()?;
         let pid = proc.pid();
+        self.scheduler.lock().admit(proc.write().sched_mut());
         self.add_proc(pid, proc);
         self.push_ready(pid);
-        debug!("Current queue: {:?}", self.ready_queue.lock());
+        debug!("Current queue: {}", self.scheduler.lock().queues());
+
+        Some(pid)
+    }
+
+    /// Replaces the image of the current process, the context is
+    /// restored at the new entry point. Returns false and keeps the
+    /// process unchanged if the image cannot be loaded.
+    pub fn exec(
+        &self,
+        elf: &ElfFile,
+        name: String,
+        argv: &[String],
+        envp: &[String],
+        context: &mut ProcessContext,
+    ) -> bool {
+        let kproc = self.get_proc(&KERNEL_PID).unwrap();
+        let page_table = kproc.read().clone_page_table();
+        let proc = self.current();
+        let mut inner = proc.write();
+
+        if let Err(err) = inner.exec(name, elf, argv, envp, page_table) {
+            warn!(
+                "Cannot exec process {}#{}: {:?}",
+                inner.name(),
+                proc.pid(),
+                err
+            );
+            return false;
+        }
+
+        inner.restore(context);
+
+        debug!("Exec process: {}#{}", inner.name(), proc.pid());
+
+        true
     }
 
     pub fn kill_self(&self, ret: isize) {
         self.kill(processor::current_pid(), ret);
     }
 
+    /// Wakes up the blocked process, it is only queued once here. The
+    /// current process blocked in the kernel is still on the CPU, so it
+    /// is resumed instead.
     pub fn wake_up(&self, pid: ProcessId, ret: Option<isize>) {
-        if let Some(proc) = self.get_proc(&pid) {
-            let mut inner = proc.write();
-            if let Some(ret) = ret {
-                inner.set_return(ret as usize);
-            }
+        let Some(proc) = self.get_proc(&pid) else {
+            return;
+        };
+
+        let mut inner = proc.write();
+
+        if inner.status() != ProgramStatus::Blocked {
+            return;
+        }
+
+        if let Some(ret) = ret {
+            inner.set_return(ret as usize);
+        }
+
+        if pid == processor::current_pid() {
+            inner.resume();
+        } else {
             inner.pause();
+            drop(inner);
             self.push_ready(pid);
         }
     }
@@ -209,7 +370,12 @@ impl ProcessManager {
     }
 
     pub fn handle_page_fault(&self, addr: VirtAddr, err_code: PageFaultErrorCode) -> bool {
-        if !err_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
+        if err_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
+            // the kernel may write to the pages of the process in a syscall,
+            // which holds the process for reading
+            err_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE)
+                && self.current().read().vm().handle_copy_on_write(addr)
+        } else {
             let cur_proc = self.current();
             trace!(
                 "Page Fault! Checking if {:#x} is on current process's stack",
@@ -222,8 +388,6 @@ impl ProcessManager {
 
             let mut inner = cur_proc.write();
             inner.handle_page_fault(addr)
-        } else {
-            false
         }
     }
 
@@ -249,8 +413,9 @@ impl ProcessManager {
     }
 
     pub fn print_process_list(&self) {
-        let mut output =
-            String::from("  PID | PPID | Process Name |  Ticks  |   Memory  | Status\n");
+        let mut output = String::from(
+            "  PID | PPID | Process Name |  Ticks  |   Memory  | Nice | Level | Status\n",
+        );
 
         self.processes
             .read()
@@ -282,7 +447,8 @@ impl ProcessManager {
 
         output += &format_res_usage("Cache", cache_used, cache_total);
 
-        output += format!("Queue  : {:?}\n", self.ready_queue.lock()).as_str();
+        let scheduler = self.scheduler.lock();
+        output += format!("Queue  : {} {}\n", scheduler.name(), scheduler.queues()).as_str();
 
         output += &processor::print_processors();
 
@@ -316,3 +482,40 @@ fn format_res_usage(name: &str, used: usize, total: usize) -> String {
         used as f32 / total as f32 * 100.0
     )
 }
+
+/// Opens the file with the filesystem methods for the mode,
+/// or the directory with its entries to read by `GetDents`
+fn open_resource(path: &str, mode: FileMode) -> storage::Result<Resource> {
+    let fs = get_rootfs();
+
+    let handle = match mode {
+        FileMode::Directory => return Ok(Resource::Directory(fs.read_dir(path)?)),
+        FileMode::ReadOnly => fs.open_file(path)?,
+        FileMode::ReadWriteAppend => fs.append_file(path)?,
+        FileMode::ReadWriteTruncate => {
+            fs.set_len(path, 0)?;
+            fs.open_file(path)?
+        }
+        FileMode::ReadWriteCreate => fs.create_file(path)?,
+        FileMode::ReadWriteCreateOrTruncate => {
+            if fs.exists(path)? {
+                fs.set_len(path, 0)?;
+                fs.open_file(path)?
+            } else {
+                fs.create_file(path)?
+            }
+        }
+        FileMode::ReadWriteCreateOrAppend => {
+            if fs.exists(path)? {
+                fs.append_file(path)?
+            } else {
+                fs.create_file(path)?
+            }
+        }
+    };
+
+    Ok(Resource::File {
+        handle,
+        writable: mode != FileMode::ReadOnly,
+    })
+}
@@ -5,6 +5,7 @@ mod paging;
 mod pid;
 mod process;
 mod processor;
+pub mod sched;
 mod sync;
 mod vm;
 
@@ -12,8 +13,10 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 use manager::*;
 use process::*;
-use storage::FileSystem;
+use sched::*;
+use storage::{FileSystem, SeekFrom};
 use sync::*;
+use syscall_def::{DirEntry, FileMode, FileStat};
 
 pub use context::ProcessContext;
 pub use data::ProcessData;
@@ -24,6 +27,8 @@ use xmas_elf::ElfFile;
 
 use crate::Resource;
 use crate::filesystem::get_rootfs;
+use crate::tasks::timer;
+use alloc::format;
 use alloc::string::{String, ToString};
 use x86_64::VirtAddr;
 use x86_64::structures::idt::PageFaultErrorCode;
@@ -56,6 +61,11 @@ pub fn init(boot_info: &'static boot::BootInfo) {
 pub fn switch(context: &mut ProcessContext) {
     x86_64::instructions::interrupts::without_interrupts(|| {
         let manager = get_process_manager();
+
+        if !manager.tick_current() {
+            return;
+        }
+
         let pid = manager.save_current(context);
         manager.push_ready(pid);
         manager.switch_next(context);
@@ -96,6 +106,83 @@ pub fn wait_pid(pid: ProcessId, context: &mut ProcessContext) {
     })
 }
 
+/// Blocks the current process for the duration, it is woken up
+/// by the timer interrupt after the ticks covering it have passed
+pub fn sleep(nanos: u64, context: &mut ProcessContext) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let ticks = timer::nanos_to_ticks(nanos);
+
+        context.set_rax(0);
+
+        if ticks == 0 {
+            return;
+        }
+
+        let manager = get_process_manager();
+        manager.sleep(timer::ticks() + ticks);
+        manager.save_current(context);
+        manager.current().write().block();
+        manager.switch_next(context);
+    })
+}
+
+/// Called by the timer interrupt to wake up the sleeping processes
+pub fn wake_up_sleepers() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().wake_up_sleepers(timer::ticks())
+    })
+}
+
+/// Called by the interrupt handlers to wake up the process blocked for them
+pub fn wake_up(pid: ProcessId) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().wake_up(pid, None)
+    })
+}
+
+/// Blocks the current process until `done` returns true, the CPU halts
+/// and serves interrupts until then, `done` is checked after each one.
+/// The interrupt handler of the event wakes it up with `wake_up`.
+///
+/// Syscalls of all processes share one stack, so the process cannot be
+/// switched out in the middle of it. Instead it stays on the CPU in the
+/// blocked state and the timer does not preempt it until woken up.
+///
+/// Returns false if the `deadline` in timer ticks passes before that.
+pub fn block_until(done: impl Fn() -> bool, deadline: Option<u64>) -> bool {
+    use x86_64::instructions::interrupts;
+
+    let enabled = interrupts::are_enabled();
+    interrupts::disable();
+
+    let manager = get_process_manager();
+    let pid = processor::current_pid();
+
+    manager.block(pid);
+
+    let ret = loop {
+        if done() {
+            break true;
+        }
+
+        if deadline.is_some_and(|deadline| timer::ticks() >= deadline) {
+            break false;
+        }
+
+        interrupts::enable_and_hlt();
+        interrupts::disable();
+    };
+
+    // resumed here if the handler has not woken it up
+    manager.wake_up(pid, None);
+
+    if enabled {
+        interrupts::enable();
+    }
+
+    ret
+}
+
 pub(crate) fn wait_no_block(pid: ProcessId) -> Option<isize> {
     x86_64::instructions::interrupts::without_interrupts(|| {
         get_process_manager().get_exit_code(pid)
@@ -110,14 +197,28 @@ pub fn write(fd: u8, buf: &[u8]) -> isize {
     x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().write(fd, buf))
 }
 
-pub fn open(path: &str) -> Option<u8> {
-    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().open(path))
+pub fn open(path: &str, mode: FileMode) -> Option<u8> {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().open(path, mode))
 }
 
 pub fn close(fd: u8) -> bool {
     x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().close(fd))
 }
 
+pub fn seek(fd: u8, pos: SeekFrom) -> isize {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().seek(fd, pos))
+}
+
+pub fn fstat(fd: u8) -> Option<FileStat> {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().fstat(fd))
+}
+
+pub fn get_dents(fd: u8, entries: &mut [DirEntry]) -> Option<usize> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().get_dents(fd, entries)
+    })
+}
+
 pub fn current_pid() -> ProcessId {
     x86_64::instructions::interrupts::without_interrupts(processor::current_pid)
 }
@@ -140,6 +241,14 @@ pub fn kill(pid: ProcessId, context: &mut ProcessContext) {
     })
 }
 
+/// Sets the nice value of the process, clamped to the valid range
+pub fn set_nice(pid: ProcessId, nice: isize) -> bool {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let nice = nice.clamp(NICE_MIN as isize, NICE_MAX as isize) as i8;
+        get_process_manager().set_nice(pid, nice)
+    })
+}
+
 pub fn new_sem(key: u32, value: usize) -> usize {
     x86_64::instructions::interrupts::without_interrupts(|| {
         if get_process_manager().current().write().new_sem(key, value) {
@@ -200,25 +309,27 @@ pub fn spawn(name: String, file_buffer: Vec<u8>) -> Result<ProcessId, String> {
 }
 
 pub fn elf_spawn(name: String, elf: &ElfFile) -> Result<ProcessId, String> {
+    let process_name = name.to_lowercase();
+
     let pid = x86_64::instructions::interrupts::without_interrupts(|| {
         let manager = get_process_manager();
-        let process_name = name.to_lowercase();
 
         let parent = Arc::downgrade(&manager.current());
-        let pid = manager.spawn(elf, name, Some(parent), None);
+        manager.spawn(elf, name, Some(parent), None)
+    })
+    .ok_or_else(|| format!("cannot create process {}", process_name))?;
 
-        debug!("Spawned process: {}#{}", process_name, pid);
-        pid
-    });
+    debug!("Spawned process: {}#{}", process_name, pid);
 
     Ok(pid)
 }
 
-pub fn fs_spawn(path: &str) -> Option<ProcessId> {
+/// Reads the whole file, returns its name and content
+fn read_file(path: &str) -> Option<(String, Vec<u8>)> {
     let handle = get_rootfs().open_file(path);
 
     if let Err(e) = handle {
-        warn!("fs_spawn: file error: {}, err: {:?}", path, e);
+        warn!("read_file: file error: {}, err: {:?}", path, e);
         return None;
     }
 
@@ -227,11 +338,17 @@ pub fn fs_spawn(path: &str) -> Option<ProcessId> {
     let mut file_buffer = Vec::new();
 
     if let Err(e) = handle.read_all(&mut file_buffer) {
-        warn!("fs_spawn: failed to read file: {}, err: {:?}", path, e);
+        warn!("read_file: failed to read file: {}, err: {:?}", path, e);
         return None;
     }
 
-    match spawn(handle.meta.name, file_buffer) {
+    Some((handle.meta.name, file_buffer))
+}
+
+pub fn fs_spawn(path: &str) -> Option<ProcessId> {
+    let (name, file_buffer) = read_file(path)?;
+
+    match spawn(name, file_buffer) {
         Ok(pid) => Some(pid),
         Err(e) => {
             warn!("fs_spawn: failed to spawn process: {}, {}", path, e);
@@ -240,11 +357,38 @@ pub fn fs_spawn(path: &str) -> Option<ProcessId> {
     }
 }
 
+/// Replaces the image of the current process with the ELF at `path`,
+/// returns false and keeps the process unchanged if it cannot be loaded
+pub fn exec(path: &str, argv: &[String], envp: &[String], context: &mut ProcessContext) -> bool {
+    let Some((name, file_buffer)) = read_file(path) else {
+        return false;
+    };
+
+    let elf = match ElfFile::new(&file_buffer) {
+        Ok(elf) => elf,
+        Err(e) => {
+            warn!("exec: invalid elf file: {}, {}", path, e);
+            return false;
+        }
+    };
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().exec(&elf, name, argv, envp, context)
+    })
+}
+
+/// Forks the current process and switches to the next one,
+/// returns -1 to the caller if the process cannot be forked
 pub fn fork(context: &mut ProcessContext) {
     x86_64::instructions::interrupts::without_interrupts(|| {
         let manager = get_process_manager();
         let parent = manager.save_current(context);
-        manager.fork();
+
+        if manager.fork().is_none() {
+            context.set_rax(usize::MAX);
+            return;
+        }
+
         manager.push_ready(parent);
         manager.switch_next(context);
     })
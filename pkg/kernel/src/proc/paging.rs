@@ -61,13 +61,6 @@ impl PageTableContext {
         unsafe { Cr3::write(self.reg.addr, self.reg.flags) }
     }
 
-    pub fn fork(&self) -> Self {
-        // forked process shares the page table
-        Self {
-            reg: self.reg.clone(),
-        }
-    }
-
     pub fn mapper(&self) -> OffsetPageTable<'static> {
         unsafe {
             OffsetPageTable::new(
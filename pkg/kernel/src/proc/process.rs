@@ -2,6 +2,7 @@ use super::*;
 use crate::humanized_size;
 use alloc::sync::Weak;
 use spin::*;
+use x86_64::structures::paging::{Size4KiB, mapper::MapToError};
 
 #[derive(Clone)]
 pub struct Process {
@@ -15,6 +16,7 @@ pub struct ProcessInner {
     children: Vec<Arc<Process>>,
     ticks_passed: usize,
     status: ProgramStatus,
+    sched: SchedInfo,
     context: ProcessContext,
     exit_code: Option<isize>,
     proc_data: Option<ProcessData>,
@@ -53,6 +55,7 @@ impl Process {
             name,
             parent,
             status: ProgramStatus::Ready,
+            sched: SchedInfo::default(),
             context: ProcessContext::default(),
             ticks_passed: 0,
             exit_code: None,
@@ -70,11 +73,16 @@ impl Process {
         })
     }
 
-    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+    /// Forks the process into `page_table`, a copy of the kernel page table,
+    /// the process is left untouched if its memory cannot be shared
+    pub fn fork(
+        self: &Arc<Self>,
+        page_table: PageTableContext,
+    ) -> Result<Arc<Self>, MapToError<Size4KiB>> {
         let mut inner = self.write();
 
         // create new process
-        let child_inner = inner.fork(Arc::downgrade(self));
+        let child_inner = inner.fork(Arc::downgrade(self), page_table)?;
         let child_pid = ProcessId::new();
 
         debug!(
@@ -96,10 +104,15 @@ impl Process {
         // pause child process
         inner.pause();
 
-        child
+        Ok(child)
     }
 
     pub fn kill(&self, ret: isize) {
+        // closing the files may block the process on the disk,
+        // so they are closed before it is marked dead
+        let proc_data = self.inner.write().proc_data.take();
+        drop(proc_data);
+
         let mut inner = self.inner.write();
 
         debug!(
@@ -126,6 +139,10 @@ impl ProcessInner {
         self.status
     }
 
+    pub fn sched_mut(&mut self) -> &mut SchedInfo {
+        &mut self.sched
+    }
+
     pub fn pause(&mut self) {
         self.status = ProgramStatus::Ready;
     }
@@ -146,6 +163,12 @@ impl ProcessInner {
         self.exit_code
     }
 
+    /// The data shared with the forked processes, it is used without
+    /// holding the process, which may be blocked on the disk meanwhile
+    pub fn proc_data(&self) -> ProcessData {
+        (**self).clone()
+    }
+
     pub fn vm(&self) -> &ProcessVm {
         self.proc_vm.as_ref().unwrap()
     }
@@ -162,10 +185,45 @@ impl ProcessInner {
         self.vm().page_table.clone_level_4()
     }
 
-    pub fn load_elf(&mut self, elf: &ElfFile) {
+    pub fn load_elf(&mut self, elf: &ElfFile) -> Result<(), MapToError<Size4KiB>> {
         self.vm_mut().load_elf(elf)
     }
 
+    /// Replaces the image of the process with the ELF, the pid, the
+    /// parent, the children and the resources are kept
+    ///
+    /// The image is loaded into `page_table`, a copy of the kernel page
+    /// table, and the old one is only freed after that, so the process
+    /// is left untouched if it runs out of memory.
+    pub fn exec(
+        &mut self,
+        name: String,
+        elf: &ElfFile,
+        argv: &[String],
+        envp: &[String],
+        page_table: PageTableContext,
+    ) -> Result<(), MapToError<Size4KiB>> {
+        let mut vm = ProcessVm::new(page_table);
+        vm.load_elf(elf)?;
+
+        // the arguments are written through the new page table
+        vm.page_table.load();
+        let (stack_top, args) = vm.push_args(argv, envp);
+
+        // the old image is freed with its page table
+        self.proc_vm = Some(vm);
+        self.name = name.to_ascii_lowercase();
+
+        self.context = ProcessContext::default();
+        self.context.init_stack_frame(
+            VirtAddr::new_truncate(elf.header.pt2.entry_point()),
+            stack_top,
+        );
+        self.context.set_rdi(args.as_u64() as usize);
+
+        Ok(())
+    }
+
     pub fn set_return(&mut self, ret: usize) {
         self.context.set_rax(ret);
     }
@@ -216,29 +274,32 @@ impl ProcessInner {
         }
     }
 
-    pub fn fork(&mut self, parent: Weak<Process>) -> ProcessInner {
-        let new_vm = self.vm().fork(self.children.len() as u64 + 1);
-        let offset = new_vm.stack.stack_offset(&self.vm().stack);
+    pub fn fork(
+        &mut self,
+        parent: Weak<Process>,
+        page_table: PageTableContext,
+    ) -> Result<ProcessInner, MapToError<Size4KiB>> {
+        // the address space is copied on write
+        let new_vm = self.vm().fork(page_table)?;
 
-        // make new stack frame
+        // the stack is at the same address
         let mut new_context = self.context;
-        // cal new stack pointer
-        new_context.set_stack_offset(offset);
         // set rax to 0
         new_context.set_rax(0);
 
         // create new process
-        Self {
+        Ok(Self {
             name: self.name.clone(),
             exit_code: None,
             parent: Some(parent),
             status: ProgramStatus::Ready,
+            sched: SchedInfo::new(self.sched.nice),
             ticks_passed: 0,
             context: new_context,
             children: Vec::new(),
             proc_vm: Some(new_vm),
             proc_data: self.proc_data.clone(),
-        }
+        })
     }
 
     pub fn kill(&mut self, pid: ProcessId, ret: isize) {
@@ -302,6 +363,7 @@ impl core::fmt::Debug for Process {
             .field("parent", &inner.parent().map(|p| p.pid))
             .field("status", &inner.status)
             .field("ticks_passed", &inner.ticks_passed)
+            .field("sched", &inner.sched)
             .field("children", &inner.children.iter().map(|c| c.pid.0))
             .field("status", &inner.status)
             .field("context", &inner.context)
@@ -316,13 +378,15 @@ impl core::fmt::Display for Process {
         let (size, unit) = humanized_size(inner.proc_vm.as_ref().map_or(0, |vm| vm.memory_usage()));
         write!(
             f,
-            " #{:-3} | #{:-3} | {:12} | {:7} | {:>5.1} {} | {:?}",
+            " #{:-3} | #{:-3} | {:12} | {:7} | {:>5.1} {} | {:>4} | {:>5} | {:?}",
             self.pid.0,
             inner.parent().map(|p| p.pid.0).unwrap_or(0),
             inner.name,
             inner.ticks_passed,
             size,
             unit,
+            inner.sched.nice,
+            inner.sched.level,
             inner.status
         )?;
         Ok(())
@@ -0,0 +1,136 @@
+//! Multi-Level Feedback Queue
+//!
+//! A process starts at the level given by its nice value and moves one
+//! level down each time it uses up its quantum, the lower levels have
+//! longer quanta. The running process is preempted as soon as a process
+//! of a higher level is ready. All the processes are boosted back to
+//! their starting level periodically so that none of them starves.
+//!
+//! reference: https://pages.cs.wisc.edu/~remzi/OSTEP/cpu-sched-mlfq.pdf
+
+use super::*;
+use alloc::{collections::VecDeque, format, vec::Vec};
+
+const LEVELS: usize = 4;
+
+/// Ticks of the quantum of each level
+const QUANTUM: [u64; LEVELS] = [1, 2, 4, 8];
+
+/// Ticks between two boosts
+const BOOST_INTERVAL: u64 = 100;
+
+#[derive(Default)]
+pub struct Mlfq {
+    /// The ready processes of each level, with their starting level
+    queues: [VecDeque<(ProcessId, usize)>; LEVELS],
+    ticks: u64,
+    /// The generation of the last boost
+    boost: u64,
+}
+
+impl Mlfq {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The positive nice values start from the lower levels
+    fn base_level(nice: i8) -> usize {
+        nice.max(0) as usize * LEVELS / (NICE_MAX as usize + 1)
+    }
+
+    /// The negative nice values double the quantum every 10 steps
+    fn quantum(info: &SchedInfo) -> u64 {
+        QUANTUM[info.level] << (info.nice.min(0).unsigned_abs() / 10)
+    }
+
+    /// Moves the process back to its starting level if a boost
+    /// happened since it was last seen
+    fn refresh(&self, info: &mut SchedInfo) {
+        if info.boost != self.boost {
+            info.boost = self.boost;
+            info.level = Self::base_level(info.nice);
+            info.used = 0;
+        }
+    }
+
+    /// Moves all the ready processes to their starting level, the
+    /// others are moved when they are seen again
+    fn boost_all(&mut self) {
+        self.boost += 1;
+
+        let ready: Vec<_> = self.queues.iter_mut().flat_map(|q| q.drain(..)).collect();
+
+        for (pid, base) in ready {
+            self.queues[base].push_back((pid, base));
+        }
+    }
+}
+
+impl Scheduler for Mlfq {
+    fn name(&self) -> &'static str {
+        "mlfq"
+    }
+
+    fn admit(&mut self, info: &mut SchedInfo) {
+        info.boost = self.boost;
+        info.level = Self::base_level(info.nice);
+        info.used = 0;
+    }
+
+    fn push(&mut self, pid: ProcessId, info: &mut SchedInfo) {
+        self.refresh(info);
+        self.queues[info.level].push_back((pid, Self::base_level(info.nice)));
+    }
+
+    fn pop(&mut self) -> Option<ProcessId> {
+        self.queues
+            .iter_mut()
+            .find_map(|queue| queue.pop_front())
+            .map(|(pid, _)| pid)
+    }
+
+    fn tick(&mut self, info: &mut SchedInfo) -> bool {
+        self.ticks += 1;
+
+        if self.ticks.is_multiple_of(BOOST_INTERVAL) {
+            self.boost_all();
+        }
+
+        self.refresh(info);
+        info.used += 1;
+
+        if info.used >= Self::quantum(info) {
+            info.used = 0;
+            info.level = (info.level + 1).min(LEVELS - 1);
+            return true;
+        }
+
+        // the quantum left is kept when preempted by a higher level
+        self.queues[..info.level].iter().any(|q| !q.is_empty())
+    }
+
+    fn set_nice(&mut self, info: &mut SchedInfo, nice: i8) {
+        let base = Self::base_level(nice);
+
+        // a higher priority takes effect at once, a lower one
+        // keeps the levels the process was moved down by its usage
+        info.level = if nice < info.nice {
+            base
+        } else {
+            info.level.max(base)
+        };
+        info.nice = nice;
+    }
+
+    fn queues(&self) -> String {
+        self.queues
+            .iter()
+            .enumerate()
+            .map(|(level, queue)| {
+                let pids: Vec<_> = queue.iter().map(|(pid, _)| pid).collect();
+                format!("L{}: {:?}", level, pids)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
@@ -0,0 +1,64 @@
+//! Process Schedulers
+//!
+//! The process manager asks the scheduler which ready process runs next
+//! and, on each timer tick, whether the running one should be preempted.
+//! The scheduling state of a process lives in its `SchedInfo`.
+
+mod mlfq;
+
+pub use mlfq::Mlfq;
+
+use super::ProcessId;
+use alloc::string::String;
+
+/// The range of the nice values, lower values get more CPU time
+pub const NICE_MIN: i8 = -20;
+pub const NICE_MAX: i8 = 19;
+
+/// The scheduling state of a process
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SchedInfo {
+    /// Set by the user, inherited by the children
+    pub nice: i8,
+    /// The queue level, 0 is the highest
+    pub level: usize,
+    /// Ticks used of the current quantum
+    pub used: u64,
+    /// The generation of the last boost seen, used by `Mlfq`
+    boost: u64,
+}
+
+impl SchedInfo {
+    pub fn new(nice: i8) -> Self {
+        Self {
+            nice,
+            ..Default::default()
+        }
+    }
+}
+
+pub trait Scheduler: Send {
+    /// Name of the policy, shown in the process list
+    fn name(&self) -> &'static str;
+
+    /// Sets up the state of a new process before it is first pushed
+    fn admit(&mut self, _info: &mut SchedInfo) {}
+
+    /// Adds the process to the ready queues
+    fn push(&mut self, pid: ProcessId, info: &mut SchedInfo);
+
+    /// Takes the next process to run, it may not be ready anymore
+    fn pop(&mut self) -> Option<ProcessId>;
+
+    /// Accounts a tick to the running process,
+    /// returns true if it should be switched out
+    fn tick(&mut self, info: &mut SchedInfo) -> bool;
+
+    /// Changes the nice value, which is already clamped
+    fn set_nice(&mut self, info: &mut SchedInfo, nice: i8) {
+        info.nice = nice;
+    }
+
+    /// Describes the ready queues
+    fn queues(&self) -> String;
+}
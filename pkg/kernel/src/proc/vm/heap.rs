@@ -28,10 +28,22 @@ impl Heap {
     pub fn fork(&self) -> Self {
         Self {
             base: self.base,
-            end: self.end.clone(),
+            end: Arc::new(AtomicU64::new(self.end.load(Ordering::Relaxed))),
         }
     }
 
+    /// The pages mapped by the heap, empty if it is not initialized
+    pub fn pages(&self) -> PageRange {
+        let start = Page::containing_address(self.base);
+        let end = self.end.load(Ordering::Relaxed);
+
+        if end == self.base.as_u64() {
+            return Page::range(start, start);
+        }
+
+        Page::range(start, Page::containing_address(VirtAddr::new(end)) + 1)
+    }
+
     pub fn brk(
         &self,
         new_end: Option<VirtAddr>,
@@ -1,14 +1,15 @@
-use alloc::{format, vec::Vec};
+use alloc::{format, string::String, vec::Vec};
 use boot::KernelPages;
+use syscall_def::{ExecArgs, StrRef};
 use x86_64::{
     VirtAddr,
     structures::paging::{
-        mapper::{CleanUp, UnmapError},
+        mapper::{CleanUp, MapToError, MappedFrame, TranslateResult, UnmapError},
         page::*,
         *,
     },
 };
-use xmas_elf::ElfFile;
+use xmas_elf::{ElfFile, program};
 
 use crate::{humanized_size, memory::*};
 
@@ -22,8 +23,13 @@ use super::PageTableContext;
 type MapperRef<'a> = &'a mut OffsetPageTable<'static>;
 type FrameAllocatorRef<'a> = &'a mut BootInfoFrameAllocator;
 
+/// Marks the pages shared read-only by `fork`, they are
+/// copied on the first write by any of the processes
+const COPY_ON_WRITE: PageTableFlags = PageTableFlags::BIT_9;
+
 pub struct ProcessVm {
-    // page table is shared by parent and child
+    // page table is owned by each process,
+    // only the kernel part is shared
     pub(super) page_table: PageTableContext,
 
     // stack is pre-process allocated
@@ -32,8 +38,7 @@ pub struct ProcessVm {
     // heap is allocated by brk syscall
     pub(super) heap: Heap,
 
-    // code is hold by the first process
-    // these fields will be empty for other processes
+    // code is shared by the forked processes
     pub(super) code: Vec<PageRangeInclusive>,
     pub(super) code_usage: u64,
 }
@@ -88,38 +93,108 @@ impl ProcessVm {
         )
     }
 
-    pub fn load_elf(&mut self, elf: &ElfFile) {
+    /// Loads the code and maps the stack of the ELF, the pages mapped
+    /// so far are unmapped if it runs out of memory
+    pub fn load_elf(&mut self, elf: &ElfFile) -> Result<(), MapToError<Size4KiB>> {
         let mapper = &mut self.page_table.mapper();
 
         let alloc = &mut *get_frame_alloc_for_sure();
 
-        self.load_elf_code(elf, mapper, alloc);
-        self.stack.init(mapper, alloc);
+        let result = self
+            .load_elf_code(elf, mapper, alloc)
+            .and_then(|_| self.stack.init(mapper, alloc));
+
+        if let Err(err) = &result {
+            error!("Cannot load elf: {:?}", err);
+
+            let code = elf
+                .program_iter()
+                .filter(|segment| segment.get_type() == Ok(program::Type::Load))
+                .filter(|segment| segment.mem_size() > 0)
+                .flat_map(|segment| {
+                    let start = VirtAddr::new(segment.virtual_addr());
+                    let end = start + (segment.mem_size() - 1);
+                    Page::range_inclusive(
+                        Page::containing_address(start),
+                        Page::containing_address(end),
+                    )
+                });
+            let stack = Page::range(
+                Page::containing_address(VirtAddr::new(stack::STACK_INIT_BOT)),
+                Page::containing_address(VirtAddr::new(stack::STACK_MAX)),
+            );
+
+            unmap_mapped(code.chain(stack), mapper, alloc);
+
+            self.code.clear();
+            self.code_usage = 0;
+        }
+
+        result
     }
 
-    fn load_elf_code(&mut self, elf: &ElfFile, mapper: MapperRef, alloc: FrameAllocatorRef) {
-        self.code =
-            elf::load_elf(elf, *PHYSICAL_OFFSET.get().unwrap(), mapper, alloc, true).unwrap();
+    fn load_elf_code(
+        &mut self,
+        elf: &ElfFile,
+        mapper: MapperRef,
+        alloc: FrameAllocatorRef,
+    ) -> Result<(), MapToError<Size4KiB>> {
+        self.code = elf::load_elf(elf, *PHYSICAL_OFFSET.get().unwrap(), mapper, alloc, true)?;
 
         let usage: usize = self.code.iter().map(|page| page.count()).sum();
-        self.code_usage = usage as u64 * crate::memory::PAGE_SIZE
+        self.code_usage = usage as u64 * crate::memory::PAGE_SIZE;
+
+        Ok(())
+    }
+
+    /// Duplicates the address space into `page_table`, a copy of the
+    /// kernel page table. The frames are shared and the writable pages
+    /// are made read-only in both tables until they are written.
+    ///
+    /// If a page cannot be mapped, the pages shared so far are unmapped
+    /// and the page table is freed with the returned error.
+    pub fn fork(&self, page_table: PageTableContext) -> Result<Self, MapToError<Size4KiB>> {
+        // the parts are moved in once all the pages are shared,
+        // so only the page table is freed if it is dropped before
+        let mut child = Self::new(page_table);
+
+        self.share_pages(&child.page_table)?;
+
+        child.stack = self.stack.fork();
+        child.heap = self.heap.fork();
+        child.code = self.code.clone();
+        child.code_usage = self.code_usage;
+
+        Ok(child)
     }
 
-    pub fn fork(&self, stack_offset_count: u64) -> Self {
-        let owned_page_table = self.page_table.fork();
-        let mapper = &mut owned_page_table.mapper();
+    fn share_pages(&self, page_table: &PageTableContext) -> Result<(), MapToError<Size4KiB>> {
+        let parent = &mut self.page_table.mapper();
+        let child = &mut page_table.mapper();
 
         let alloc = &mut *get_frame_alloc_for_sure();
 
-        Self {
-            page_table: owned_page_table,
-            stack: self.stack.fork(mapper, alloc, stack_offset_count),
-            heap: self.heap.fork(),
+        let pages = || {
+            self.code
+                .iter()
+                .flat_map(|range| *range)
+                .chain(self.heap.pages())
+                .chain(self.stack.pages())
+        };
 
-            // do not share code info
-            code: Vec::new(),
-            code_usage: 0,
+        for (shared, page) in pages().enumerate() {
+            if let Err(err) = share_page(page, parent, child, alloc) {
+                error!("Cannot share page {:#x}: {:?}", page.start_address(), err);
+
+                // the pages stay copy-on-write in the parent,
+                // they are made writable again on the next write
+                unmap_mapped(pages().take(shared), child, alloc);
+
+                return Err(err);
+            }
         }
+
+        Ok(())
     }
 
     pub fn handle_page_fault(&mut self, addr: VirtAddr) -> bool {
@@ -129,6 +204,115 @@ impl ProcessVm {
         self.stack.handle_page_fault(addr, mapper, alloc)
     }
 
+    /// Resolves a write to a copy-on-write page, the page is copied
+    /// unless no other process maps the frame anymore
+    ///
+    /// It only changes the page table, so it takes `&self` to be called
+    /// while the process is read by the syscall writing to the page.
+    pub fn handle_copy_on_write(&self, addr: VirtAddr) -> bool {
+        let mapper = &mut self.page_table.mapper();
+        let page = Page::<Size4KiB>::containing_address(addr);
+
+        let TranslateResult::Mapped {
+            frame: MappedFrame::Size4KiB(frame),
+            flags,
+            ..
+        } = mapper.translate(page.start_address())
+        else {
+            return false;
+        };
+
+        if !flags.contains(COPY_ON_WRITE) {
+            return false;
+        }
+
+        let flags = (flags - COPY_ON_WRITE) | PageTableFlags::WRITABLE;
+        let alloc = &mut *get_frame_alloc_for_sure();
+
+        if alloc.frame_refs(frame) == 1 {
+            // the other processes have copied or dropped it
+            return match unsafe { mapper.update_flags(page, flags) } {
+                Ok(flush) => {
+                    flush.flush();
+                    true
+                }
+                Err(_) => false,
+            };
+        }
+
+        let Some(new_frame) = alloc.allocate_frame() else {
+            error!("Cannot allocate frame to copy page {:#x}", addr);
+            return false;
+        };
+
+        unsafe {
+            core::ptr::copy_nonoverlapping::<u8>(
+                physical_to_virtual(frame.start_address().as_u64()) as *const u8,
+                physical_to_virtual(new_frame.start_address().as_u64()) as *mut u8,
+                Size4KiB::SIZE as usize,
+            );
+        }
+
+        if let Ok((_, flush)) = mapper.unmap(page) {
+            flush.ignore();
+        }
+
+        unsafe {
+            // drop the reference of this process
+            alloc.deallocate_frame(frame);
+
+            match mapper.map_to_with_table_flags(page, new_frame, flags, USER_TABLE_FLAGS, alloc) {
+                Ok(flush) => flush.flush(),
+                Err(err) => {
+                    error!("Cannot map the copied page {:#x}: {:?}", addr, err);
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Copies the arguments and environment of `Exec` to the top of the
+    /// stack, returns the stack pointer and the address of the `ExecArgs`
+    ///
+    /// The page table of the process must be loaded, and they must fit
+    /// in `stack::EXEC_ARGS_MAX` bytes to stay in the mapped stack.
+    pub fn push_args(&self, argv: &[String], envp: &[String]) -> (VirtAddr, VirtAddr) {
+        let mut sp = stack::STACK_MAX;
+
+        let mut push_str = |s: &String| {
+            sp -= s.len() as u64;
+            unsafe { core::ptr::copy_nonoverlapping(s.as_ptr(), sp as *mut u8, s.len()) };
+            StrRef {
+                ptr: sp as *const u8,
+                len: s.len(),
+            }
+        };
+
+        let argv: Vec<StrRef> = argv.iter().map(&mut push_str).collect();
+        let envp: Vec<StrRef> = envp.iter().map(&mut push_str).collect();
+
+        let mut push_refs = |refs: &[StrRef]| {
+            sp = (sp - core::mem::size_of_val(refs) as u64) & !0xf;
+            unsafe { core::ptr::copy_nonoverlapping(refs.as_ptr(), sp as *mut StrRef, refs.len()) };
+            sp as *const StrRef
+        };
+
+        let args = ExecArgs {
+            argv: push_refs(&argv),
+            argc: argv.len(),
+            envp: push_refs(&envp),
+            envc: envp.len(),
+        };
+
+        sp = (sp - core::mem::size_of::<ExecArgs>() as u64) & !0xf;
+        unsafe { (sp as *mut ExecArgs).write(args) };
+
+        // aligned as if the entry point is called
+        (VirtAddr::new(sp - 8), VirtAddr::new(sp))
+    }
+
     pub(super) fn memory_usage(&self) -> u64 {
         self.stack.memory_usage() + self.heap.memory_usage() + self.code_usage
     }
@@ -139,24 +323,14 @@ impl ProcessVm {
 
         let start_count = dealloc.frames_recycled();
 
-        self.stack.clean_up(mapper, dealloc)?;
-
-        if self.page_table.using_count() == 1 {
-            // free heap
-            self.heap.clean_up(mapper, dealloc)?;
-
-            // free code
-            for page_range in self.code.iter() {
-                elf::unmap_range(*page_range, mapper, dealloc, true)?;
-            }
+        self.unload_pages(mapper, dealloc)?;
 
-            unsafe {
-                // free P1-P3
-                mapper.clean_up(dealloc);
+        unsafe {
+            // free P1-P3
+            mapper.clean_up(dealloc);
 
-                // free P4
-                dealloc.deallocate_frame(self.page_table.reg.addr);
-            }
+            // free P4
+            dealloc.deallocate_frame(self.page_table.reg.addr);
         }
 
         let end_count = dealloc.frames_recycled();
@@ -171,6 +345,82 @@ impl ProcessVm {
 
         Ok(())
     }
+
+    fn unload_pages(
+        &mut self,
+        mapper: MapperRef,
+        dealloc: FrameAllocatorRef,
+    ) -> Result<(), UnmapError> {
+        // the frames shared with other processes are only recycled
+        // when the last of them is cleaned up
+        self.stack.clean_up(mapper, dealloc)?;
+
+        // free heap
+        self.heap.clean_up(mapper, dealloc)?;
+
+        // free code
+        for page_range in self.code.iter() {
+            elf::unmap_range(*page_range, mapper, dealloc, true)?;
+        }
+
+        self.code.clear();
+        self.code_usage = 0;
+
+        Ok(())
+    }
+}
+
+/// Flags of the page tables above the user pages, the permissions
+/// are restricted by the flags of the pages themselves
+const USER_TABLE_FLAGS: PageTableFlags = PageTableFlags::PRESENT
+    .union(PageTableFlags::WRITABLE)
+    .union(PageTableFlags::USER_ACCESSIBLE);
+
+/// Maps the page of the parent to the same frame in the child, the
+/// writable pages become read-only and copy-on-write in both of them
+fn share_page(
+    page: Page,
+    parent: MapperRef,
+    child: MapperRef,
+    alloc: FrameAllocatorRef,
+) -> Result<(), MapToError<Size4KiB>> {
+    let TranslateResult::Mapped {
+        frame: MappedFrame::Size4KiB(frame),
+        mut flags,
+        ..
+    } = parent.translate(page.start_address())
+    else {
+        // the pages are not mapped in the middle of a range
+        return Ok(());
+    };
+
+    if flags.contains(PageTableFlags::WRITABLE) {
+        flags = (flags - PageTableFlags::WRITABLE) | COPY_ON_WRITE;
+
+        if let Ok(flush) = unsafe { parent.update_flags(page, flags) } {
+            flush.flush();
+        }
+    }
+
+    unsafe {
+        child
+            .map_to_with_table_flags(page, frame, flags, USER_TABLE_FLAGS, alloc)?
+            .ignore();
+    }
+
+    alloc.share_frame(frame);
+
+    Ok(())
+}
+
+/// Unmaps and frees the pages mapped before a failure, the others are skipped
+fn unmap_mapped(pages: impl Iterator<Item = Page>, mapper: MapperRef, dealloc: FrameAllocatorRef) {
+    for page in pages {
+        if let Ok((frame, flush)) = mapper.unmap(page) {
+            flush.flush();
+            unsafe { dealloc.deallocate_frame(frame) };
+        }
+    }
 }
 
 impl Drop for ProcessVm {
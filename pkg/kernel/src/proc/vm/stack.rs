@@ -1,5 +1,3 @@
-use core::ptr::copy_nonoverlapping;
-
 use x86_64::{
     VirtAddr,
     structures::paging::{
@@ -34,6 +32,10 @@ pub const STACK_DEF_SIZE: u64 = STACK_DEF_PAGE * crate::memory::PAGE_SIZE;
 pub const STACK_INIT_BOT: u64 = STACK_MAX - STACK_DEF_SIZE;
 pub const STACK_INIT_TOP: u64 = STACK_MAX - 8;
 
+// the arguments and environment copied to the top of the init stack
+// by exec, the rest of the page is left to the program
+pub const EXEC_ARGS_MAX: usize = STACK_DEF_SIZE as usize / 2;
+
 const STACK_INIT_TOP_PAGE: Page<Size4KiB> = Page::containing_address(VirtAddr::new(STACK_INIT_TOP));
 
 // [bot..0xffffff0100000000..top..0xffffff01ffffffff]
@@ -70,46 +72,31 @@ impl Stack {
         }
     }
 
-    pub fn init(&mut self, mapper: MapperRef, alloc: FrameAllocatorRef) {
-        debug_assert!(self.usage == 0, "Stack is not empty.");
-
-        self.range = elf::map_pages(STACK_INIT_BOT, STACK_DEF_PAGE, mapper, alloc, true).unwrap();
-        self.usage = STACK_DEF_PAGE;
-    }
-
-    pub fn fork(
-        &self,
+    pub fn init(
+        &mut self,
         mapper: MapperRef,
         alloc: FrameAllocatorRef,
-        stack_offset_count: u64,
-    ) -> Self {
-        let cur_stack_base = self.range.start.start_address().as_u64();
-        let mut new_stack_base = cur_stack_base - stack_offset_count * STACK_MAX_SIZE;
-
-        while elf::map_pages(new_stack_base, self.usage, mapper, alloc, true).is_err() {
-            trace!("Map thread stack to {:#x} failed.", new_stack_base);
-            new_stack_base -= STACK_MAX_SIZE; // stack grow down
-        }
-
-        debug!("Map thread stack to {:#x} succeed.", new_stack_base);
+    ) -> Result<(), MapToError<Size4KiB>> {
+        debug_assert!(self.usage == 0, "Stack is not empty.");
 
-        // clone new stack content
-        self.clone_range(cur_stack_base, new_stack_base, self.usage);
+        self.range = elf::map_pages(STACK_INIT_BOT, STACK_DEF_PAGE, mapper, alloc, true)?;
+        self.usage = STACK_DEF_PAGE;
 
-        let new_start = Page::containing_address(VirtAddr::new(new_stack_base));
+        Ok(())
+    }
 
+    /// The forked stack is at the same address,
+    /// its pages are shared by `ProcessVm::fork`
+    pub fn fork(&self) -> Self {
         Self {
-            range: Page::range(new_start, new_start + self.usage),
+            range: self.range,
             usage: self.usage,
         }
     }
 
-    pub fn stack_offset(&self, old_stack: &Stack) -> u64 {
-        let cur_stack_base = self.range.start.start_address().as_u64();
-        let old_stack_base = old_stack.range.start.start_address().as_u64();
-        let offset = cur_stack_base - old_stack_base;
-        debug_assert!(offset % STACK_MAX_SIZE != 0, "Invalid stack offset.");
-        offset
+    /// The pages mapped by the stack
+    pub fn pages(&self) -> PageRange {
+        self.range
     }
 
     pub fn handle_page_fault(
@@ -171,17 +158,6 @@ impl Stack {
 
         Ok(())
     }
-
-    fn clone_range(&self, cur_addr: u64, dest_addr: u64, size: u64) {
-        trace!("Clone range: {:#x} -> {:#x}", cur_addr, dest_addr);
-        unsafe {
-            copy_nonoverlapping::<u64>(
-                cur_addr as *mut u64,
-                dest_addr as *mut u64,
-                (size * Size4KiB::SIZE / 8) as usize,
-            );
-        }
-    }
 }
 
 impl VmPartExt for Stack {
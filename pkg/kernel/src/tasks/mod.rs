@@ -7,6 +7,7 @@ use core::{
 };
 
 pub mod executor;
+pub mod timer;
 pub use executor::Executor;
 
 pub struct Task {
@@ -0,0 +1,120 @@
+use alloc::collections::BinaryHeap;
+use core::{
+    cmp::Ordering as CmpOrdering,
+    future::poll_fn,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Poll, Waker},
+};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+/// Number of timer interrupts since boot
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Period of the timer interrupt, measured when the APIC is initialized
+static TICK_NANOS: AtomicU64 = AtomicU64::new(1_000_000);
+
+/// A kernel task waiting for the timer
+struct Sleeper {
+    deadline: u64,
+    waker: Waker,
+}
+
+impl PartialEq for Sleeper {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for Sleeper {}
+
+impl PartialOrd for Sleeper {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Sleeper {
+    /// The earliest deadline is the greatest, on top of the heap
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+/// The kernel tasks waiting for the timer, the earliest deadline first
+static SLEEPERS: Mutex<BinaryHeap<Sleeper>> = Mutex::new(BinaryHeap::new());
+
+/// The earliest deadline in `SLEEPERS`, saves locking it on every tick
+static NEXT_DEADLINE: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Called by the timer interrupt handler
+pub fn tick() {
+    let ticks = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+
+    if ticks < NEXT_DEADLINE.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mut sleepers = SLEEPERS.lock();
+
+    while let Some(sleeper) = sleepers.peek()
+        && sleeper.deadline <= ticks
+    {
+        if let Some(sleeper) = sleepers.pop() {
+            sleeper.waker.wake();
+        }
+    }
+
+    let next = sleepers.peek().map_or(u64::MAX, |sleeper| sleeper.deadline);
+    NEXT_DEADLINE.store(next, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+pub fn set_tick_nanos(nanos: u64) {
+    TICK_NANOS.store(nanos.max(1), Ordering::Relaxed);
+}
+
+/// Converts the duration to ticks, rounded up
+#[inline]
+pub fn nanos_to_ticks(nanos: u64) -> u64 {
+    nanos.div_ceil(TICK_NANOS.load(Ordering::Relaxed))
+}
+
+/// Waits until the given number of ticks has passed
+pub async fn sleep_ticks(count: u64) {
+    let deadline = ticks() + count;
+    let mut registered: Option<Waker> = None;
+
+    poll_fn(|cx| {
+        if ticks() >= deadline {
+            return Poll::Ready(());
+        }
+
+        // queue the waker again only if the task is polled by another one
+        if !registered
+            .as_ref()
+            .is_some_and(|waker| waker.will_wake(cx.waker()))
+        {
+            let waker = cx.waker().clone();
+            registered = Some(waker.clone());
+
+            // the queue is also locked by the timer interrupt
+            interrupts::without_interrupts(|| {
+                SLEEPERS.lock().push(Sleeper { deadline, waker });
+                NEXT_DEADLINE.fetch_min(deadline, Ordering::Relaxed);
+            });
+        }
+
+        // the deadline may have passed before it was queued
+        if ticks() >= deadline {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
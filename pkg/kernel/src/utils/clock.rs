@@ -1,6 +1,136 @@
+//! Kernel Clocks
+//!
+//! The monotonic clock counts the nanoseconds since boot with the TSC,
+//! its frequency is measured with the PIT, or taken from the CPUID when
+//! the PIT does not answer. The UEFI time is read only once at boot as
+//! the base of the wall clock.
+
+use crate::drivers::pit;
 use chrono::{DateTime, naive::*};
+use x86::cpuid::CpuId;
+use x86::time::rdtsc;
+
+/// Time measured by the PIT to calibrate the TSC
+const CALIBRATE_MS: u64 = 50;
+
+/// TSC cycles to wait for the PIT before giving up,
+/// four times `CALIBRATE_MS` on a 10 GHz TSC
+const CALIBRATE_TIMEOUT: u64 = 10_000_000 * CALIBRATE_MS * 4;
+
+/// Frequency assumed when neither the PIT nor the CPUID gives one
+const DEFAULT_TSC_FREQUENCY: u64 = 1_000_000_000;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+struct Clock {
+    /// Frequency of the TSC in Hz
+    tsc_frequency: u64,
+    /// The TSC at boot, where the monotonic clock starts
+    tsc_base: u64,
+    /// Nanoseconds since the unix epoch at boot
+    realtime_base: i64,
+}
+
+static CLOCK: spin::Once<Clock> = spin::Once::new();
+
+pub fn init() {
+    CLOCK.call_once(|| {
+        let invariant = CpuId::new()
+            .get_advanced_power_mgmt_info()
+            .is_some_and(|info| info.has_invariant_tsc());
+
+        if !invariant {
+            warn!("TSC is not invariant, the clock may drift.");
+        }
+
+        let tsc_frequency = calibrate_tsc()
+            .or_else(cpuid_tsc_frequency)
+            .unwrap_or_else(|| {
+                warn!("Cannot measure the TSC frequency, assume 1 GHz.");
+                DEFAULT_TSC_FREQUENCY
+            });
+        let tsc_base = unsafe { rdtsc() };
+        let realtime_base = uefi_time()
+            .and_utc()
+            .timestamp_nanos_opt()
+            .unwrap_or_default();
+
+        info!("TSC frequency: {} MHz", tsc_frequency / 1_000_000);
+
+        Clock {
+            tsc_frequency,
+            tsc_base,
+            realtime_base,
+        }
+    });
+}
+
+/// Counts the TSC cycles during `CALIBRATE_MS`, returns the frequency,
+/// or None if the PIT has not finished within `CALIBRATE_TIMEOUT`
+fn calibrate_tsc() -> Option<u64> {
+    pit::start(CALIBRATE_MS);
+    let start = unsafe { rdtsc() };
+
+    while !pit::finished() {
+        if unsafe { rdtsc() }.wrapping_sub(start) > CALIBRATE_TIMEOUT {
+            pit::stop();
+            warn!("PIT timed out, cannot calibrate the TSC.");
+            return None;
+        }
+
+        core::hint::spin_loop();
+    }
+
+    let end = unsafe { rdtsc() };
+    pit::stop();
+
+    Some((end.wrapping_sub(start) * 1000 / CALIBRATE_MS).max(1))
+}
+
+/// The TSC frequency reported by the CPUID leaf 0x15,
+/// or the base frequency of the processor from the leaf 0x16
+fn cpuid_tsc_frequency() -> Option<u64> {
+    let cpuid = CpuId::new();
+
+    cpuid
+        .get_tsc_info()
+        .and_then(|info| info.tsc_frequency())
+        .or_else(|| {
+            cpuid
+                .get_processor_frequency_info()
+                .map(|info| info.processor_base_frequency() as u64 * 1_000_000)
+        })
+        .filter(|&frequency| frequency != 0)
+}
+
+/// Nanoseconds since boot, 0 before the clock is initialized
+pub fn monotonic_nanos() -> u64 {
+    let Some(clock) = CLOCK.get() else {
+        return 0;
+    };
+
+    let cycles = unsafe { rdtsc() }.saturating_sub(clock.tsc_base) as u128;
+    (cycles * NANOS_PER_SEC / clock.tsc_frequency as u128) as u64
+}
+
+/// Nanoseconds since the unix epoch
+pub fn realtime_nanos() -> i64 {
+    match CLOCK.get() {
+        Some(clock) => clock.realtime_base + monotonic_nanos() as i64,
+        None => uefi_time()
+            .and_utc()
+            .timestamp_nanos_opt()
+            .unwrap_or_default(),
+    }
+}
 
 pub fn now() -> NaiveDateTime {
+    DateTime::from_timestamp_nanos(realtime_nanos()).naive_utc()
+}
+
+/// Reads the wall clock from the firmware, it is slow and
+/// only precise to the second on many of them
+fn uefi_time() -> NaiveDateTime {
     let time = match uefi::runtime::get_time() {
         Ok(time) => time,
         Err(_) => return DateTime::from_timestamp(0, 0).unwrap().naive_utc(),
@@ -1,7 +1,10 @@
-use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
 use pc_keyboard::DecodedKey;
 use spin::Mutex;
-use storage::{Device, FileHandle, random::Random};
+use storage::{FileHandle, FileSystem, Metadata, Mount, Read, Seek, SeekFrom, Write};
+use syscall_def::{
+    DIR_ENTRY_NAME_LEN, DirEntry, FS_TYPE_LEN, FileStat, MOUNT_POINT_LEN, MountEntry,
+};
 
 use crate::input::try_get_key;
 
@@ -70,20 +73,42 @@ impl ResourceSet {
             -1
         }
     }
+
+    pub fn seek(&self, fd: u8, pos: SeekFrom) -> isize {
+        if let Some(offset) = self.handles.get(&fd).and_then(|h| h.lock().seek(pos)) {
+            offset as isize
+        } else {
+            -1
+        }
+    }
+
+    pub fn stat(&self, fd: u8) -> Option<FileStat> {
+        self.handles.get(&fd).and_then(|h| h.lock().stat())
+    }
+
+    pub fn get_dents(&self, fd: u8, entries: &mut [DirEntry]) -> Option<usize> {
+        self.handles
+            .get(&fd)
+            .and_then(|h| h.lock().get_dents(entries))
+    }
 }
 
 pub enum Resource {
-    File(FileHandle),
+    /// Files opened with `FileMode::ReadOnly` are not writable
+    File {
+        handle: FileHandle,
+        writable: bool,
+    },
+    /// The entries of a directory not yet read by `GetDents`
+    Directory(Box<dyn Iterator<Item = Metadata> + Send>),
     Console(StdIO),
-    Random(Random),
-    Null,
 }
 
 impl Resource {
     fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
         match self {
-            Resource::File(file) => {
-                let ret = file.read(buf);
+            Resource::File { handle, .. } => {
+                let ret = handle.read(buf);
                 if let Err(e) = ret {
                     error!("Failed to read file: {:?}", e);
                     None
@@ -102,14 +127,22 @@ impl Resource {
                 }),
                 _ => Some(0),
             },
-            Resource::Random(random) => Some(random.read(buf, 0, buf.len()).unwrap()),
-            Resource::Null => Some(0),
+            Resource::Directory(_) => None,
         }
     }
 
-    fn write(&self, buf: &[u8]) -> Option<usize> {
+    fn write(&mut self, buf: &[u8]) -> Option<usize> {
         match self {
-            Resource::File(_) => None,
+            Resource::File {
+                writable: false, ..
+            } => None,
+            Resource::File { handle, .. } => match handle.write(buf) {
+                Ok(count) => Some(count),
+                Err(e) => {
+                    error!("Failed to write file: {:?}", e);
+                    None
+                }
+            },
             Resource::Console(stdio) => match *stdio {
                 StdIO::Stdin => Some(0),
                 StdIO::Stdout => {
@@ -121,19 +154,114 @@ impl Resource {
                     Some(buf.len())
                 }
             },
-            Resource::Random(_) => Some(0),
-            Resource::Null => Some(buf.len()),
+            Resource::Directory(_) => None,
+        }
+    }
+}
+
+impl Resource {
+    fn seek(&mut self, pos: SeekFrom) -> Option<usize> {
+        match self {
+            Resource::File { handle, .. } => match handle.seek(pos) {
+                Ok(offset) => Some(offset),
+                Err(e) => {
+                    error!("Failed to seek file: {:?}", e);
+                    None
+                }
+            },
+            Resource::Directory(_) | Resource::Console(_) => None,
         }
     }
+
+    /// The length in the metadata follows the writes through the handle
+    fn stat(&self) -> Option<FileStat> {
+        match self {
+            Resource::File { handle, .. } => Some(file_stat(&handle.meta)),
+            Resource::Directory(_) | Resource::Console(_) => None,
+        }
+    }
+
+    /// Fills the records with the next entries of the directory,
+    /// returns how many, 0 at the end
+    fn get_dents(&mut self, buf: &mut [DirEntry]) -> Option<usize> {
+        match self {
+            // the buffer is checked first, no entry is dropped when it is full
+            Resource::Directory(entries) => Some(
+                buf.iter_mut()
+                    .zip(entries)
+                    .map(|(entry, meta)| *entry = dir_entry(&meta))
+                    .count(),
+            ),
+            Resource::File { .. } | Resource::Console(_) => None,
+        }
+    }
+}
+
+/// Converts the metadata into the struct shared with the user space
+pub fn file_stat(meta: &Metadata) -> FileStat {
+    let nanos = |time: Option<storage::FsTime>| {
+        time.and_then(|t| t.timestamp_nanos_opt())
+            .unwrap_or_default()
+    };
+
+    FileStat {
+        file_type: if meta.is_dir() {
+            FileStat::DIRECTORY
+        } else {
+            FileStat::FILE
+        },
+        reserved: 0,
+        len: meta.len as u64,
+        created: nanos(meta.created),
+        modified: nanos(meta.modified),
+        accessed: nanos(meta.accessed),
+    }
+}
+
+/// Converts the metadata into the record of `GetDents`,
+/// the name is truncated to `DIR_ENTRY_NAME_LEN` bytes
+pub fn dir_entry(meta: &Metadata) -> DirEntry {
+    let stat = file_stat(meta);
+    let name = meta.name.as_bytes();
+    let name_len = name.len().min(DIR_ENTRY_NAME_LEN);
+
+    let mut entry = DirEntry {
+        file_type: stat.file_type,
+        name_len: name_len as u32,
+        len: stat.len,
+        modified: stat.modified,
+        ..Default::default()
+    };
+
+    entry.name[..name_len].copy_from_slice(&name[..name_len]);
+    entry
+}
+
+/// Converts the mount into the record of `ListMounts`,
+/// the strings are truncated to the length of their fields
+pub fn mount_entry(mount: &Mount) -> MountEntry {
+    let fs_type = mount.fs_type().as_bytes();
+    let fs_type_len = fs_type.len().min(FS_TYPE_LEN);
+    let mount_point = mount.mount_point.as_bytes();
+    let mount_point_len = mount_point.len().min(MOUNT_POINT_LEN);
+
+    let mut entry = MountEntry {
+        fs_type_len: fs_type_len as u32,
+        mount_point_len: mount_point_len as u32,
+        ..Default::default()
+    };
+
+    entry.fs_type[..fs_type_len].copy_from_slice(&fs_type[..fs_type_len]);
+    entry.mount_point[..mount_point_len].copy_from_slice(&mount_point[..mount_point_len]);
+    entry
 }
 
 impl core::fmt::Debug for Resource {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
-            Resource::File(h) => write!(f, "File({})", h.meta.name),
+            Resource::File { handle, .. } => write!(f, "File({})", handle.meta.name),
+            Resource::Directory(_) => write!(f, "Directory"),
             Resource::Console(c) => write!(f, "Console({:?})", c),
-            Resource::Random(_) => write!(f, "Random"),
-            Resource::Null => write!(f, "Null"),
         }
     }
 }
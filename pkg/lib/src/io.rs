@@ -1,6 +1,9 @@
 use crate::*;
 use alloc::string::*;
 use alloc::vec;
+use alloc::vec::Vec;
+
+pub use syscall_def::{DirEntry, FileMode, FileStat, MountEntry, PciEntry, SeekWhence};
 
 pub struct Stdin;
 pub struct Stdout;
@@ -132,6 +135,210 @@ impl Drop for Random {
     }
 }
 
+/// Where to seek in a file, like `std::io::SeekFrom`
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SeekFrom {
+    Start(u64),
+    End(i64),
+    Current(i64),
+}
+
+/// An opened file, closed when dropped
+pub struct File {
+    fd: u8,
+}
+
+impl File {
+    /// Opens the file in read-only mode
+    pub fn open(path: &str) -> Option<Self> {
+        Self::open_with(path, FileMode::ReadOnly)
+    }
+
+    /// Opens the file for writing, it is created if it does not exist
+    /// and truncated if it does
+    pub fn create(path: &str) -> Option<Self> {
+        Self::open_with(path, FileMode::ReadWriteCreateOrTruncate)
+    }
+
+    pub fn open_with(path: &str, mode: FileMode) -> Option<Self> {
+        match sys_open(path, mode) {
+            0 => None,
+            fd => Some(Self { fd }),
+        }
+    }
+
+    #[inline]
+    pub fn fd(&self) -> u8 {
+        self.fd
+    }
+
+    pub fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        sys_read(self.fd, buf)
+    }
+
+    /// Reads until the end of the file, returns the bytes read
+    pub fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Option<usize> {
+        let start = buf.len();
+        let mut chunk = vec![0; 4096];
+
+        loop {
+            match self.read(&mut chunk)? {
+                0 => break,
+                n => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+
+        Some(buf.len() - start)
+    }
+
+    /// Reads until the end of the file, fails if it is not valid UTF-8
+    pub fn read_to_string(&mut self, buf: &mut String) -> Option<usize> {
+        let mut bytes = Vec::new();
+        let len = self.read_to_end(&mut bytes)?;
+
+        buf.push_str(core::str::from_utf8(&bytes).ok()?);
+        Some(len)
+    }
+
+    pub fn write(&mut self, buf: &[u8]) -> Option<usize> {
+        sys_write(self.fd, buf)
+    }
+
+    pub fn write_all(&mut self, mut buf: &[u8]) -> Option<()> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return None,
+                n => buf = &buf[n..],
+            }
+        }
+
+        Some(())
+    }
+
+    /// Returns the new offset from the start of the file
+    pub fn seek(&mut self, pos: SeekFrom) -> Option<u64> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(offset) => (offset as isize, SeekWhence::Start),
+            SeekFrom::End(offset) => (offset as isize, SeekWhence::End),
+            SeekFrom::Current(offset) => (offset as isize, SeekWhence::Current),
+        };
+
+        sys_seek(self.fd, offset, whence).map(|offset| offset as u64)
+    }
+
+    pub fn metadata(&self) -> Option<FileStat> {
+        sys_fstat(self.fd)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        sys_close(self.fd);
+    }
+}
+
+/// Entries fetched from the kernel by each `GetDents`
+const READ_DIR_BATCH: usize = 16;
+
+/// Iterates over the entries of a directory, fetched in batches
+/// from the directory opened in the kernel
+pub struct ReadDir {
+    fd: u8,
+    entries: Vec<DirEntry>,
+    /// The next entry to yield in the batch
+    pos: usize,
+}
+
+/// Opens the directory for iterating, fails if it cannot be read
+pub fn read_dir(path: &str) -> Option<ReadDir> {
+    let fd = match sys_open(path, FileMode::Directory) {
+        0 => return None,
+        fd => fd,
+    };
+
+    let mut dir = ReadDir {
+        fd,
+        entries: Vec::with_capacity(READ_DIR_BATCH),
+        pos: 0,
+    };
+
+    dir.fetch()?;
+    Some(dir)
+}
+
+impl ReadDir {
+    /// Replaces the batch with the next entries, returns how many
+    fn fetch(&mut self) -> Option<usize> {
+        self.entries.clear();
+        self.entries.resize(READ_DIR_BATCH, DirEntry::default());
+
+        let count = sys_get_dents(self.fd, &mut self.entries)?;
+
+        self.entries.truncate(count);
+        self.pos = 0;
+
+        Some(count)
+    }
+}
+
+impl Drop for ReadDir {
+    fn drop(&mut self) {
+        sys_close(self.fd);
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos == self.entries.len() {
+            // a short batch means the end of the directory
+            if self.entries.len() < READ_DIR_BATCH || self.fetch()? == 0 {
+                return None;
+            }
+        }
+
+        self.pos += 1;
+        Some(self.entries[self.pos - 1])
+    }
+}
+
+/// Mounts fetched from the kernel by the first `ListMounts`
+const MOUNTS_BATCH: usize = 8;
+
+/// Returns the mounted filesystems sorted by their mount points
+pub fn mounts() -> Option<Vec<MountEntry>> {
+    let mut entries = vec![MountEntry::default(); MOUNTS_BATCH];
+
+    loop {
+        let count = sys_list_mounts(&mut entries)?;
+        // retry with a buffer large enough
+        if count <= entries.len() {
+            entries.truncate(count);
+            return Some(entries);
+        }
+        entries.resize(count, MountEntry::default());
+    }
+}
+
+/// Functions fetched from the kernel by the first `ListPci`
+const PCI_BATCH: usize = 32;
+
+/// Returns the PCI functions in the order they were scanned
+pub fn pci_devices() -> Option<Vec<PciEntry>> {
+    let mut entries = vec![PciEntry::default(); PCI_BATCH];
+
+    loop {
+        let count = sys_list_pci(&mut entries)?;
+        // retry with a buffer large enough
+        if count <= entries.len() {
+            entries.truncate(count);
+            return Some(entries);
+        }
+        entries.resize(count, PciEntry::default());
+    }
+}
+
 pub fn stdin() -> Stdin {
     Stdin::new()
 }
@@ -143,21 +350,3 @@ pub fn stdout() -> Stdout {
 pub fn stderr() -> Stderr {
     Stderr::new()
 }
-
-/// The different ways we can open a file.
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-#[repr(u8)]
-pub enum FileMode {
-    /// Open a file for reading, if it exists.
-    ReadOnly = 0,
-    /// Open a file for appending (writing to the end of the existing file), if it exists.
-    ReadWriteAppend = 1,
-    /// Open a file and remove all contents, before writing to the start of the existing file, if it exists.
-    ReadWriteTruncate = 2,
-    /// Create a new empty file. Fail if it exists.
-    ReadWriteCreate = 3,
-    /// Create a new empty file, or truncate an existing file.
-    ReadWriteCreateOrTruncate = 4,
-    /// Create a new empty file, or append to an existing file.
-    ReadWriteCreateOrAppend = 5,
-}
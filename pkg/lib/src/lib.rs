@@ -26,9 +26,11 @@ pub use sync::*;
 pub use syscall::*;
 pub use utils::*;
 
-pub fn init() {
+pub fn init(args: *const ExecArgs) {
     #[cfg(feature = "brk_alloc")]
     crate::allocator::init();
+
+    crate::utils::init_args(args);
 }
 
 #[macro_export]
@@ -5,8 +5,8 @@ use crate::errln;
 macro_rules! entry {
     ($fn:ident) => {
         #[unsafe(export_name = "_start")]
-        pub extern "C" fn __impl_start() {
-            lib::init();
+        pub extern "C" fn __impl_start(args: *const lib::ExecArgs) {
+            lib::init(args);
             let ret = $fn();
             lib::sys_exit(ret);
         }
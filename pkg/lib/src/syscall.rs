@@ -1,5 +1,13 @@
 use chrono::{DateTime, Utc};
-use syscall_def::Syscall;
+use syscall_def::{DirEntry, FileStat, MountEntry, PciEntry, SeekWhence, Syscall};
+
+pub use syscall_def::{ClockId, ExecArgs};
+
+/// Returns the nanoseconds of the clock
+#[inline(always)]
+pub fn sys_clock_get_time(clock: ClockId) -> i64 {
+    syscall!(Syscall::ClockGetTime, clock as u64) as i64
+}
 
 #[inline(always)]
 pub fn sys_draw(x: i32, y: i32, color: u32) -> usize {
@@ -65,8 +73,51 @@ pub fn sys_time() -> DateTime<Utc> {
 }
 
 #[inline(always)]
-pub fn sys_list_dir(root: &str) {
-    syscall!(Syscall::ListDir, root.as_ptr() as u64, root.len() as u64);
+pub fn sys_sync() -> bool {
+    syscall!(Syscall::Sync) == 0
+}
+
+#[inline(always)]
+pub fn sys_get_dents(fd: u8, entries: &mut [DirEntry]) -> Option<usize> {
+    let ret = syscall!(
+        Syscall::GetDents,
+        fd as u64,
+        entries.as_mut_ptr() as u64,
+        entries.len() as u64
+    ) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as usize)
+    }
+}
+
+#[inline(always)]
+pub fn sys_list_mounts(entries: &mut [MountEntry]) -> Option<usize> {
+    let ret = syscall!(
+        Syscall::ListMounts,
+        entries.as_mut_ptr() as u64,
+        entries.len() as u64
+    ) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as usize)
+    }
+}
+
+#[inline(always)]
+pub fn sys_list_pci(entries: &mut [PciEntry]) -> Option<usize> {
+    let ret = syscall!(
+        Syscall::ListPci,
+        entries.as_mut_ptr() as u64,
+        entries.len() as u64
+    ) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as usize)
+    }
 }
 
 #[inline(always)]
@@ -79,6 +130,17 @@ pub fn sys_spawn(path: &str) -> u16 {
     syscall!(Syscall::Spawn, path.as_ptr() as u64, path.len() as u64) as u16
 }
 
+/// Only returns if the program cannot be loaded
+#[inline(always)]
+pub fn sys_exec(path: &str, args: &ExecArgs) -> isize {
+    syscall!(
+        Syscall::Exec,
+        path.as_ptr() as u64,
+        path.len() as u64,
+        args as *const ExecArgs
+    ) as isize
+}
+
 #[inline(always)]
 pub fn sys_open(path: &str, mode: crate::FileMode) -> u8 {
     syscall!(
@@ -94,14 +156,55 @@ pub fn sys_close(fd: u8) -> bool {
     syscall!(Syscall::Close, fd as u64) != 0
 }
 
+#[inline(always)]
+pub fn sys_seek(fd: u8, offset: isize, whence: SeekWhence) -> Option<usize> {
+    let ret = syscall!(Syscall::Seek, fd as u64, offset as u64, whence as u64) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as usize)
+    }
+}
+
+#[inline(always)]
+pub fn sys_fstat(fd: u8) -> Option<FileStat> {
+    let mut stat = FileStat::default();
+    match syscall!(Syscall::FStat, fd as u64, &mut stat as *mut FileStat) {
+        0 => Some(stat),
+        _ => None,
+    }
+}
+
+#[inline(always)]
+pub fn sys_stat_path(path: &str) -> Option<FileStat> {
+    let mut stat = FileStat::default();
+    match syscall!(
+        Syscall::StatPath,
+        path.as_ptr() as u64,
+        path.len() as u64,
+        &mut stat as *mut FileStat
+    ) {
+        0 => Some(stat),
+        _ => None,
+    }
+}
+
+/// Blocks the current process for at least `nanos` nanoseconds
+#[inline(always)]
+pub fn sys_sleep(nanos: u64) {
+    syscall!(Syscall::Sleep, nanos);
+}
+
 #[inline(always)]
 pub fn sys_get_pid() -> u16 {
     syscall!(Syscall::GetPid) as u16
 }
 
+/// Returns 0 in the child and its pid in the parent,
+/// or `u16::MAX` if the process cannot be forked
 #[inline(always)]
 pub fn sys_fork() -> u16 {
-    let pid = syscall!(Syscall::VFork);
+    let pid = syscall!(Syscall::Fork);
     pid as u16
 }
 
@@ -110,6 +213,13 @@ pub fn sys_kill(pid: u16) {
     syscall!(Syscall::Kill, pid as u64);
 }
 
+/// Sets the nice value of the process, 0 for the current one, only the
+/// current process and its children, the value is clamped to `-20..=19`
+#[inline(always)]
+pub fn sys_set_priority(pid: u16, nice: isize) -> bool {
+    syscall!(Syscall::SetPriority, pid as u64, nice as u64) == 0
+}
+
 #[inline(always)]
 pub fn sys_new_sem(key: u32, value: usize) -> bool {
     syscall!(Syscall::Sem, 0, key as u64, value) == 0
@@ -1,10 +1,89 @@
 use crate::*;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use syscall_def::StrRef;
 
+/// The arguments and environment on the stack, null if the program is spawned
+static EXEC_ARGS: AtomicPtr<ExecArgs> = AtomicPtr::new(core::ptr::null_mut());
+
+pub(crate) fn init_args(args: *const ExecArgs) {
+    EXEC_ARGS.store(args as *mut ExecArgs, Ordering::Relaxed);
+}
+
+/// Replaces the current process with the program at `path`,
+/// only returns if it cannot be loaded
+///
+/// The arguments and environment are copied to the first page of the
+/// new stack, they must fit in 2 KiB with a 16 bytes `StrRef` for each
+/// string and the 32 bytes `ExecArgs`, or -1 is returned.
+pub fn exec(path: &str, argv: &[&str], envp: &[&str]) -> isize {
+    let argv: Vec<StrRef> = argv.iter().map(|s| StrRef::new(s)).collect();
+    let envp: Vec<StrRef> = envp.iter().map(|s| StrRef::new(s)).collect();
+
+    let args = ExecArgs {
+        argv: argv.as_ptr(),
+        argc: argv.len(),
+        envp: envp.as_ptr(),
+        envc: envp.len(),
+    };
+
+    sys_exec(path, &args)
+}
+
+/// The arguments passed by `exec`, empty if the program is spawned
+pub fn args() -> Vec<&'static str> {
+    match unsafe { EXEC_ARGS.load(Ordering::Relaxed).as_ref() } {
+        Some(args) => exec_strs(args.argv, args.argc),
+        None => Vec::new(),
+    }
+}
+
+/// The environment passed by `exec` as `KEY=VALUE` strings
+pub fn env() -> Vec<&'static str> {
+    match unsafe { EXEC_ARGS.load(Ordering::Relaxed).as_ref() } {
+        Some(args) => exec_strs(args.envp, args.envc),
+        None => Vec::new(),
+    }
+}
+
+/// Looks up the value of a variable in the environment
+pub fn env_var(key: &str) -> Option<&'static str> {
+    env().into_iter().find_map(|var| {
+        var.split_once('=')
+            .filter(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    })
+}
+
+/// The strings are copied to the top of the stack by the kernel,
+/// they are never freed
+fn exec_strs(ptr: *const StrRef, len: usize) -> Vec<&'static str> {
+    if len == 0 {
+        return Vec::new();
+    }
+
+    unsafe { core::slice::from_raw_parts(ptr, len) }
+        .iter()
+        .map(|s| unsafe { s.as_str() })
+        .collect()
+}
+
+/// The time since boot, it never goes backwards
+pub fn uptime() -> Duration {
+    Duration::nanoseconds(sys_clock_get_time(ClockId::Monotonic))
+}
+
+/// Blocks the current process for the given milliseconds
 pub fn sleep(millisecs: i64) {
-    let start = sys_time();
-    let dur = Duration::try_milliseconds(millisecs).unwrap();
-    let mut current = start;
-    while current - start < dur {
-        current = sys_time();
+    sleep_for(Duration::milliseconds(millisecs));
+}
+
+/// Blocks the current process for the duration,
+/// returns at once if it is not positive
+pub fn sleep_for(duration: Duration) {
+    let nanos = duration.num_nanoseconds().unwrap_or(i64::MAX);
+
+    if nanos > 0 {
+        sys_sleep(nanos as u64);
     }
 }
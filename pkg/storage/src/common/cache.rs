@@ -1,7 +1,11 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
 use spin::RwLock;
 
 use super::*;
 
+/// Number of blocks to read at once on a sequential cache miss
+const READ_AHEAD: usize = 16;
+
 pub struct BlockCache<B>
 where
     B: BlockTrait,
@@ -39,18 +43,21 @@ impl<B: BlockTrait> BlockCache<B> {
         data.as_mut().copy_from_slice(self.inner.as_ref());
         Ok(())
     }
+
+    /// Write the block back to the device if it is modified
+    pub fn flush(&mut self) -> Result<()> {
+        if self.modified {
+            self.device.write_block(self.offset, &self.inner)?;
+            self.modified = false;
+        }
+        Ok(())
+    }
 }
 
 impl<B: BlockTrait> Drop for BlockCache<B> {
     fn drop(&mut self) {
-        // This can be implemented as kernel async task
-        if self.modified {
-            match self.device.write_block(self.offset, &self.inner) {
-                Ok(_) => {}
-                Err(e) => {
-                    log::error!("Failed to write block to device: {:?}", e);
-                }
-            }
+        if let Err(e) = self.flush() {
+            log::error!("Failed to write block to device: {:?}", e);
         }
     }
 }
@@ -64,6 +71,13 @@ where
 
     /// Put a block into the cache
     fn put(&self, key: usize, value: BlockCache<B>);
+
+    /// Returns true if putting a new block would evict a modified one
+    fn evicts_modified(&self) -> bool;
+
+    /// Write all the modified blocks back to the device,
+    /// the blocks are kept in the cache
+    fn flush_all(&self) -> Result<()>;
 }
 
 pub struct CachedDevice<B, C>
@@ -73,6 +87,24 @@ where
 {
     cache: C,
     device: Arc<dyn BlockDevice<B>>,
+    /// The block after the last read from the device,
+    /// a miss on it indicates a sequential read
+    next_miss: Arc<AtomicUsize>,
+}
+
+/// Clones share the same device, cache and read-ahead state
+impl<B, C> Clone for CachedDevice<B, C>
+where
+    B: BlockTrait,
+    C: CacheManager<B> + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            device: self.device.clone(),
+            next_miss: self.next_miss.clone(),
+        }
+    }
 }
 
 impl<B, C> CachedDevice<B, C>
@@ -84,13 +116,57 @@ where
         Self {
             device: Arc::new(device),
             cache,
+            next_miss: Arc::new(AtomicUsize::new(usize::MAX)),
         }
     }
 
+    /// Write all the modified blocks back to the device
+    pub fn sync(&self) -> Result<()> {
+        self.cache.flush_all()
+    }
+
     fn save_cache(&self, offset: usize, block: B, modified: bool) {
         let cache = BlockCache::new(offset, self.device.clone(), block, modified);
         self.cache.put(offset, cache);
     }
+
+    /// Reads the missed block from the device, and the following
+    /// blocks as well if the reads look sequential
+    fn read_missed(&self, offset: usize, block: &mut B) -> Result<()> {
+        let count = if self.next_miss.load(Ordering::Relaxed) == offset {
+            READ_AHEAD.min(self.device.block_count()?.saturating_sub(offset))
+        } else {
+            1
+        };
+
+        if count <= 1 {
+            self.device.read_block(offset, block)?;
+            self.save_cache(offset, block.clone(), false);
+            self.next_miss.store(offset + 1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let mut blocks = vec![B::default(); count];
+        self.device.read_blocks(offset, &mut blocks)?;
+        block.as_mut().copy_from_slice(blocks[0].as_ref());
+
+        for (idx, data) in blocks.into_iter().enumerate() {
+            if idx > 0 {
+                // keep the cached blocks, they may be modified
+                if self.cache.get(&(offset + idx)).is_some() {
+                    continue;
+                }
+                // stop reading ahead rather than writing back a modified block
+                if self.cache.evicts_modified() {
+                    break;
+                }
+            }
+            self.save_cache(offset + idx, data, false);
+        }
+
+        self.next_miss.store(offset + count, Ordering::Relaxed);
+        Ok(())
+    }
 }
 
 impl<B, C> BlockDevice<B> for CachedDevice<B, C>
@@ -110,14 +186,48 @@ where
             }
             None => {
                 log::trace!("Cache missed for block {}", offset);
-                self.device.read_block(offset, block)?;
-                self.save_cache(offset, block.clone(), false);
+                self.read_missed(offset, block)?;
             }
         };
 
         Ok(())
     }
 
+    /// Reads the cached blocks from the cache, and each run
+    /// of the missed blocks from the device at once
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> Result<()> {
+        let mut idx = 0;
+
+        while idx < blocks.len() {
+            if let Some(cache) = self.cache.get(&(offset + idx)) {
+                cache.read().load(&mut blocks[idx])?;
+                idx += 1;
+                continue;
+            }
+
+            let end = (idx + 1..blocks.len())
+                .find(|&next| self.cache.get(&(offset + next)).is_some())
+                .unwrap_or(blocks.len());
+
+            self.device
+                .read_blocks(offset + idx, &mut blocks[idx..end])?;
+
+            for (next, block) in (idx..end).zip(blocks[idx..end].iter()) {
+                // leave the rest uncached rather than writing back a modified block
+                if self.cache.evicts_modified() {
+                    break;
+                }
+                self.save_cache(offset + next, block.clone(), false);
+            }
+
+            idx = end;
+        }
+
+        self.next_miss
+            .store(offset + blocks.len(), Ordering::Relaxed);
+        Ok(())
+    }
+
     fn write_block(&self, offset: usize, block: &B) -> Result<()> {
         match self.cache.get(&offset) {
             Some(cache) => {
@@ -130,3 +240,209 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use spin::Mutex;
+
+    type CacheValue = Arc<RwLock<BlockCache<Block512>>>;
+
+    #[derive(Clone, Default)]
+    struct MapCache(Arc<Mutex<BTreeMap<usize, CacheValue>>>);
+
+    impl CacheManager<Block512> for MapCache {
+        fn get(&self, key: &usize) -> Option<Arc<RwLock<BlockCache<Block512>>>> {
+            self.0.lock().get(key).cloned()
+        }
+
+        fn put(&self, key: usize, value: BlockCache<Block512>) {
+            self.0.lock().insert(key, Arc::new(RwLock::new(value)));
+        }
+
+        fn evicts_modified(&self) -> bool {
+            false
+        }
+
+        fn flush_all(&self) -> Result<()> {
+            self.0
+                .lock()
+                .values()
+                .try_for_each(|block| block.write().flush())
+        }
+    }
+
+    /// A cache of a few blocks, the oldest one is evicted first
+    #[derive(Clone)]
+    struct FifoCache(Arc<Mutex<Vec<(usize, CacheValue)>>>, usize);
+
+    impl FifoCache {
+        fn new(capacity: usize) -> Self {
+            Self(Default::default(), capacity)
+        }
+    }
+
+    impl CacheManager<Block512> for FifoCache {
+        fn get(&self, key: &usize) -> Option<CacheValue> {
+            let blocks = self.0.lock();
+            blocks
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        }
+
+        fn put(&self, key: usize, value: BlockCache<Block512>) {
+            let mut blocks = self.0.lock();
+            blocks.retain(|(k, _)| *k != key);
+            if blocks.len() == self.1 {
+                blocks.remove(0);
+            }
+            blocks.push((key, Arc::new(RwLock::new(value))));
+        }
+
+        fn evicts_modified(&self) -> bool {
+            let blocks = self.0.lock();
+            blocks.len() == self.1 && blocks[0].1.read().is_modified()
+        }
+
+        fn flush_all(&self) -> Result<()> {
+            self.0
+                .lock()
+                .iter()
+                .try_for_each(|(_, block)| block.write().flush())
+        }
+    }
+
+    #[test]
+    fn test_cache_sync() {
+        let disk = MemDisk::new(16);
+        let device = CachedDevice::new(disk.clone(), MapCache::default());
+
+        let block = Block512::new(&[0xAA; 512]);
+        device.write_block(3, &block).unwrap();
+
+        // the write is only in the cache until synced
+        assert_eq!(disk.0.lock()[3].as_ref(), [0; 512]);
+        let mut buf = Block512::default();
+        device.read_block(3, &mut buf).unwrap();
+        assert_eq!(buf.as_ref(), block.as_ref());
+
+        device.sync().unwrap();
+        assert_eq!(disk.0.lock()[3].as_ref(), block.as_ref());
+        assert!(!device.cache.get(&3).unwrap().read().is_modified());
+
+        // clean blocks are not written again on drop
+        disk.0.lock()[3] = Block512::default();
+        drop(device);
+        assert_eq!(disk.0.lock()[3].as_ref(), [0; 512]);
+    }
+
+    #[test]
+    fn test_cache_read_ahead() {
+        let disk = MemDisk::new(16);
+        disk.write_block(7, &Block512::new(&[0x55; 512])).unwrap();
+
+        let device = CachedDevice::new(disk.clone(), MapCache::default());
+        device.write_block(5, &Block512::new(&[0xAA; 512])).unwrap();
+
+        // a random read only loads the block itself
+        let mut buf = Block512::default();
+        device.read_block(2, &mut buf).unwrap();
+        assert!(device.cache.get(&3).is_none());
+
+        // the sequential read loads the rest of the device
+        device.read_block(3, &mut buf).unwrap();
+        assert!((3..16).all(|idx| device.cache.get(&idx).is_some()));
+        assert!(device.cache.get(&5).unwrap().read().is_modified());
+
+        device.read_block(7, &mut buf).unwrap();
+        assert_eq!(buf.as_ref(), [0x55; 512]);
+        device.read_block(5, &mut buf).unwrap();
+        assert_eq!(buf.as_ref(), [0xAA; 512]);
+    }
+
+    #[test]
+    fn test_cache_clone() {
+        let disk = MemDisk::new(16);
+        let device = CachedDevice::new(disk.clone(), MapCache::default());
+        let other = device.clone();
+
+        // the clones see the writes of each other before syncing
+        let block = Block512::new(&[0xAA; 512]);
+        other.write_block(9, &block).unwrap();
+        assert_eq!(disk.0.lock()[9].as_ref(), [0; 512]);
+
+        let mut buf = Block512::default();
+        device.read_block(9, &mut buf).unwrap();
+        assert_eq!(buf.as_ref(), block.as_ref());
+
+        device.sync().unwrap();
+        assert_eq!(disk.0.lock()[9].as_ref(), block.as_ref());
+    }
+
+    #[test]
+    fn test_read_ahead_keeps_modified() {
+        let disk = MemDisk::new(16);
+        let device = CachedDevice::new(disk.clone(), FifoCache::new(4));
+
+        device.write_block(0, &Block512::new(&[0xAA; 512])).unwrap();
+
+        let mut buf = Block512::default();
+        device.read_block(2, &mut buf).unwrap();
+        // the read ahead stops before evicting block 0
+        device.read_block(3, &mut buf).unwrap();
+
+        assert!(device.cache.get(&0).unwrap().read().is_modified());
+        assert!(device.cache.get(&4).is_some());
+        assert!(device.cache.get(&5).is_none());
+        assert_eq!(disk.0.lock()[0].as_ref(), [0; 512]);
+    }
+
+    #[test]
+    fn test_read_blocks_keeps_modified() {
+        let disk = MemDisk::new(16);
+        for idx in 0..16 {
+            disk.write_block(idx, &Block512::new(&[idx as u8; 512]))
+                .unwrap();
+        }
+
+        let device = CachedDevice::new(disk.clone(), FifoCache::new(4));
+        device.write_block(0, &Block512::new(&[0xAA; 512])).unwrap();
+
+        // the blocks are all read, only the first ones are cached
+        let mut blocks = vec![Block512::default(); 8];
+        device.read_blocks(1, &mut blocks).unwrap();
+
+        for (idx, block) in blocks.iter().enumerate() {
+            assert_eq!(block.as_ref(), [idx as u8 + 1; 512]);
+        }
+
+        assert!(device.cache.get(&0).unwrap().read().is_modified());
+        assert!(device.cache.get(&3).is_some());
+        assert!(device.cache.get(&4).is_none());
+        assert_eq!(disk.0.lock()[0].as_ref(), [0; 512]);
+    }
+
+    #[test]
+    fn test_cache_read_blocks() {
+        let disk = MemDisk::new(16);
+        for idx in 0..8 {
+            disk.write_block(idx, &Block512::new(&[idx as u8; 512]))
+                .unwrap();
+        }
+
+        let device = CachedDevice::new(disk.clone(), MapCache::default());
+        device.write_block(2, &Block512::new(&[0xAA; 512])).unwrap();
+
+        // the cached block is read from the cache, the rest from the disk
+        let mut blocks = vec![Block512::default(); 6];
+        device.read_blocks(0, &mut blocks).unwrap();
+
+        for (idx, block) in blocks.iter().enumerate() {
+            let expected = if idx == 2 { 0xAA } else { idx as u8 };
+            assert_eq!(block.as_ref(), [expected; 512]);
+            assert!(device.cache.get(&idx).is_some());
+        }
+    }
+}
@@ -21,6 +21,24 @@ where
     /// Writes a block to the device from the provided buffer
     fn write_block(&self, offset: usize, block: &B) -> Result<()>;
 
+    /// Reads consecutive blocks starting at `offset` into the provided buffers
+    ///
+    /// Devices that can transfer several blocks at once should override this
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> Result<()> {
+        blocks
+            .iter_mut()
+            .enumerate()
+            .try_for_each(|(idx, block)| self.read_block(offset + idx, block))
+    }
+
+    /// Writes consecutive blocks starting at `offset` from the provided buffers
+    fn write_blocks(&self, offset: usize, blocks: &[B]) -> Result<()> {
+        blocks
+            .iter()
+            .enumerate()
+            .try_for_each(|(idx, block)| self.write_block(offset + idx, block))
+    }
+
     /// Returns the block size of the device
     fn block_size(&self) -> usize {
         B::size()
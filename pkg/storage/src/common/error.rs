@@ -12,10 +12,16 @@ pub enum FsError {
     EndOfFile,
     /// Writing to a file with no space left.
     WriteZero,
+    /// There are no free clusters left on the volume.
+    DiskFull,
     /// The entry is not a directory.
     NotADirectory,
     /// The entry is not a file.
     NotAFile,
+    /// The entry already exists.
+    AlreadyExists,
+    /// The directory is not empty.
+    DirectoryNotEmpty,
     /// The file is read-only.
     ReadOnly,
     /// Invalid operation.
@@ -48,6 +54,8 @@ pub enum DeviceError {
     ReadError,
     /// Write error.
     WriteError,
+    /// The device did not respond in time.
+    Timeout,
     /// The device error status code.
     WithStatus(usize),
 }
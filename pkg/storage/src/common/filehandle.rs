@@ -2,14 +2,49 @@ use super::*;
 use core::fmt::Debug;
 use core::ops::{Deref, DerefMut};
 
+/// An opened file, the length in `meta` follows the writes through the handle
 pub struct FileHandle {
     pub meta: Metadata,
+    /// The offset in the file, tracked to extend the length
+    offset: usize,
     file: Box<dyn FileIO + Send>,
 }
 
 impl FileHandle {
     pub fn new(meta: Metadata, file: Box<dyn FileIO + Send>) -> Self {
-        Self { meta, file }
+        Self {
+            meta,
+            offset: 0,
+            file,
+        }
+    }
+}
+
+impl Read for FileHandle {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let len = self.file.read(buf)?;
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+impl Write for FileHandle {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let len = self.file.write(buf)?;
+        self.offset += len;
+        self.meta.len = self.meta.len.max(self.offset);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for FileHandle {
+    fn seek(&mut self, pos: SeekFrom) -> Result<usize> {
+        self.offset = self.file.seek(pos)?;
+        Ok(self.offset)
     }
 }
 
@@ -34,3 +69,30 @@ impl Debug for FileHandle {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::ramfs::RamFs;
+
+    #[test]
+    fn test_handle_length() {
+        let fs = RamFs::new();
+
+        let mut file = fs.create_file("/len.txt").unwrap();
+        file.write_all(b"hello world").unwrap();
+        assert_eq!(file.meta.len, 11);
+
+        // overwriting keeps the length
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(b"HELLO").unwrap();
+        assert_eq!(file.meta.len, 11);
+
+        file.seek(SeekFrom::End(4)).unwrap();
+        file.write_all(b"!").unwrap();
+        assert_eq!(file.meta.len, 16);
+
+        let file = fs.append_file("/len.txt").unwrap();
+        assert_eq!(file.meta.len, 16);
+    }
+}
@@ -18,7 +18,7 @@ pub trait FileSystem: Debug + Sync + Send {
     fn exists(&self, path: &str) -> Result<bool>;
 
     // ----------------------------------------------------
-    // NOTE: following functions are not implemented (optional)
+    // NOTE: following functions are optional
     // ----------------------------------------------------
 
     /// Creates a file at this path for writing
@@ -26,18 +26,28 @@ pub trait FileSystem: Debug + Sync + Send {
         Err(FsError::NotSupported)
     }
 
+    /// Creates an empty directory at this path
+    fn create_dir(&self, _path: &str) -> Result<()> {
+        Err(FsError::NotSupported)
+    }
+
     /// Opens the file at this path for appending
     fn append_file(&self, _path: &str) -> Result<FileHandle> {
         Err(FsError::NotSupported)
     }
 
+    /// Shrinks or extends with zeros the file at this path to `len` bytes
+    fn set_len(&self, _path: &str, _len: usize) -> Result<()> {
+        Err(FsError::NotSupported)
+    }
+
     /// Removes the file at this path
-    fn remove_file(&self, _path: &str) -> Result<FileHandle> {
+    fn remove_file(&self, _path: &str) -> Result<()> {
         Err(FsError::NotSupported)
     }
 
-    /// Removes the directory at this path
-    fn remove_dir(&self, _path: &str) -> Result<FileHandle> {
+    /// Removes the empty directory at this path
+    fn remove_dir(&self, _path: &str) -> Result<()> {
         Err(FsError::NotSupported)
     }
 
@@ -55,4 +65,9 @@ pub trait FileSystem: Debug + Sync + Send {
     fn move_dir(&self, _src: &str, _dst: &str) -> Result<()> {
         Err(FsError::NotSupported)
     }
+
+    /// Returns the name of the filesystem type
+    fn fs_type(&self) -> &'static str {
+        "unknown"
+    }
 }
@@ -53,18 +53,28 @@ macro_rules! define_field {
         }
     };
 
+    (u64, $offset:expr, $name:ident) => {
+        paste::item! {
+            #[doc = "Get u64 from the " $name " field"]
+            pub fn $name(&self) -> u64 {
+                u64::from_le_bytes(self.data[$offset..$offset + 8].try_into().unwrap_or([0; 8]))
+            }
+        }
+    };
+
     ([u8; $len:expr], $offset:expr, $name:ident) => {
         paste::item! {
             #[doc = "Get `&[u8]` from the " $name " field"]
             pub fn $name(&self) -> &[u8; $len] {
-                (&self.data[$offset..$offset + $len])
+                // sliced twice to not add a zero offset
+                (&self.data[$offset..][..$len])
                     .try_into()
                     .unwrap_or(&[0; $len])
             }
 
             #[doc = "Get `&str` from the " $name " field"]
             pub fn [<$name _str>](&self) -> &str {
-                core::str::from_utf8(&self.data[$offset..$offset+$len]).unwrap_or("")
+                core::str::from_utf8(&self.data[$offset..][..$len]).unwrap_or("")
             }
         }
     };
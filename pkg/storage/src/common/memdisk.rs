@@ -0,0 +1,51 @@
+//! A disk in memory for the tests
+
+use super::*;
+use spin::Mutex;
+
+/// A disk in memory, the clones share the same blocks
+#[derive(Clone)]
+pub struct MemDisk(pub Arc<Mutex<Vec<Block512>>>);
+
+impl MemDisk {
+    /// A zeroed disk of `count` blocks
+    pub fn new(count: usize) -> Self {
+        Self::from_blocks(vec![Block512::default(); count])
+    }
+
+    pub fn from_blocks(blocks: Vec<Block512>) -> Self {
+        Self(Arc::new(Mutex::new(blocks)))
+    }
+
+    /// A disk with the image, the last block is padded with zeros
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let blocks = data
+            .chunks(Block512::size())
+            .map(|chunk| {
+                let mut block = Block512::default();
+                block.as_mut()[..chunk.len()].copy_from_slice(chunk);
+                block
+            })
+            .collect();
+
+        Self::from_blocks(blocks)
+    }
+}
+
+impl BlockDevice<Block512> for MemDisk {
+    fn block_count(&self) -> Result<usize> {
+        Ok(self.0.lock().len())
+    }
+
+    fn read_block(&self, offset: usize, block: &mut Block512) -> Result<()> {
+        let blocks = self.0.lock();
+        *block = blocks.get(offset).ok_or(FsError::InvalidOffset)?.clone();
+        Ok(())
+    }
+
+    fn write_block(&self, offset: usize, block: &Block512) -> Result<()> {
+        let mut blocks = self.0.lock();
+        *blocks.get_mut(offset).ok_or(FsError::InvalidOffset)? = block.clone();
+        Ok(())
+    }
+}
@@ -3,6 +3,19 @@ use chrono::{DateTime, Utc};
 
 pub type FsTime = DateTime<Utc>;
 
+static CLOCK: spin::Once<fn() -> FsTime> = spin::Once::new();
+
+/// Register the clock used to timestamp filesystem entries
+pub fn set_clock(clock: fn() -> FsTime) {
+    CLOCK.call_once(|| clock);
+}
+
+/// Returns the current time from the registered clock,
+/// or the unix epoch if no clock has been registered
+pub fn current_time() -> FsTime {
+    CLOCK.get().map(|clock| clock()).unwrap_or_default()
+}
+
 /// Type of file entry
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum FileType {
@@ -8,8 +8,11 @@ mod error;
 mod filehandle;
 mod filesystem;
 mod io;
+#[cfg(test)]
+mod memdisk;
 mod metadata;
 mod mount;
+mod vfs;
 
 use super::*;
 
@@ -20,7 +23,10 @@ pub use error::*;
 pub use filehandle::*;
 pub use filesystem::*;
 pub use io::*;
+#[cfg(test)]
+pub use memdisk::*;
 pub use metadata::*;
 pub use mount::*;
+pub use vfs::*;
 
 pub const PATH_SEPARATOR: char = '/';
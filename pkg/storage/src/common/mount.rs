@@ -11,9 +11,26 @@ impl Mount {
         Self { fs, mount_point }
     }
 
+    /// Returns true if the path is the mount point or inside of it
+    pub fn contains(&self, path: &str) -> bool {
+        match path.strip_prefix(self.mount_point.as_ref()) {
+            Some(rest) => {
+                rest.is_empty()
+                    || rest.starts_with(PATH_SEPARATOR)
+                    || self.mount_point.ends_with(PATH_SEPARATOR)
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the path relative to the root of the mounted filesystem
     #[inline]
     fn trim_mount_point<'a>(&self, path: &'a str) -> &'a str {
-        path.trim_start_matches(self.mount_point.as_ref())
+        match path.strip_prefix(self.mount_point.trim_end_matches(PATH_SEPARATOR)) {
+            Some("") => "/",
+            Some(rest) => rest,
+            None => path,
+        }
     }
 }
 
@@ -37,6 +54,59 @@ impl FileSystem for Mount {
     fn exists(&self, path: &str) -> Result<bool> {
         self.fs.exists(self.trim_mount_point(path))
     }
+
+    #[inline]
+    fn create_file(&self, path: &str) -> Result<FileHandle> {
+        self.fs.create_file(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn create_dir(&self, path: &str) -> Result<()> {
+        self.fs.create_dir(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn append_file(&self, path: &str) -> Result<FileHandle> {
+        self.fs.append_file(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn set_len(&self, path: &str, len: usize) -> Result<()> {
+        self.fs.set_len(self.trim_mount_point(path), len)
+    }
+
+    #[inline]
+    fn remove_file(&self, path: &str) -> Result<()> {
+        self.fs.remove_file(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn remove_dir(&self, path: &str) -> Result<()> {
+        self.fs.remove_dir(self.trim_mount_point(path))
+    }
+
+    #[inline]
+    fn copy_file(&self, src: &str, dst: &str) -> Result<()> {
+        self.fs
+            .copy_file(self.trim_mount_point(src), self.trim_mount_point(dst))
+    }
+
+    #[inline]
+    fn move_file(&self, src: &str, dst: &str) -> Result<()> {
+        self.fs
+            .move_file(self.trim_mount_point(src), self.trim_mount_point(dst))
+    }
+
+    #[inline]
+    fn move_dir(&self, src: &str, dst: &str) -> Result<()> {
+        self.fs
+            .move_dir(self.trim_mount_point(src), self.trim_mount_point(dst))
+    }
+
+    #[inline]
+    fn fs_type(&self) -> &'static str {
+        self.fs.fs_type()
+    }
 }
 
 impl core::fmt::Debug for Mount {
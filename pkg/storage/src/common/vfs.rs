@@ -0,0 +1,313 @@
+//! The virtual filesystem that dispatches paths to the mounted filesystems
+use super::*;
+use spin::RwLock;
+
+/// A mount table, every path is resolved to the filesystem
+/// with the longest matching mount point.
+#[derive(Default)]
+pub struct Vfs {
+    /// Sorted by the length of the mount point, the longest first
+    mounts: RwLock<Vec<Arc<Mount>>>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mounts the filesystem at the given path
+    pub fn mount(&self, fs: Box<dyn FileSystem>, mount_point: &str) -> Result<()> {
+        let mount_point = normalize(mount_point);
+
+        if !mount_point.starts_with(PATH_SEPARATOR) {
+            return Err(FsError::InvalidPath(mount_point.into()));
+        }
+
+        let mut mounts = self.mounts.write();
+
+        if mounts.iter().any(|m| m.mount_point.as_ref() == mount_point) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let mount = Arc::new(Mount::new(fs, mount_point.into()));
+        let idx = mounts.partition_point(|m| m.mount_point.len() >= mount_point.len());
+        mounts.insert(idx, mount);
+
+        trace!("Mounted filesystem at {}", mount_point);
+
+        Ok(())
+    }
+
+    /// Unmounts the filesystem at the given path
+    pub fn umount(&self, mount_point: &str) -> Result<()> {
+        let mount_point = normalize(mount_point);
+        let mut mounts = self.mounts.write();
+
+        let idx = mounts
+            .iter()
+            .position(|m| m.mount_point.as_ref() == mount_point)
+            .ok_or(FsError::FileNotFound)?;
+
+        mounts.remove(idx);
+
+        Ok(())
+    }
+
+    /// Returns all mounts, the longest mount point first
+    pub fn mounts(&self) -> Vec<Arc<Mount>> {
+        self.mounts.read().clone()
+    }
+
+    /// Finds the mount that contains the path
+    pub fn resolve(&self, path: &str) -> Result<Arc<Mount>> {
+        self.mounts
+            .read()
+            .iter()
+            .find(|m| m.contains(path))
+            .cloned()
+            .ok_or(FsError::FileNotFound)
+    }
+
+    /// Returns true if a filesystem is mounted at the path
+    pub fn is_mount_point(&self, path: &str) -> bool {
+        let path = normalize(path);
+        self.mounts
+            .read()
+            .iter()
+            .any(|m| m.mount_point.as_ref() == path)
+    }
+
+    /// Returns the names of the directories in `dir` that are mount points
+    /// or lead to one, e.g. `mnt` in `/` for the mount point `/mnt/hda1`
+    fn child_mount_points(&self, dir: &str) -> Vec<String> {
+        let dir = normalize(dir).trim_end_matches(PATH_SEPARATOR);
+        let mut names: Vec<String> = Vec::new();
+
+        for mount in self.mounts.read().iter() {
+            let name = mount
+                .mount_point
+                .strip_prefix(dir)
+                .and_then(|rest| rest.strip_prefix(PATH_SEPARATOR))
+                .and_then(|rest| rest.split(PATH_SEPARATOR).next());
+
+            if let Some(name) = name
+                && !name.is_empty()
+                && !names.iter().any(|n| n == name)
+            {
+                names.push(name.into());
+            }
+        }
+
+        names
+    }
+
+    /// Returns true if the path is a mount point or leads to one
+    fn is_mount_dir(&self, path: &str) -> bool {
+        self.is_mount_point(path) || !self.child_mount_points(path).is_empty()
+    }
+}
+
+/// Removes the trailing separators of the path, except for the root
+fn normalize(path: &str) -> &str {
+    match path.trim_end_matches(PATH_SEPARATOR) {
+        "" if path.starts_with(PATH_SEPARATOR) => "/",
+        path => path,
+    }
+}
+
+/// Metadata of a mount point that is not provided by its filesystem
+fn mount_point_meta(path: &str) -> Metadata {
+    let name = normalize(path).rsplit(PATH_SEPARATOR).next().unwrap_or("");
+    Metadata::new(name.into(), FileType::Directory, 0, None, None, None)
+}
+
+impl FileSystem for Vfs {
+    fn read_dir(&self, path: &str) -> Result<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let path = normalize(path);
+        let mut entries: Vec<_> = match self.resolve(path)?.read_dir(path) {
+            Ok(entries) => entries.collect(),
+            // the directories leading to a mount point may not exist
+            Err(_) if self.is_mount_dir(path) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+
+        // the mount points show up as directories in their parent
+        for name in self.child_mount_points(path) {
+            if !entries.iter().any(|e| e.name == name) {
+                entries.push(mount_point_meta(&name));
+            }
+        }
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> Result<FileHandle> {
+        self.resolve(path)?.open_file(path)
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata> {
+        match self.resolve(path)?.metadata(path) {
+            Err(_) if self.is_mount_dir(path) => Ok(mount_point_meta(path)),
+            ret => ret,
+        }
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        if self.is_mount_dir(path) {
+            return Ok(true);
+        }
+
+        self.resolve(path)?.exists(path)
+    }
+
+    fn create_file(&self, path: &str) -> Result<FileHandle> {
+        self.resolve(path)?.create_file(path)
+    }
+
+    fn create_dir(&self, path: &str) -> Result<()> {
+        self.resolve(path)?.create_dir(path)
+    }
+
+    fn append_file(&self, path: &str) -> Result<FileHandle> {
+        self.resolve(path)?.append_file(path)
+    }
+
+    fn set_len(&self, path: &str, len: usize) -> Result<()> {
+        self.resolve(path)?.set_len(path, len)
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        self.resolve(path)?.remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<()> {
+        if self.is_mount_point(path) {
+            return Err(FsError::InvalidOperation);
+        }
+
+        self.resolve(path)?.remove_dir(path)
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> Result<()> {
+        let src_mount = self.resolve(src)?;
+        let dst_mount = self.resolve(dst)?;
+
+        if Arc::ptr_eq(&src_mount, &dst_mount) {
+            return src_mount.copy_file(src, dst);
+        }
+
+        // copy across filesystems
+        let mut src = src_mount.open_file(src)?;
+        let mut dst = dst_mount.create_file(dst)?;
+        let mut buf = vec![0u8; 4096];
+
+        loop {
+            match src.read(&mut buf)? {
+                0 => break,
+                n => dst.write_all(&buf[..n])?,
+            }
+        }
+
+        dst.flush()
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> Result<()> {
+        let src_mount = self.resolve(src)?;
+        let dst_mount = self.resolve(dst)?;
+
+        if Arc::ptr_eq(&src_mount, &dst_mount) {
+            return src_mount.move_file(src, dst);
+        }
+
+        self.copy_file(src, dst)?;
+        src_mount.remove_file(src)
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> Result<()> {
+        if self.is_mount_point(src) {
+            return Err(FsError::InvalidOperation);
+        }
+
+        let src_mount = self.resolve(src)?;
+        let dst_mount = self.resolve(dst)?;
+
+        if !Arc::ptr_eq(&src_mount, &dst_mount) {
+            // moving directories across filesystems is not supported
+            return Err(FsError::NotSupported);
+        }
+
+        src_mount.move_dir(src, dst)
+    }
+
+    fn fs_type(&self) -> &'static str {
+        "vfs"
+    }
+}
+
+impl core::fmt::Debug for Vfs {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Vfs")
+            .field("mounts", &*self.mounts.read())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A filesystem that only knows its own name
+    #[derive(Debug)]
+    struct NamedFs(&'static str);
+
+    impl FileSystem for NamedFs {
+        fn read_dir(&self, _path: &str) -> Result<Box<dyn Iterator<Item = Metadata> + Send>> {
+            Ok(Box::new(core::iter::empty()))
+        }
+
+        fn open_file(&self, _path: &str) -> Result<FileHandle> {
+            Err(FsError::NotSupported)
+        }
+
+        fn metadata(&self, path: &str) -> Result<Metadata> {
+            let name = format!("{}:{}", self.0, path);
+            Ok(Metadata::new(name, FileType::File, 0, None, None, None))
+        }
+
+        fn exists(&self, _path: &str) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn test_vfs_resolve() {
+        let vfs = Vfs::new();
+
+        vfs.mount(Box::new(NamedFs("root")), "/").unwrap();
+        vfs.mount(Box::new(NamedFs("dev")), "/dev/").unwrap();
+        vfs.mount(Box::new(NamedFs("disk")), "/mnt/hda1").unwrap();
+
+        assert_eq!(
+            vfs.mount(Box::new(NamedFs("dev")), "/dev"),
+            Err(FsError::AlreadyExists)
+        );
+
+        let name = |path| vfs.metadata(path).unwrap().name;
+
+        assert_eq!(name("/APP/SH"), "root:/APP/SH");
+        assert_eq!(name("/dev/null"), "dev:/null");
+        assert_eq!(name("/dev"), "dev:/");
+        assert_eq!(name("/devices"), "root:/devices");
+        assert_eq!(name("/mnt/hda1/a/b"), "disk:/a/b");
+        assert_eq!(name("/mnt/hda2"), "root:/mnt/hda2");
+
+        let names: Vec<_> = vfs.read_dir("/").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, vec!["mnt", "dev"]);
+
+        let names: Vec<_> = vfs.read_dir("/mnt").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, vec!["hda1"]);
+
+        vfs.umount("/dev").unwrap();
+        assert_eq!(name("/dev/null"), "root:/dev/null");
+    }
+}
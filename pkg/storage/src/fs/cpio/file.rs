@@ -0,0 +1,60 @@
+//! File of the CpioFs, the data is borrowed from the archive
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub struct File {
+    /// The current offset in the file.
+    pub offset: usize,
+    data: &'static [u8],
+}
+
+impl File {
+    pub fn new(data: &'static [u8]) -> Self {
+        Self { offset: 0, data }
+    }
+
+    pub fn length(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let remaining = self.data.get(self.offset..).unwrap_or_default();
+        let to_read = buf.len().min(remaining.len());
+
+        buf[..to_read].copy_from_slice(&remaining[..to_read]);
+        self.offset += to_read;
+
+        Ok(to_read)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, _buf: &[u8]) -> Result<usize> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> Result<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::End(offset) => self.length() as isize + offset,
+            SeekFrom::Current(offset) => self.offset as isize + offset,
+        };
+
+        // reading beyond the end returns nothing
+        if offset < 0 {
+            return Err(FsError::InvalidOffset);
+        }
+
+        self.offset = offset as usize;
+        Ok(self.offset)
+    }
+}
@@ -0,0 +1,313 @@
+//! Cpio Archive Filesystem
+//!
+//! A read-only filesystem over an archive in the "newc" format,
+//! which is what initramfs images are usually packed with.
+//!
+//! reference:
+//! - <https://www.kernel.org/doc/html/latest/driver-api/early-userspace/buffer-format.html>
+//! - <https://man.archlinux.org/man/cpio.5>
+
+mod file;
+
+use crate::*;
+use alloc::collections::BTreeMap;
+use chrono::DateTime;
+use file::File;
+
+/// Header of an archive member, every field is 8 hex digits
+/// except the 6 bytes magic at the beginning.
+struct CpioHeader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> CpioHeader<'a> {
+    const LEN: usize = 110;
+    const MAGIC: &'static [u8] = b"070701";
+    /// The same format with a checksum of the data, which is ignored
+    const MAGIC_CRC: &'static [u8] = b"070702";
+    const TRAILER: &'static str = "TRAILER!!!";
+
+    const S_IFMT: u32 = 0o170000;
+    const S_IFDIR: u32 = 0o040000;
+    const S_IFREG: u32 = 0o100000;
+
+    fn parse(data: &'a [u8]) -> Result<Self> {
+        if data.len() < Self::LEN {
+            return Err(FsError::EndOfFile);
+        }
+
+        if !matches!(&data[..6], Self::MAGIC | Self::MAGIC_CRC) {
+            return Err(FsError::InvalidOperation);
+        }
+
+        Ok(Self {
+            data: &data[..Self::LEN],
+        })
+    }
+
+    /// Parses the field at the given index, 0 is the inode number
+    fn field(&self, idx: usize) -> Result<u32> {
+        let offset = 6 + idx * 8;
+
+        core::str::from_utf8(&self.data[offset..offset + 8])
+            .ok()
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or(FsError::InvalidOperation)
+    }
+
+    fn mode(&self) -> Result<u32> {
+        self.field(1)
+    }
+
+    fn mtime(&self) -> Result<u32> {
+        self.field(5)
+    }
+
+    fn file_size(&self) -> Result<usize> {
+        self.field(6).map(|size| size as usize)
+    }
+
+    fn name_size(&self) -> Result<usize> {
+        self.field(11).map(|size| size as usize)
+    }
+}
+
+/// Both the name and the data are padded to a multiple of 4 bytes
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    File(&'static [u8]),
+    Directory,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    node: Node,
+    mtime: Option<FsTime>,
+}
+
+impl Entry {
+    fn as_meta(&self, name: &str) -> Metadata {
+        let (entry_type, len) = match self.node {
+            Node::File(data) => (FileType::File, data.len()),
+            Node::Directory => (FileType::Directory, 0),
+        };
+
+        Metadata::new(name.into(), entry_type, len, None, self.mtime, None)
+    }
+}
+
+/// Removes the leading `./` and separators, the root becomes an empty string
+fn normalize(path: &str) -> &str {
+    let path = path.strip_prefix("./").unwrap_or(path);
+
+    match path.trim_matches(PATH_SEPARATOR) {
+        "." => "",
+        path => path,
+    }
+}
+
+pub struct CpioFs {
+    /// Entries indexed by their normalized path
+    entries: BTreeMap<String, Entry>,
+}
+
+impl CpioFs {
+    pub fn new(archive: &'static [u8]) -> Result<Self> {
+        let mut entries = BTreeMap::new();
+        let mut offset = 0;
+
+        loop {
+            let header = CpioHeader::parse(archive.get(offset..).unwrap_or_default())?;
+
+            let name_start = offset + CpioHeader::LEN;
+            let name_end = name_start + header.name_size()?;
+            let data_start = align4(name_end);
+            let data_end = data_start + header.file_size()?;
+
+            if data_end > archive.len() {
+                return Err(FsError::EndOfFile);
+            }
+
+            // the name is terminated by a NUL byte
+            let name = core::str::from_utf8(&archive[name_start..name_end])
+                .map_err(|_| FilenameError::Utf8Error)?
+                .trim_end_matches('\0');
+
+            if name == CpioHeader::TRAILER {
+                break;
+            }
+
+            let node = match header.mode()? & CpioHeader::S_IFMT {
+                CpioHeader::S_IFDIR => Some(Node::Directory),
+                CpioHeader::S_IFREG => Some(Node::File(&archive[data_start..data_end])),
+                mode => {
+                    warn!("Skip unsupported cpio entry {} ({:#o})", name, mode);
+                    None
+                }
+            };
+
+            let path = normalize(name);
+
+            if let Some(node) = node
+                && !path.is_empty()
+            {
+                let mtime = DateTime::from_timestamp(header.mtime()? as i64, 0);
+                Self::add_parents(&mut entries, path);
+                entries.insert(path.into(), Entry { node, mtime });
+            }
+
+            offset = align4(data_end);
+        }
+
+        trace!("Loaded {} entries from cpio archive", entries.len());
+
+        Ok(Self { entries })
+    }
+
+    /// Archives may omit the parent directories, add them if missing
+    fn add_parents(entries: &mut BTreeMap<String, Entry>, path: &str) {
+        let mut parent = path;
+
+        while let Some((dir, _)) = parent.rsplit_once(PATH_SEPARATOR) {
+            entries.entry(dir.into()).or_insert(Entry {
+                node: Node::Directory,
+                mtime: None,
+            });
+            parent = dir;
+        }
+    }
+
+    fn get(&self, path: &str) -> Result<(&str, &Entry)> {
+        self.entries
+            .get_key_value(normalize(path))
+            .map(|(path, entry)| (path.rsplit(PATH_SEPARATOR).next().unwrap_or(path), entry))
+            .ok_or(FsError::FileNotFound)
+    }
+}
+
+impl FileSystem for CpioFs {
+    fn read_dir(&self, path: &str) -> Result<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let dir = normalize(path);
+
+        if !dir.is_empty() && !matches!(self.get(dir)?.1.node, Node::Directory) {
+            return Err(FsError::NotADirectory);
+        }
+
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .filter_map(|(path, entry)| {
+                let (parent, name) = path.rsplit_once(PATH_SEPARATOR).unwrap_or(("", path));
+                (parent == dir).then(|| entry.as_meta(name))
+            })
+            .collect();
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> Result<FileHandle> {
+        let (name, entry) = self.get(path)?;
+
+        match entry.node {
+            Node::File(data) => Ok(FileHandle::new(
+                entry.as_meta(name),
+                Box::new(File::new(data)),
+            )),
+            Node::Directory => Err(FsError::NotAFile),
+        }
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata> {
+        if normalize(path).is_empty() {
+            return Ok(Metadata::new(
+                String::new(),
+                FileType::Directory,
+                0,
+                None,
+                None,
+                None,
+            ));
+        }
+
+        let (name, entry) = self.get(path)?;
+        Ok(entry.as_meta(name))
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.metadata(path).is_ok())
+    }
+
+    fn fs_type(&self) -> &'static str {
+        "cpio"
+    }
+}
+
+impl core::fmt::Debug for CpioFs {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CpioFs")
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Appends a member in the newc format to the archive
+    fn push_entry(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+        let name_size = name.len() as u32 + 1;
+        let fields = [0, mode, 0, 0, 1, 1_700_000_000, data.len() as u32];
+        let fields = fields.into_iter().chain([0, 0, 0, 0, name_size, 0]);
+
+        archive.extend_from_slice(CpioHeader::MAGIC);
+        for field in fields {
+            archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+        }
+
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(align4(archive.len()), 0);
+        archive.extend_from_slice(data);
+        archive.resize(align4(archive.len()), 0);
+    }
+
+    #[test]
+    fn test_cpio() {
+        let mut archive = Vec::new();
+        push_entry(&mut archive, ".", 0o040755, &[]);
+        push_entry(&mut archive, "APP", 0o040755, &[]);
+        push_entry(&mut archive, "APP/SH", 0o100755, b"\x7fELF");
+        push_entry(&mut archive, "etc/motd", 0o100644, b"Hello, GGOS!\n");
+        push_entry(&mut archive, "APP/link", 0o120777, b"SH");
+        push_entry(&mut archive, CpioHeader::TRAILER, 0, &[]);
+
+        let fs = CpioFs::new(archive.leak()).unwrap();
+
+        let names: Vec<_> = fs.read_dir("/").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, vec!["APP", "etc"]);
+
+        let names: Vec<_> = fs.read_dir("/APP/").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, vec!["SH"]);
+
+        let meta = fs.metadata("/etc/motd").unwrap();
+        assert_eq!(meta.len, 13);
+        assert!(meta.modified.is_some());
+        assert!(fs.metadata("/etc").unwrap().is_dir());
+
+        let mut buf = Vec::new();
+        let mut file = fs.open_file("/APP/SH").unwrap();
+        file.read_all(&mut buf).unwrap();
+        assert_eq!(buf, b"\x7fELF");
+        assert_eq!(file.write(b"foo"), Err(FsError::ReadOnly));
+
+        assert_eq!(fs.open_file("/APP").err(), Some(FsError::NotAFile));
+        assert_eq!(fs.read_dir("/etc/motd").err(), Some(FsError::NotADirectory));
+        assert!(!fs.exists("/APP/link").unwrap());
+        assert_eq!(fs.create_file("/tmp").err(), Some(FsError::NotSupported));
+    }
+}
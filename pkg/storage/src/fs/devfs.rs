@@ -0,0 +1,329 @@
+//! Device Filesystem
+//!
+//! Exposes devices as files in a flat directory, usually mounted at `/dev`.
+//! `null`, `zero` and `random` are always available, other devices are
+//! registered by the kernel along with a function that opens them.
+
+use crate::random::Random;
+use crate::*;
+use alloc::collections::BTreeMap;
+use spin::RwLock;
+
+/// Opens a new instance of the device
+pub type DeviceOpener = Box<dyn Fn() -> Result<Box<dyn FileIO + Send>> + Send + Sync>;
+
+struct DeviceNode {
+    /// Size of the device in bytes, 0 for character devices
+    len: usize,
+    open: DeviceOpener,
+}
+
+pub struct DevFs {
+    devices: RwLock<BTreeMap<String, DeviceNode>>,
+}
+
+impl DevFs {
+    pub fn new() -> Self {
+        let devfs = Self {
+            devices: RwLock::new(BTreeMap::new()),
+        };
+
+        devfs.register("null", 0, || Ok(Box::new(Null)));
+        devfs.register("zero", 0, || Ok(Box::new(Zero)));
+        devfs.register("random", 0, || Ok(Box::new(Random::new())));
+
+        devfs
+    }
+
+    /// Registers a device, replacing the device with the same name
+    pub fn register<F>(&self, name: &str, len: usize, open: F)
+    where
+        F: Fn() -> Result<Box<dyn FileIO + Send>> + Send + Sync + 'static,
+    {
+        trace!("Registering device: {}", name);
+
+        let node = DeviceNode {
+            len,
+            open: Box::new(open),
+        };
+
+        self.devices.write().insert(name.into(), node);
+    }
+
+    /// Removes the device from the filesystem
+    pub fn unregister(&self, name: &str) -> Result<()> {
+        match self.devices.write().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(FsError::FileNotFound),
+        }
+    }
+
+    /// Returns the device name of the path, devfs has no subdirectories
+    fn device_name(path: &str) -> Result<&str> {
+        let name = path.trim_matches(PATH_SEPARATOR);
+
+        if name.contains(PATH_SEPARATOR) {
+            return Err(FsError::FileNotFound);
+        }
+
+        Ok(name)
+    }
+
+    fn device_meta(name: &str, node: &DeviceNode) -> Metadata {
+        Metadata::new(name.into(), FileType::File, node.len, None, None, None)
+    }
+}
+
+impl Default for DevFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for DevFs {
+    fn read_dir(&self, path: &str) -> Result<Box<dyn Iterator<Item = Metadata> + Send>> {
+        if !Self::device_name(path)?.is_empty() {
+            return Err(FsError::NotADirectory);
+        }
+
+        let entries: Vec<_> = self
+            .devices
+            .read()
+            .iter()
+            .map(|(name, node)| Self::device_meta(name, node))
+            .collect();
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> Result<FileHandle> {
+        let name = Self::device_name(path)?;
+        let devices = self.devices.read();
+        let node = devices.get(name).ok_or(FsError::FileNotFound)?;
+
+        Ok(FileHandle::new(
+            Self::device_meta(name, node),
+            (node.open)()?,
+        ))
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata> {
+        let name = Self::device_name(path)?;
+
+        if name.is_empty() {
+            return Ok(Metadata::new(
+                String::new(),
+                FileType::Directory,
+                0,
+                None,
+                None,
+                None,
+            ));
+        }
+
+        let devices = self.devices.read();
+        let node = devices.get(name).ok_or(FsError::FileNotFound)?;
+
+        Ok(Self::device_meta(name, node))
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.metadata(path).is_ok())
+    }
+
+    fn fs_type(&self) -> &'static str {
+        "devfs"
+    }
+}
+
+impl core::fmt::Debug for DevFs {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DevFs")
+            .field("devices", &self.devices.read().keys())
+            .finish()
+    }
+}
+
+/// Discards everything written to it and reads nothing
+#[derive(Debug, Clone)]
+pub struct Null;
+
+impl Read for Null {
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for Null {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Null {
+    fn seek(&mut self, _pos: SeekFrom) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+/// Discards everything written to it and reads zeros
+#[derive(Debug, Clone)]
+pub struct Zero;
+
+impl Read for Zero {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        buf.fill(0);
+        Ok(buf.len())
+    }
+}
+
+impl Write for Zero {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Zero {
+    fn seek(&mut self, _pos: SeekFrom) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+/// Exposes a block device as a seekable file
+pub struct BlockFile<T: BlockDevice<Block512>> {
+    inner: T,
+    offset: usize,
+}
+
+impl<T: BlockDevice<Block512>> BlockFile<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner, offset: 0 }
+    }
+
+    pub fn length(&self) -> usize {
+        self.inner.block_count().unwrap_or(0) * Block512::size()
+    }
+}
+
+impl<T: BlockDevice<Block512>> Read for BlockFile<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let block_size = Block512::size();
+        let length = self.length();
+        let mut block = Block::default();
+        let mut bytes_read = 0;
+
+        while bytes_read < buf.len() && self.offset < length {
+            self.inner
+                .read_block(self.offset / block_size, &mut block)?;
+
+            let current_offset = self.offset % block_size;
+            let to_read = (buf.len() - bytes_read)
+                .min(block_size - current_offset)
+                .min(length - self.offset);
+
+            buf[bytes_read..bytes_read + to_read]
+                .copy_from_slice(&block[current_offset..current_offset + to_read]);
+
+            bytes_read += to_read;
+            self.offset += to_read;
+        }
+
+        Ok(bytes_read)
+    }
+}
+
+impl<T: BlockDevice<Block512>> Write for BlockFile<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let block_size = Block512::size();
+        let length = self.length();
+        let mut block = Block::default();
+        let mut bytes_written = 0;
+
+        while bytes_written < buf.len() && self.offset < length {
+            let sector = self.offset / block_size;
+            let current_offset = self.offset % block_size;
+            let to_write = (buf.len() - bytes_written)
+                .min(block_size - current_offset)
+                .min(length - self.offset);
+
+            if to_write < block_size {
+                // partial block, keep the rest of the data
+                self.inner.read_block(sector, &mut block)?;
+            }
+
+            block.as_mut()[current_offset..current_offset + to_write]
+                .copy_from_slice(&buf[bytes_written..bytes_written + to_write]);
+
+            self.inner.write_block(sector, &block)?;
+
+            bytes_written += to_write;
+            self.offset += to_write;
+        }
+
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: BlockDevice<Block512>> Seek for BlockFile<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::End(offset) => self.length() as isize + offset,
+            SeekFrom::Current(offset) => self.offset as isize + offset,
+        };
+
+        if offset < 0 || offset as usize > self.length() {
+            return Err(FsError::InvalidOffset);
+        }
+
+        self.offset = offset as usize;
+        Ok(self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_devfs() {
+        let devfs = DevFs::new();
+        devfs.register("foo", 0, || Ok(Box::new(Zero)));
+
+        let names: Vec<_> = devfs.read_dir("/").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, vec!["foo", "null", "random", "zero"]);
+
+        let mut buf = [0xFFu8; 16];
+        assert_eq!(devfs.open_file("/null").unwrap().read(&mut buf), Ok(0));
+        assert_eq!(devfs.open_file("/foo").unwrap().read(&mut buf), Ok(16));
+        assert_eq!(buf, [0; 16]);
+
+        assert!(devfs.open_file("/foo/bar").is_err());
+        devfs.unregister("foo").unwrap();
+        assert!(!devfs.exists("/foo").unwrap());
+    }
+
+    #[test]
+    fn test_random() {
+        let devfs = DevFs::new();
+        let mut file = devfs.open_file("/random").unwrap();
+
+        // more than the 8 bytes of one random number
+        let mut buf = [0u8; 32];
+        assert_eq!(file.read(&mut buf), Ok(32));
+        assert!(buf.chunks(8).any(|chunk| chunk != &buf[..8]));
+        assert!(buf.iter().any(|&byte| byte != 0));
+    }
+}
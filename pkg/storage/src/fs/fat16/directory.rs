@@ -6,7 +6,7 @@
 
 use super::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Directory {
     /// The starting point of the directory listing.
     pub cluster: Cluster,
@@ -31,8 +31,14 @@ impl Directory {
     }
 
     pub fn from_entry(entry: DirEntry) -> Self {
+        // `..` entries point to cluster 0 when the parent is the root directory
+        let cluster = match entry.cluster {
+            Cluster::EMPTY => Cluster::ROOT_DIR,
+            cluster => cluster,
+        };
+
         Directory {
-            cluster: entry.cluster,
+            cluster,
             entry: Some(entry),
         }
     }
@@ -8,13 +8,15 @@
 use crate::*;
 use bitflags::bitflags;
 use chrono::LocalResult::Single;
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Datelike, TimeZone, Timelike, Utc};
 use core::fmt::{Debug, Display};
 use core::ops::*;
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct DirEntry {
     pub filename: ShortFileName,
+    /// The VFAT long file name, if any
+    pub long_name: Option<String>,
     pub moditified_time: FsTime,
     pub created_time: FsTime,
     pub accessed_time: FsTime,
@@ -36,13 +38,28 @@ bitflags! {
         const VOLUME_ID = 0x08;
         const DIRECTORY = 0x10;
         const ARCHIVE   = 0x20;
-        const LFN       = 0x0f; // Long File Name, Not Implemented
+        const LFN       = 0x0f; // Long File Name
     }
 }
 
 impl DirEntry {
     pub const LEN: usize = 0x20;
 
+    /// Create a new entry with the current time
+    pub fn new(filename: ShortFileName, attributes: Attributes, cluster: Cluster) -> Self {
+        let now = current_time();
+        Self {
+            filename,
+            long_name: None,
+            moditified_time: now,
+            created_time: now,
+            accessed_time: now,
+            cluster,
+            attributes,
+            size: 0,
+        }
+    }
+
     pub fn is_readonly(&self) -> bool {
         self.attributes.contains(Attributes::READ_ONLY)
     }
@@ -84,13 +101,23 @@ impl DirEntry {
     }
 
     pub fn filename(&self) -> String {
-        if self.is_valid() && !self.is_long_name() {
+        if let Some(long_name) = &self.long_name {
+            long_name.clone()
+        } else if self.is_valid() && !self.is_long_name() {
             format!("{}", self.filename)
         } else {
             String::from("unknown")
         }
     }
 
+    /// Returns true if the long or the short name matches, ignoring case
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.long_name
+            .as_deref()
+            .is_some_and(|long_name| long_name.eq_ignore_ascii_case(name))
+            || format!("{}", self.filename).eq_ignore_ascii_case(name)
+    }
+
     /// For Standard 8.3 format
     pub fn parse(data: &[u8]) -> Result<DirEntry> {
         // trace!(
@@ -103,10 +130,7 @@ impl DirEntry {
 
         let filename = ShortFileName::new(&data[..11]);
 
-        // TODO: parse long file name
-        // if filename.is_eod() || filename.is_unused() {
-        //     return Err(FilenameError::UnableToParse);
-        // }
+        // long file names are collected by `LfnBuffer` while iterating
 
         let attributes = Attributes::from_bits_truncate(data[11]);
 
@@ -131,6 +155,7 @@ impl DirEntry {
 
         Ok(DirEntry {
             filename,
+            long_name: None,
             moditified_time,
             created_time,
             accessed_time,
@@ -140,6 +165,27 @@ impl DirEntry {
         })
     }
 
+    /// Serialize the entry into the standard 8.3 format
+    pub fn as_bytes(&self) -> [u8; DirEntry::LEN] {
+        let mut data = [0u8; DirEntry::LEN];
+
+        data[..8].copy_from_slice(&self.filename.name);
+        data[8..11].copy_from_slice(&self.filename.ext);
+        data[11] = self.attributes.bits();
+
+        // 12: Reserved. Must be set to zero
+        // 13: CrtTimeTenth, not supported, set to zero
+
+        data[14..18].copy_from_slice(&encode_datetime(&self.created_time).to_le_bytes());
+        data[18..20].copy_from_slice(&encode_datetime(&self.accessed_time).to_le_bytes()[2..]);
+        data[20..22].copy_from_slice(&((self.cluster.0 >> 16) as u16).to_le_bytes());
+        data[22..26].copy_from_slice(&encode_datetime(&self.moditified_time).to_le_bytes());
+        data[26..28].copy_from_slice(&(self.cluster.0 as u16).to_le_bytes());
+        data[28..32].copy_from_slice(&self.size.to_le_bytes());
+
+        data
+    }
+
     pub fn as_meta(&self) -> Metadata {
         self.into()
     }
@@ -160,6 +206,17 @@ fn prase_datetime(time: u32) -> FsTime {
     }
 }
 
+fn encode_datetime(time: &FsTime) -> u32 {
+    let year = (time.year() - 1980).clamp(0, 127) as u32;
+
+    (year << 25)
+        | (time.month() << 21)
+        | (time.day() << 16)
+        | (time.hour() << 11)
+        | (time.minute() << 5)
+        | (time.second() / 2)
+}
+
 #[derive(PartialEq, Eq, Clone)]
 pub struct ShortFileName {
     pub name: [u8; 8],
@@ -167,6 +224,21 @@ pub struct ShortFileName {
 }
 
 impl ShortFileName {
+    /// Marker of a deleted entry
+    pub const UNUSED: u8 = 0xE5;
+
+    /// The `.` entry of a directory
+    pub const DOT: ShortFileName = ShortFileName {
+        name: *b".       ",
+        ext: *b"   ",
+    };
+
+    /// The `..` entry of a directory
+    pub const DOTDOT: ShortFileName = ShortFileName {
+        name: *b"..      ",
+        ext: *b"   ",
+    };
+
     pub fn new(buf: &[u8]) -> Self {
         Self {
             name: buf[..8].try_into().unwrap(),
@@ -187,7 +259,11 @@ impl ShortFileName {
     }
 
     pub fn is_unused(&self) -> bool {
-        self.name[0] == 0xE5
+        self.name[0] == Self::UNUSED
+    }
+
+    pub fn is_dot_entry(&self) -> bool {
+        self.matches(&Self::DOT) || self.matches(&Self::DOTDOT)
     }
 
     pub fn matches(&self, sfn: &ShortFileName) -> bool {
@@ -195,6 +271,12 @@ impl ShortFileName {
     }
 
     pub fn parse(name: &str) -> Result<ShortFileName> {
+        match name {
+            "." => return Ok(Self::DOT),
+            ".." => return Ok(Self::DOTDOT),
+            _ => {}
+        }
+
         let mut sfn = ShortFileName {
             name: [0x20; 8],
             ext: [0x20; 3],
@@ -255,6 +337,44 @@ impl ShortFileName {
         }
         Ok(sfn)
     }
+
+    /// Generates the `n`-th 8.3 alias (`BASENA~N.EXT`) of a long file name
+    pub fn alias(name: &str, n: usize) -> ShortFileName {
+        let mut sfn = ShortFileName {
+            name: [0x20; 8],
+            ext: [0x20; 3],
+        };
+
+        let (base, ext) = match name.trim_start_matches('.').rsplit_once('.') {
+            Some((base, ext)) if !base.is_empty() => (base, ext),
+            _ => (name, ""),
+        };
+
+        let convert = |s: &str| {
+            s.chars()
+                .filter(|&ch| ch != ' ' && ch != '.')
+                .map(|ch| match ch {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' => ch.to_ascii_uppercase() as u8,
+                    '$' | '%' | '\'' | '-' | '_' | '@' | '~' | '`' | '!' | '(' | ')' | '{'
+                    | '}' | '^' | '#' | '&' => ch as u8,
+                    _ => b'_',
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let tail = format!("~{}", n);
+        let base = convert(base);
+        let base_len = base.len().min(8 - tail.len());
+
+        sfn.name[..base_len].copy_from_slice(&base[..base_len]);
+        sfn.name[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+
+        for (dst, src) in sfn.ext.iter_mut().zip(convert(ext)) {
+            *dst = src;
+        }
+
+        sfn
+    }
 }
 
 impl Debug for ShortFileName {
@@ -380,4 +500,53 @@ mod tests {
 
         println!("{:#?}", res);
     }
+
+    #[test]
+    fn test_dir_entry_as_bytes() {
+        let data = hex_literal::hex!(
+            "4b 45 52 4e 45 4c 20 20 45 4c 46 20 00 00 0f be
+             d0 50 d0 50 00 00 0f be d0 50 02 00 f0 e4 0e 00"
+        );
+
+        let mut res = DirEntry::parse(&data).unwrap();
+
+        assert_eq!(res.as_bytes(), data);
+
+        res.cluster = Cluster(0x0012_3456);
+        res.size = 0x1234;
+        res.moditified_time = Utc.with_ymd_and_hms(2023, 3, 14, 15, 9, 26).unwrap();
+
+        let parsed = DirEntry::parse(&res.as_bytes()).unwrap();
+
+        assert_eq!(parsed, res);
+    }
+
+    #[test]
+    fn test_short_file_name() {
+        let sfn = ShortFileName::parse("hello.txt").unwrap();
+
+        assert_eq!(&sfn.name, b"HELLO   ");
+        assert_eq!(&sfn.ext, b"TXT");
+        assert_eq!(format!("{}", sfn), "HELLO.TXT");
+
+        assert_eq!(ShortFileName::parse("..").unwrap(), ShortFileName::DOTDOT);
+        assert!(ShortFileName::parse("..").unwrap().is_dot_entry());
+        assert!(ShortFileName::parse("toolongname").is_err());
+        assert!(ShortFileName::parse("a*b").is_err());
+    }
+
+    #[test]
+    fn test_short_file_name_alias() {
+        let sfn = ShortFileName::alias("hello_world", 1);
+        assert_eq!(format!("{}", sfn), "HELLO_~1");
+
+        let sfn = ShortFileName::alias("archive.tar.gz", 2);
+        assert_eq!(format!("{}", sfn), "ARCHIV~2.GZ");
+
+        let sfn = ShortFileName::alias("my file+name.text", 10);
+        assert_eq!(format!("{}", sfn), "MYFIL~10.TEX");
+
+        let sfn = ShortFileName::alias(".config", 1);
+        assert_eq!(format!("{}", sfn), "CONFIG~1");
+    }
 }
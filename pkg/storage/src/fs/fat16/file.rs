@@ -4,28 +4,40 @@
 //! - <https://wiki.osdev.org/FAT#Directories_on_FAT12.2F16.2F32>
 //! - <https://github.com/rust-embedded-community/embedded-sdmmc-rs/blob/develop/src/filesystem.rs>
 
-use x86_64::registers::debug;
-
 use super::*;
 
-#[derive(Debug, Clone)]
+/// The most sectors read from the disk at once
+const MAX_READ_SECTORS: usize = 64;
+
+/// An opened file, the DirEntry is written back when it is dropped,
+/// so it is not `Clone` to avoid overwriting it with a stale copy.
+#[derive(Debug)]
 pub struct File {
     /// The current offset in the file.
     pub offset: usize,
     /// DirEntry of this file
     entry: DirEntry,
+    /// The directory that contains this file
+    dir: Directory,
     /// The current cluster of this file
     current: Cluster,
+    /// The index of the current cluster in the cluster chain
+    index: usize,
+    /// Whether the DirEntry needs to be written back
+    modified: bool,
     /// The file system handle that contains this file.
     handle: Fat16Handle,
 }
 
 impl File {
-    pub fn new(handle: Fat16Handle, entry: DirEntry) -> Self {
+    pub fn new(handle: Fat16Handle, dir: Directory, entry: DirEntry) -> Self {
         Self {
             offset: 0,
             current: entry.cluster,
+            index: 0,
+            modified: false,
             entry,
+            dir,
             handle,
         }
     }
@@ -33,6 +45,167 @@ impl File {
     pub fn length(&self) -> usize {
         self.entry.size as usize
     }
+
+    /// Moves `current` to the cluster that contains `offset`.
+    ///
+    /// Returns `false` if the cluster chain ends before `offset`,
+    /// unless `alloc` is set, in which case the chain is extended.
+    fn locate(&mut self, alloc: bool) -> Result<bool> {
+        let index = self.offset / self.handle.cluster_size();
+
+        if self.current == Cluster::EMPTY {
+            // empty file, no cluster allocated yet
+            if !alloc {
+                return Ok(false);
+            }
+
+            let cluster = self.handle.alloc_cluster(None)?;
+            self.entry.cluster = cluster;
+            self.current = cluster;
+            self.index = 0;
+            self.modified = true;
+        }
+
+        if index < self.index {
+            // the chain is singly linked, walk from the start
+            self.current = self.entry.cluster;
+            self.index = 0;
+        }
+
+        while self.index < index {
+            self.current = match self.handle.next_cluster(&self.current) {
+                Ok(next) => next,
+                Err(FsError::EndOfFile) if alloc => {
+                    self.handle.alloc_cluster(Some(&self.current))?
+                }
+                Err(FsError::EndOfFile) => return Ok(false),
+                Err(e) => return Err(e),
+            };
+            self.index += 1;
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the sector that contains `offset`, `current` must be located
+    fn current_sector(&self) -> usize {
+        let cluster_offset = self.offset % self.handle.cluster_size();
+        self.handle.cluster_to_sector(&self.current) + cluster_offset / BLOCK_SIZE
+    }
+
+    /// Returns the count of sectors from the one that contains `offset` on
+    /// which are contiguous on the disk, up to `max`, `current` must be located
+    fn contiguous_sectors(&self, max: usize) -> Result<usize> {
+        let sectors_per_cluster = self.handle.bpb.sectors_per_cluster() as usize;
+        let cluster_offset = self.offset % self.handle.cluster_size();
+        let mut count = sectors_per_cluster - cluster_offset / BLOCK_SIZE;
+        let mut cluster = self.current;
+
+        while count < max {
+            match self.handle.next_cluster(&cluster) {
+                Ok(next) if next.0 == cluster.0 + 1 => {
+                    cluster = next;
+                    count += sectors_per_cluster;
+                }
+                Ok(_) | Err(FsError::EndOfFile) => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(count.min(max))
+    }
+
+    /// Writes zeros from the end of the file up to `offset`
+    fn fill_gap(&mut self) -> Result<()> {
+        let target = self.offset;
+        let zeros = [0u8; BLOCK_SIZE];
+
+        self.offset = self.length();
+
+        while self.offset < target {
+            let len = (target - self.offset).min(BLOCK_SIZE);
+            self.write_data(&zeros[..len])?;
+        }
+
+        Ok(())
+    }
+
+    /// Shrinks the file to `len` and frees the clusters beyond it,
+    /// or extends it with zeros, the offset is kept
+    pub fn set_len(&mut self, len: usize) -> Result<()> {
+        let offset = self.offset;
+
+        if len > self.length() {
+            self.offset = len;
+            self.fill_gap()?;
+        } else if len < self.length() {
+            match len.div_ceil(self.handle.cluster_size()) {
+                0 => {
+                    self.handle.free_cluster_chain(&self.entry.cluster)?;
+                    self.entry.cluster = Cluster::EMPTY;
+                }
+                clusters => {
+                    // the chain ends at the cluster of the last byte
+                    self.offset = (clusters - 1) * self.handle.cluster_size();
+                    if self.locate(false)? {
+                        self.handle.truncate_cluster_chain(&self.current)?;
+                    }
+                }
+            }
+
+            self.current = self.entry.cluster;
+            self.index = 0;
+            self.entry.size = len as u32;
+            self.modified = true;
+        }
+
+        self.offset = offset;
+        Ok(())
+    }
+
+    /// Writes at `offset`, the cluster chain is extended as needed
+    fn write_data(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut block = Block::default();
+        let mut bytes_written = 0;
+
+        while bytes_written < buf.len() {
+            match self.locate(true) {
+                Ok(_) => {}
+                // return what we have written before the disk is full
+                Err(FsError::DiskFull) if bytes_written > 0 => break,
+                Err(e) => return Err(e),
+            }
+
+            let sector = self.current_sector();
+            let current_offset = self.offset % BLOCK_SIZE;
+            let block_remain = BLOCK_SIZE - current_offset;
+            let buf_remain = buf.len() - bytes_written;
+            let to_write = buf_remain.min(block_remain);
+
+            if to_write < BLOCK_SIZE {
+                // partial block, keep the rest of the data
+                self.handle.inner.read_block(sector, &mut block)?;
+            }
+
+            block.as_mut()[current_offset..current_offset + to_write]
+                .copy_from_slice(&buf[bytes_written..bytes_written + to_write]);
+
+            self.handle.inner.write_block(sector, &block)?;
+
+            bytes_written += to_write;
+            self.offset += to_write;
+
+            if self.offset > self.length() {
+                self.entry.size = self.offset as u32;
+            }
+        }
+
+        if bytes_written > 0 {
+            self.modified = true;
+        }
+
+        Ok(bytes_written)
+    }
 }
 
 impl Read for File {
@@ -43,43 +216,47 @@ impl Read for File {
             return Ok(0);
         }
 
-        let sector_pre_cluster = self.handle.bpb.sectors_per_cluster() as usize;
-        let sector_size = self.handle.bpb.bytes_per_sector() as usize;
-        let cluster_size = sector_pre_cluster * sector_size;
-
         let mut block = Block::default();
+        let mut blocks = Vec::new();
         let mut bytes_read = 0;
 
         while bytes_read < buf.len() && self.offset < length {
-            let cluster_sector = self.handle.cluster_to_sector(&self.current);
-            let cluster_offset = self.offset % cluster_size;
-            let current_sector = cluster_sector + cluster_offset / BLOCK_SIZE;
-
-            self.handle.inner.read_block(current_sector, &mut block)?;
+            if !self.locate(false)? {
+                break;
+            }
 
             let current_offset = self.offset % BLOCK_SIZE;
-            let block_remain = BLOCK_SIZE - current_offset;
-            let file_remain = length - self.offset;
-            let buf_remain = buf.len() - bytes_read;
-            let to_read = buf_remain.min(block_remain).min(file_remain);
+            let remain = (buf.len() - bytes_read).min(length - self.offset);
+
+            if current_offset == 0 && remain >= BLOCK_SIZE {
+                // whole sectors, the contiguous ones are read at once
+                let max = (remain / BLOCK_SIZE).min(MAX_READ_SECTORS);
+                blocks.resize(self.contiguous_sectors(max)?, Block::default());
+
+                self.handle
+                    .inner
+                    .read_blocks(self.current_sector(), &mut blocks)?;
+
+                for block in blocks.iter() {
+                    buf[bytes_read..bytes_read + BLOCK_SIZE].copy_from_slice(block.as_ref());
+                    bytes_read += BLOCK_SIZE;
+                }
+
+                self.offset += blocks.len() * BLOCK_SIZE;
+                continue;
+            }
+
+            self.handle
+                .inner
+                .read_block(self.current_sector(), &mut block)?;
+
+            let to_read = remain.min(BLOCK_SIZE - current_offset);
 
             buf[bytes_read..bytes_read + to_read]
                 .copy_from_slice(&block[current_offset..current_offset + to_read]);
 
             bytes_read += to_read;
             self.offset += to_read;
-
-            if to_read < block_remain {
-                break;
-            }
-
-            if self.offset % cluster_size == 0 {
-                if let Ok(next_cluster) = self.handle.next_cluster(&self.current) {
-                    self.current = next_cluster;
-                } else {
-                    break;
-                }
-            }
         }
 
         Ok(bytes_read)
@@ -87,17 +264,188 @@ impl Read for File {
 }
 
 impl Seek for File {
-    fn seek(&mut self, _pos: SeekFrom) -> Result<usize> {
-        unimplemented!()
+    fn seek(&mut self, pos: SeekFrom) -> Result<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::End(offset) => self.length() as isize + offset,
+            SeekFrom::Current(offset) => self.offset as isize + offset,
+        };
+
+        if offset < 0 {
+            return Err(FsError::InvalidOffset);
+        }
+
+        // the gap beyond the end is filled on the next write,
+        // `current` will be located on the next read or write
+        self.offset = offset as usize;
+        Ok(self.offset)
     }
 }
 
 impl Write for File {
-    fn write(&mut self, _buf: &[u8]) -> Result<usize> {
-        unimplemented!()
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.offset > self.length() {
+            self.fill_gap()?;
+        }
+
+        self.write_data(buf)
     }
 
     fn flush(&mut self) -> Result<()> {
-        unimplemented!()
+        if !self.modified {
+            return Ok(());
+        }
+
+        self.entry.moditified_time = current_time();
+        self.handle.update_directory_entry(&self.dir, &self.entry)?;
+        self.modified = false;
+
+        Ok(())
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            error!("Failed to flush file {}: {:?}", self.entry.filename, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::*;
+    use super::*;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_file_write() {
+        let fs = Fat16::new(format(0x4000, 1));
+        let data = pattern(1500);
+
+        let mut file = fs.create_file("/data.bin").unwrap();
+        file.write_all(&data).unwrap();
+        drop(file);
+
+        assert_eq!(fs.metadata("/data.bin").unwrap().len, data.len());
+
+        let mut buf = Vec::new();
+        fs.open_file("/data.bin")
+            .unwrap()
+            .read_all(&mut buf)
+            .unwrap();
+        assert_eq!(buf, data);
+
+        // overwriting in the middle keeps the length
+        let mut file = fs.open_file("/data.bin").unwrap();
+        file.seek(SeekFrom::Start(510)).unwrap();
+        file.write_all(b"hello").unwrap();
+        drop(file);
+
+        let mut buf = Vec::new();
+        fs.open_file("/data.bin")
+            .unwrap()
+            .read_all(&mut buf)
+            .unwrap();
+        assert_eq!(buf.len(), data.len());
+        assert_eq!(&buf[510..515], b"hello");
+        assert_eq!(&buf[..510], &data[..510]);
+        assert_eq!(&buf[515..], &data[515..]);
+    }
+
+    #[test]
+    fn test_file_cluster_chain() {
+        let fs = Fat16::new(format(0x4000, 2));
+        let cluster_size = fs.handle.cluster_size();
+
+        let mut file = fs.create_file("/chain.bin").unwrap();
+        file.write_all(&pattern(cluster_size)).unwrap();
+        drop(file);
+
+        let entry = fs.handle.get_dir_entry("/chain.bin").unwrap();
+        assert_eq!(cluster_chain(&fs.handle, entry.cluster).len(), 1);
+
+        // appending extends the chain from its last cluster
+        let mut file = fs.append_file("/chain.bin").unwrap();
+        file.write_all(&pattern(cluster_size * 2 + 1)).unwrap();
+        drop(file);
+
+        let entry = fs.handle.get_dir_entry("/chain.bin").unwrap();
+        assert_eq!(entry.size as usize, cluster_size * 3 + 1);
+        assert_eq!(cluster_chain(&fs.handle, entry.cluster).len(), 4);
+
+        let mut buf = Vec::new();
+        fs.open_file("/chain.bin")
+            .unwrap()
+            .read_all(&mut buf)
+            .unwrap();
+        assert_eq!(&buf[cluster_size..], pattern(cluster_size * 2 + 1));
+    }
+
+    #[test]
+    fn test_file_fragmented_read() {
+        let fs = Fat16::new(format(0x4000, 1));
+        let data = pattern(4096);
+
+        // the clusters of the two files are interleaved
+        let mut a = fs.create_file("/A.BIN").unwrap();
+        let mut b = fs.create_file("/B.BIN").unwrap();
+        for chunk in data.chunks(BLOCK_SIZE) {
+            a.write_all(chunk).unwrap();
+            b.write_all(&[0xFF; BLOCK_SIZE]).unwrap();
+        }
+        drop((a, b));
+
+        let mut file = fs.open_file("/A.BIN").unwrap();
+        let mut buf = vec![0u8; 3000];
+        file.seek(SeekFrom::Start(100)).unwrap();
+        assert_eq!(file.read(&mut buf).unwrap(), 3000);
+        assert_eq!(buf, data[100..3100]);
+
+        let mut buf = Vec::new();
+        fs.open_file("/A.BIN").unwrap().read_all(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn test_file_seek() {
+        let fs = Fat16::new(format(0x4000, 1));
+
+        let mut file = fs.create_file("/seek.txt").unwrap();
+        file.write_all(b"hello world").unwrap();
+
+        let mut buf = [0u8; 5];
+        assert_eq!(file.seek(SeekFrom::Start(6)), Ok(6));
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"world");
+
+        assert_eq!(file.seek(SeekFrom::End(-5)), Ok(6));
+        assert_eq!(file.seek(SeekFrom::Current(-6)), Ok(0));
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        assert_eq!(
+            file.seek(SeekFrom::Current(-6)),
+            Err(FsError::InvalidOffset)
+        );
+
+        // seeking beyond the end reads nothing, the gap is filled on write
+        assert_eq!(file.seek(SeekFrom::End(1000)), Ok(1011));
+        assert_eq!(file.read(&mut buf), Ok(0));
+        file.write_all(b"!").unwrap();
+        drop(file);
+
+        let mut buf = Vec::new();
+        fs.open_file("/seek.txt")
+            .unwrap()
+            .read_all(&mut buf)
+            .unwrap();
+        assert_eq!(buf.len(), 1012);
+        assert_eq!(&buf[..11], b"hello world");
+        assert!(buf[11..1011].iter().all(|&b| b == 0));
+        assert_eq!(buf[1011], b'!');
     }
 }
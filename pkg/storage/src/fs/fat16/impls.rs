@@ -1,4 +1,5 @@
 use super::*;
+use core::ops::Range;
 
 impl Fat16Impl {
     pub fn new(inner: impl BlockDevice<Block512>) -> Self {
@@ -14,22 +15,34 @@ impl Fat16Impl {
         let root_dir_size =
             (bpb.root_entries_count() as usize * DirEntry::LEN).div_ceil(block_size);
 
+        let sectors_per_fat = bpb.sectors_per_fat() as usize;
         let fat_start = bpb.reserved_sector_count() as usize;
-        let first_root_dir_sector =
-            fat_start + (bpb.fat_count() as usize * bpb.sectors_per_fat() as usize);
+        let first_root_dir_sector = fat_start + (bpb.fat_count() as usize * sectors_per_fat);
         let first_data_sector = first_root_dir_sector + root_dir_size;
 
         Self {
             bpb,
             inner: Box::new(inner),
+            fat_type: FatType::Fat16,
+            sectors_per_fat,
             fat_start,
             first_data_sector,
             first_root_dir_sector,
+            root_cluster: Cluster::ROOT_DIR,
+            ext: None,
         }
     }
 
-    pub fn cluster_to_sector(&self, cluster: &Cluster) -> usize {
+    /// Maps `Cluster::ROOT_DIR` to the first cluster of the root directory
+    pub fn resolve_cluster(&self, cluster: &Cluster) -> Cluster {
         match *cluster {
+            Cluster::ROOT_DIR => self.root_cluster,
+            cluster => cluster,
+        }
+    }
+
+    pub fn cluster_to_sector(&self, cluster: &Cluster) -> usize {
+        match self.resolve_cluster(cluster) {
             Cluster::ROOT_DIR => self.first_root_dir_sector,
             Cluster(c) => {
                 // FirstSectorofCluster = ((N – 2) * BPB_SecPerClus) + FirstDataSector;
@@ -39,126 +52,449 @@ impl Fat16Impl {
         }
     }
 
-    /// Finds an entry in a given sector
-    fn find_entry_in_sector(&self, match_name: &ShortFileName, sector: usize) -> Result<DirEntry> {
-        let mut block = Block::default();
-        let block_size = Block512::size();
-        self.inner.read_block(sector, &mut block).unwrap();
-
-        for entry in 0..block_size / DirEntry::LEN {
-            let start = entry * DirEntry::LEN;
-            let end = (entry + 1) * DirEntry::LEN;
-            let dir_entry =
-                DirEntry::parse(&block[start..end]).map_err(|_| FsError::InvalidOperation)?;
-            // trace!("Matching {} to {}...", dir_entry.filename(), match_name);
-            if dir_entry.is_eod() {
-                // Can quit early
-                return Err(FsError::FileNotFound);
-            } else if dir_entry.filename.matches(match_name) {
-                // Found it
-                return Ok(dir_entry);
-            };
+    /// Returns the size of a cluster in bytes
+    pub fn cluster_size(&self) -> usize {
+        self.bpb.sectors_per_cluster() as usize * BLOCK_SIZE
+    }
+
+    /// Returns the number of data clusters in the volume
+    pub fn cluster_count(&self) -> usize {
+        let data_sectors = self.bpb.total_sectors() as usize - self.first_data_sector;
+        data_sectors / self.bpb.sectors_per_cluster() as usize
+    }
+
+    /// Returns the FAT copies in use, which are all of them
+    /// unless mirroring is disabled on Fat32
+    fn fat_copies(&self) -> Range<usize> {
+        match self.ext.as_ref().and_then(|ext| ext.active_fat()) {
+            Some(fat) => fat..fat + 1,
+            None => 0..self.bpb.fat_count() as usize,
+        }
+    }
+
+    /// Returns the cluster to start looking for free clusters from
+    fn next_free_hint(&self) -> usize {
+        self.ext
+            .as_ref()
+            .map_or(2, |ext| ext.next_free_hint(self.inner.as_ref()))
+    }
+
+    /// Accounts the clusters freed or allocated in the volume
+    fn update_free(&self, delta: isize, allocated: Option<Cluster>) -> Result<()> {
+        match &self.ext {
+            Some(ext) => ext.update_free(self.inner.as_ref(), delta, allocated),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the first sector of the FAT that is read from
+    fn active_fat_start(&self) -> usize {
+        self.fat_start + self.fat_copies().start * self.sectors_per_fat
+    }
+
+    /// Reads the `idx`-th FAT entry in a sector of the FAT
+    fn read_fat_entry(&self, block: &Block512, idx: usize) -> u32 {
+        let offset = idx * self.fat_type.entry_size();
+        match self.fat_type {
+            FatType::Fat16 => u16::from_le_bytes([block[offset], block[offset + 1]]) as u32,
+            // the high 4 bits of Fat32 entries are reserved
+            FatType::Fat32 => {
+                u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap()) & 0x0FFF_FFFF
+            }
         }
-        Err(FsError::NotInSector)
     }
 
     /// look for next cluster in FAT
     pub fn next_cluster(&self, cluster: &Cluster) -> Result<Cluster> {
-        let fat_offset = (cluster.0 * 2) as usize;
+        let cluster = self.resolve_cluster(cluster);
         let mut block = Block::default();
         let block_size = Block512::size();
-        let cur_fat_sector = self.fat_start + fat_offset / block_size;
-        let offset = fat_offset % block_size;
+        let entries_per_sector = block_size / self.fat_type.entry_size();
+        let cur_fat_sector = self.active_fat_start() + cluster.0 as usize / entries_per_sector;
 
-        self.inner.read_block(cur_fat_sector, &mut block).unwrap();
+        self.inner.read_block(cur_fat_sector, &mut block)?;
 
-        let fat_entry = u16::from_le_bytes(block[offset..=offset + 1].try_into().unwrap_or([0; 2]));
+        let fat_entry = self.read_fat_entry(&block, cluster.0 as usize % entries_per_sector);
+        let bad_cluster = self.fat_type.bad_cluster();
         match fat_entry {
-            0xFFF7 => Err(FsError::BadCluster),         // Bad cluster
-            0xFFF8..=0xFFFF => Err(FsError::EndOfFile), // There is no next cluster
-            f => Ok(Cluster(f as u32)),                 // Seems legit
+            f if f == bad_cluster => Err(FsError::BadCluster), // Bad cluster
+            f if f > bad_cluster => Err(FsError::EndOfFile),   // There is no next cluster
+            f => Ok(Cluster(f)),                               // Seems legit
         }
     }
 
-    pub fn iterate_dir<F>(&self, dir: &directory::Directory, mut func: F) -> Result<()>
-    where
-        F: FnMut(&DirEntry),
-    {
-        if let Some(entry) = &dir.entry {
-            trace!("Iterating directory: {}", entry.filename());
+    /// Writes the FAT entry of the given cluster into every FAT copy in use
+    fn set_fat_entry(&self, cluster: &Cluster, value: u32) -> Result<()> {
+        let entry_size = self.fat_type.entry_size();
+        let fat_offset = cluster.0 as usize * entry_size;
+        let mut block = Block::default();
+        let block_size = Block512::size();
+        let offset = fat_offset % block_size;
+
+        for fat in self.fat_copies() {
+            let fat_sector = self.fat_start + fat * self.sectors_per_fat + fat_offset / block_size;
+
+            self.inner.read_block(fat_sector, &mut block)?;
+            let entry = &mut block.as_mut()[offset..offset + entry_size];
+            match self.fat_type {
+                FatType::Fat16 => entry.copy_from_slice(&(value as u16).to_le_bytes()),
+                FatType::Fat32 => {
+                    // keep the reserved high 4 bits
+                    let old = u32::from_le_bytes((&*entry).try_into().unwrap());
+                    let value = (old & 0xF000_0000) | (value & 0x0FFF_FFFF);
+                    entry.copy_from_slice(&value.to_le_bytes());
+                }
+            }
+            self.inner.write_block(fat_sector, &block)?;
         }
 
-        let mut current_cluster = Some(dir.cluster);
-        let mut dir_sector_num = self.cluster_to_sector(&dir.cluster);
-        let dir_size = match dir.cluster {
-            Cluster::ROOT_DIR => self.first_data_sector - self.first_root_dir_sector,
-            _ => self.bpb.sectors_per_cluster() as usize,
-        };
-        trace!("Directory size: {}", dir_size);
+        Ok(())
+    }
 
+    /// Allocates a free cluster and marks it as the end of a chain,
+    /// the new cluster is linked after `prev` if given.
+    pub fn alloc_cluster(&self, prev: Option<&Cluster>) -> Result<Cluster> {
         let mut block = Block::default();
         let block_size = Block512::size();
+        let entries_per_sector = block_size / self.fat_type.entry_size();
+        let last_cluster = self.cluster_count() + 2;
+        let last_sector = last_cluster.div_ceil(entries_per_sector);
+
+        // start from the hint and wrap around,
+        // the sector of the hint is visited again at last
+        let hint = self.next_free_hint() % last_cluster;
+        let first_sector = hint / entries_per_sector;
+
+        for offset in 0..=last_sector {
+            let sector = (first_sector + offset) % last_sector;
+            self.inner
+                .read_block(self.active_fat_start() + sector, &mut block)?;
+
+            for idx in 0..entries_per_sector {
+                let cluster = sector * entries_per_sector + idx;
+                // the first two entries are reserved
+                if cluster < 2 || cluster >= last_cluster || (offset == 0 && cluster < hint) {
+                    continue;
+                }
+
+                if self.read_fat_entry(&block, idx) == 0 {
+                    let cluster = Cluster(cluster as u32);
+                    self.set_fat_entry(&cluster, self.fat_type.end_of_chain())?;
+                    if let Some(prev) = prev {
+                        self.set_fat_entry(prev, cluster.0)?;
+                    }
+                    self.update_free(-1, Some(cluster))?;
+                    trace!("Allocated cluster: {}", cluster);
+                    return Ok(cluster);
+                }
+            }
+        }
+
+        Err(FsError::DiskFull)
+    }
+
+    /// Walks the sectors of the given directory,
+    /// stops and returns the value once `func` returns `Some`
+    fn walk_dir_sectors<T, F>(&self, dir: &Directory, mut func: F) -> Result<Option<T>>
+    where
+        F: FnMut(usize) -> Result<Option<T>>,
+    {
+        let mut current_cluster = Some(self.resolve_cluster(&dir.cluster));
         while let Some(cluster) = current_cluster {
+            let dir_sector_num = self.cluster_to_sector(&cluster);
+            let dir_size = match cluster {
+                Cluster::ROOT_DIR => self.first_data_sector - self.first_root_dir_sector,
+                _ => self.bpb.sectors_per_cluster() as usize,
+            };
             for sector in dir_sector_num..dir_sector_num + dir_size {
-                self.inner.read_block(sector, &mut block).unwrap();
-                for entry in 0..block_size / DirEntry::LEN {
-                    let start = entry * DirEntry::LEN;
-                    let end = (entry + 1) * DirEntry::LEN;
+                if let Some(ret) = func(sector)? {
+                    return Ok(Some(ret));
+                }
+            }
+            current_cluster = match cluster {
+                // the root directory of Fat16 is not a cluster chain
+                Cluster::ROOT_DIR => None,
+                _ => self.next_cluster(&cluster).ok(),
+            };
+        }
+        Ok(None)
+    }
 
-                    let dir_entry = DirEntry::parse(&block[start..end])?;
+    /// Walks the entries of the given directory along with the slots
+    /// (sector, offset) they occupy, long name entries come before the
+    /// 8.3 entry, stops and returns the value once `func` returns `Some`
+    fn scan_dir<T, F>(&self, dir: &Directory, mut func: F) -> Result<Option<T>>
+    where
+        F: FnMut(DirEntry, &[(usize, usize)]) -> Option<T>,
+    {
+        let mut block = Block::default();
+        let block_size = Block512::size();
+        let mut lfn = LfnBuffer::default();
+        let mut slots = Vec::new();
+
+        let found = self.walk_dir_sectors(dir, |sector| {
+            self.inner.read_block(sector, &mut block)?;
+            for idx in 0..block_size / DirEntry::LEN {
+                let start = idx * DirEntry::LEN;
+                let data = &block[start..start + DirEntry::LEN];
+
+                match data[0] {
+                    0x00 => return Ok(Some(None)),
+                    ShortFileName::UNUSED => {
+                        lfn.clear();
+                        slots.clear();
+                        continue;
+                    }
+                    _ => {}
+                }
 
-                    if dir_entry.is_eod() {
-                        return Ok(());
-                    } else if dir_entry.is_valid() && !dir_entry.is_long_name() {
-                        func(&dir_entry);
+                if let Some(entry) = LfnEntry::parse(data) {
+                    if entry.is_last() {
+                        slots.clear();
                     }
+                    lfn.push(&entry);
+                    slots.push((sector, start));
+                    continue;
                 }
+
+                let mut dir_entry = DirEntry::parse(data)?;
+                dir_entry.long_name = lfn.take(&dir_entry.filename);
+                if dir_entry.long_name.is_none() {
+                    // orphaned long name entries are not part of this entry
+                    slots.clear();
+                }
+                slots.push((sector, start));
+
+                if !dir_entry.is_volume_id()
+                    && let Some(ret) = func(dir_entry, &slots)
+                {
+                    return Ok(Some(Some(ret)));
+                }
+                slots.clear();
             }
-            current_cluster = if cluster != Cluster::ROOT_DIR {
-                match self.next_cluster(&cluster) {
-                    Ok(n) => {
-                        dir_sector_num = self.cluster_to_sector(&n);
-                        Some(n)
-                    }
-                    _ => None,
+            Ok(None)
+        })?;
+
+        Ok(found.flatten())
+    }
+
+    /// Finds the slots of the entry with the given short name
+    fn find_entry_slots(
+        &self,
+        dir: &Directory,
+        name: &ShortFileName,
+    ) -> Result<Vec<(usize, usize)>> {
+        let found = self.scan_dir(dir, |entry, slots| {
+            entry.filename.matches(name).then(|| slots.to_vec())
+        })?;
+
+        found.ok_or(FsError::FileNotFound)
+    }
+
+    /// Finds `count` consecutive unused slots in the directory,
+    /// non-root directories are extended with new clusters when they are full.
+    fn find_free_slots(&self, dir: &Directory, count: usize) -> Result<Vec<(usize, usize)>> {
+        let mut block = Block::default();
+        let block_size = Block512::size();
+        let mut slots = Vec::with_capacity(count);
+
+        let found = self.walk_dir_sectors(dir, |sector| {
+            self.inner.read_block(sector, &mut block)?;
+            for idx in 0..block_size / DirEntry::LEN {
+                let start = idx * DirEntry::LEN;
+
+                // every slot after the end of directory is unused as well
+                match block[start] {
+                    0x00 | ShortFileName::UNUSED => slots.push((sector, start)),
+                    _ => slots.clear(),
+                }
+
+                if slots.len() == count {
+                    return Ok(Some(()));
                 }
-            } else {
-                None
             }
+            Ok(None)
+        })?;
+
+        if found.is_some() {
+            return Ok(slots);
         }
-        Ok(())
+
+        let mut last = self.resolve_cluster(&dir.cluster);
+
+        if last == Cluster::ROOT_DIR {
+            // the root directory has a fixed size in Fat16
+            return Err(FsError::DiskFull);
+        }
+
+        while let Ok(next) = self.next_cluster(&last) {
+            last = next;
+        }
+
+        // the unused slots at the end of the last cluster are kept,
+        // a long name may span more than one new cluster
+        let slots_per_cluster = self.cluster_size() / DirEntry::LEN;
+        while slots.len() < count {
+            let cluster = self.alloc_cluster(Some(&last))?;
+            self.clear_cluster(&cluster)?;
+
+            trace!("Extended directory {} with cluster {}", dir, cluster);
+
+            let sector = self.cluster_to_sector(&cluster);
+            for idx in 0..slots_per_cluster.min(count - slots.len()) {
+                let offset = idx * DirEntry::LEN;
+                slots.push((sector + offset / block_size, offset % block_size));
+            }
+
+            last = cluster;
+        }
+
+        Ok(slots)
     }
 
-    /// Get an entry from the given directory
-    fn find_directory_entry(&self, dir: &Directory, name: &str) -> Result<DirEntry> {
-        let match_name = ShortFileName::parse(name)?;
+    /// Writes raw entry data into the given slot
+    fn write_entry_slot(&self, (sector, offset): (usize, usize), data: &[u8]) -> Result<()> {
+        let mut block = Block::default();
+        self.inner.read_block(sector, &mut block)?;
+        block.as_mut()[offset..offset + data.len()].copy_from_slice(data);
+        self.inner.write_block(sector, &block)
+    }
 
-        let mut current_cluster = Some(dir.cluster);
-        let mut dir_sector_num = self.cluster_to_sector(&dir.cluster);
-        let dir_size = match dir.cluster {
-            Cluster::ROOT_DIR => self.first_data_sector - self.first_root_dir_sector,
-            _ => self.bpb.sectors_per_cluster() as usize,
+    /// Writes the entry back into the directory,
+    /// replacing the entry with the same short name
+    pub fn update_directory_entry(&self, dir: &Directory, entry: &DirEntry) -> Result<()> {
+        let slots = self.find_entry_slots(dir, &entry.filename)?;
+        // the 8.3 entry is always the last one
+        let slot = *slots.last().ok_or(FsError::FileNotFound)?;
+        self.write_entry_slot(slot, &entry.as_bytes())
+    }
+
+    /// Adds a new entry into the directory, along with its long name
+    fn add_directory_entry(&self, dir: &Directory, entry: &DirEntry) -> Result<()> {
+        let mut entries = match &entry.long_name {
+            Some(name) => lfn::encode(name, &entry.filename)?,
+            None => Vec::new(),
         };
-        while let Some(cluster) = current_cluster {
-            for sector in dir_sector_num..dir_sector_num + dir_size {
-                match self.find_entry_in_sector(&match_name, sector) {
-                    Err(FsError::NotInSector) => continue,
-                    x => return x,
-                }
+        entries.push(entry.as_bytes());
+
+        let slots = self.find_free_slots(dir, entries.len())?;
+        for (slot, data) in slots.into_iter().zip(entries.iter()) {
+            self.write_entry_slot(slot, data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Marks the entry in the directory as unused, along with its long name
+    fn remove_directory_entry(&self, dir: &Directory, entry: &DirEntry) -> Result<()> {
+        for slot in self.find_entry_slots(dir, &entry.filename)? {
+            self.write_entry_slot(slot, &[ShortFileName::UNUSED])?;
+        }
+
+        Ok(())
+    }
+
+    /// Picks the short name of a new entry named `name` in the directory,
+    /// an unique `~N` alias is generated if a long name is required.
+    fn new_entry_name(
+        &self,
+        dir: &Directory,
+        name: &str,
+    ) -> Result<(ShortFileName, Option<String>)> {
+        if !lfn::needs_long_name(name) {
+            return Ok((ShortFileName::parse(name)?, None));
+        }
+
+        let mut used = Vec::new();
+        self.scan_dir(dir, |entry, _| {
+            used.push(entry.filename);
+            None::<()>
+        })?;
+
+        let sfn = (1..1000000)
+            .map(|n| ShortFileName::alias(name, n))
+            .find(|sfn| !used.iter().any(|used| used.matches(sfn)))
+            .ok_or(FsError::AlreadyExists)?;
+
+        Ok((sfn, Some(name.into())))
+    }
+
+    /// Fills the cluster with zeros
+    fn clear_cluster(&self, cluster: &Cluster) -> Result<()> {
+        let block = Block::default();
+        let sector = self.cluster_to_sector(cluster);
+
+        for offset in 0..self.bpb.sectors_per_cluster() as usize {
+            self.inner.write_block(sector + offset, &block)?;
+        }
+
+        Ok(())
+    }
+
+    /// Frees every cluster in the chain starting at `cluster`
+    pub fn free_cluster_chain(&self, cluster: &Cluster) -> Result<()> {
+        let mut current = Some(*cluster);
+
+        let mut freed = 0;
+
+        while let Some(cluster) = current {
+            if cluster == Cluster::EMPTY {
+                break;
             }
-            current_cluster = if cluster != Cluster::ROOT_DIR {
-                match self.next_cluster(&cluster) {
-                    Ok(n) => {
-                        dir_sector_num = self.cluster_to_sector(&n);
-                        Some(n)
-                    }
-                    _ => None,
-                }
-            } else {
-                None
+            current = self.next_cluster(&cluster).ok();
+            self.set_fat_entry(&cluster, 0)?;
+            freed += 1;
+        }
+
+        self.update_free(freed, None)
+    }
+
+    /// Ends the chain at `last` and frees the clusters after it
+    pub fn truncate_cluster_chain(&self, last: &Cluster) -> Result<()> {
+        match self.next_cluster(last) {
+            Ok(next) => {
+                self.set_fat_entry(last, self.fat_type.end_of_chain())?;
+                self.free_cluster_chain(&next)
+            }
+            Err(FsError::EndOfFile) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns true if the directory only contains `.` and `..`
+    fn is_empty_dir(&self, dir: &Directory) -> Result<bool> {
+        let mut empty = true;
+
+        self.iterate_dir(dir, |entry| {
+            if !entry.filename.is_dot_entry() {
+                empty = false;
             }
+        })?;
+
+        Ok(empty)
+    }
+
+    pub fn iterate_dir<F>(&self, dir: &directory::Directory, mut func: F) -> Result<()>
+    where
+        F: FnMut(&DirEntry),
+    {
+        if let Some(entry) = &dir.entry {
+            trace!("Iterating directory: {}", entry.filename());
         }
-        Err(FsError::FileNotFound)
+
+        self.scan_dir(dir, |entry, _| {
+            func(&entry);
+            None::<()>
+        })?;
+
+        Ok(())
+    }
+
+    /// Get an entry from the given directory, the name is case-insensitive
+    fn find_directory_entry(&self, dir: &Directory, name: &str) -> Result<DirEntry> {
+        let found = self.scan_dir(dir, |entry, _| entry.matches_name(name).then_some(entry))?;
+
+        found.ok_or(FsError::FileNotFound)
     }
 
     fn get_parent_dir(&self, path: &str) -> Result<Directory> {
@@ -184,12 +520,133 @@ impl Fat16Impl {
         Ok(current)
     }
 
-    fn get_dir_entry(&self, path: &str) -> Result<DirEntry> {
-        let parent = self.get_parent_dir(path)?;
-        let name = path.rsplit(PATH_SEPARATOR).next().unwrap_or("");
+    pub(super) fn get_dir_entry(&self, path: &str) -> Result<DirEntry> {
+        let (parent, name) = self.split_path(path)?;
 
         self.find_directory_entry(&parent, name)
     }
+
+    /// Splits the path into the parent directory and the entry name,
+    /// every component of the parent must be an existing directory.
+    fn split_path<'a>(&self, path: &'a str) -> Result<(Directory, &'a str)> {
+        let path = path.trim_end_matches(PATH_SEPARATOR);
+        let (parent, name) = path.rsplit_once(PATH_SEPARATOR).unwrap_or(("", path));
+
+        if matches!(name, "" | "." | "..") {
+            return Err(FsError::InvalidPath(path.into()));
+        }
+
+        let mut current = Directory::root();
+        for dir in parent.split(PATH_SEPARATOR).filter(|d| !d.is_empty()) {
+            let entry = self.find_directory_entry(&current, dir)?;
+            if !entry.is_directory() {
+                return Err(FsError::NotADirectory);
+            }
+            current = Directory::from_entry(entry);
+        }
+
+        Ok((current, name))
+    }
+
+    /// Creates a new entry at the path, fails if it already exists
+    fn create_entry(&self, path: &str, attributes: Attributes) -> Result<(Directory, DirEntry)> {
+        let (dir, name) = self.split_path(path)?;
+
+        match self.find_directory_entry(&dir, name) {
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(FsError::FileNotFound) => {}
+            Err(e) => return Err(e),
+        }
+
+        let (filename, long_name) = self.new_entry_name(&dir, name)?;
+        let mut entry = DirEntry::new(filename, attributes, Cluster::EMPTY);
+        entry.long_name = long_name;
+
+        if entry.is_directory() {
+            let cluster = self.alloc_cluster(None)?;
+            self.clear_cluster(&cluster)?;
+
+            // `..` of a subdirectory of the root points to cluster 0
+            let parent = match dir.cluster {
+                Cluster::ROOT_DIR => Cluster::EMPTY,
+                cluster => cluster,
+            };
+
+            let dot = DirEntry::new(ShortFileName::DOT, Attributes::DIRECTORY, cluster);
+            let dotdot = DirEntry::new(ShortFileName::DOTDOT, Attributes::DIRECTORY, parent);
+
+            let sector = self.cluster_to_sector(&cluster);
+            self.write_entry_slot((sector, 0), &dot.as_bytes())?;
+            self.write_entry_slot((sector, DirEntry::LEN), &dotdot.as_bytes())?;
+
+            entry.cluster = cluster;
+        }
+
+        self.add_directory_entry(&dir, &entry)?;
+
+        Ok((dir, entry))
+    }
+
+    /// Moves the entry at `src` to `dst`, the cluster chain is kept as is
+    fn move_entry(&self, src: &str, dst: &str, is_dir: bool) -> Result<()> {
+        let (src_dir, src_name) = self.split_path(src)?;
+        let entry = self.find_directory_entry(&src_dir, src_name)?;
+
+        if entry.is_directory() != is_dir {
+            return Err(if is_dir {
+                FsError::NotADirectory
+            } else {
+                FsError::NotAFile
+            });
+        }
+
+        let (dst_dir, dst_name) = self.split_path(dst)?;
+
+        match self.find_directory_entry(&dst_dir, dst_name) {
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(FsError::FileNotFound) => {}
+            Err(e) => return Err(e),
+        }
+
+        if is_dir && self.is_ancestor(&entry.cluster, &dst_dir)? {
+            // cannot move a directory into itself
+            return Err(FsError::InvalidOperation);
+        }
+
+        let mut new_entry = entry.clone();
+        (new_entry.filename, new_entry.long_name) = self.new_entry_name(&dst_dir, dst_name)?;
+
+        self.add_directory_entry(&dst_dir, &new_entry)?;
+        self.remove_directory_entry(&src_dir, &entry)?;
+
+        if is_dir && src_dir.cluster != dst_dir.cluster {
+            // update `..` to point to the new parent
+            let moved = Directory::from_entry(new_entry);
+            let mut dotdot = self.find_directory_entry(&moved, "..")?;
+            dotdot.cluster = match dst_dir.cluster {
+                Cluster::ROOT_DIR => Cluster::EMPTY,
+                cluster => cluster,
+            };
+            self.update_directory_entry(&moved, &dotdot)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the directory starting at `cluster` is `dir` or one of its ancestors
+    fn is_ancestor(&self, cluster: &Cluster, dir: &Directory) -> Result<bool> {
+        let mut current = dir.clone();
+
+        while current.cluster != Cluster::ROOT_DIR {
+            if current.cluster == *cluster {
+                return Ok(true);
+            }
+            let parent = self.find_directory_entry(&current, "..")?;
+            current = Directory::from_entry(parent);
+        }
+
+        Ok(false)
+    }
 }
 
 impl FileSystem for Fat16 {
@@ -205,7 +662,8 @@ impl FileSystem for Fat16 {
     }
 
     fn open_file(&self, path: &str) -> Result<FileHandle> {
-        let entry = self.handle.get_dir_entry(path)?;
+        let (dir, name) = self.handle.split_path(path)?;
+        let entry = self.handle.find_directory_entry(&dir, name)?;
 
         if entry.is_directory() {
             return Err(FsError::NotAFile);
@@ -213,7 +671,7 @@ impl FileSystem for Fat16 {
 
         let handle = self.handle.clone();
         let meta = entry.as_meta();
-        let file = Box::new(File::new(handle, entry));
+        let file = Box::new(File::new(handle, dir, entry));
 
         let file_handle = FileHandle::new(meta, file);
 
@@ -227,4 +685,318 @@ impl FileSystem for Fat16 {
     fn exists(&self, path: &str) -> Result<bool> {
         Ok(self.handle.get_dir_entry(path).is_ok())
     }
+
+    fn create_file(&self, path: &str) -> Result<FileHandle> {
+        let (dir, entry) = self.handle.create_entry(path, Attributes::ARCHIVE)?;
+
+        let handle = self.handle.clone();
+        let meta = entry.as_meta();
+        let file = Box::new(File::new(handle, dir, entry));
+
+        Ok(FileHandle::new(meta, file))
+    }
+
+    fn create_dir(&self, path: &str) -> Result<()> {
+        self.handle.create_entry(path, Attributes::DIRECTORY)?;
+        Ok(())
+    }
+
+    fn append_file(&self, path: &str) -> Result<FileHandle> {
+        let mut file = self.open_file(path)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(file)
+    }
+
+    fn set_len(&self, path: &str, len: usize) -> Result<()> {
+        let (dir, name) = self.handle.split_path(path)?;
+        let entry = self.handle.find_directory_entry(&dir, name)?;
+
+        if entry.is_directory() {
+            return Err(FsError::NotAFile);
+        }
+
+        let mut file = File::new(self.handle.clone(), dir, entry);
+        file.set_len(len)?;
+        file.flush()
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        let (dir, name) = self.handle.split_path(path)?;
+        let entry = self.handle.find_directory_entry(&dir, name)?;
+
+        if entry.is_directory() {
+            return Err(FsError::NotAFile);
+        }
+
+        self.handle.remove_directory_entry(&dir, &entry)?;
+        self.handle.free_cluster_chain(&entry.cluster)
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<()> {
+        let (dir, name) = self.handle.split_path(path)?;
+        let entry = self.handle.find_directory_entry(&dir, name)?;
+
+        if !entry.is_directory() {
+            return Err(FsError::NotADirectory);
+        }
+
+        if !self
+            .handle
+            .is_empty_dir(&Directory::from_entry(entry.clone()))?
+        {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+
+        self.handle.remove_directory_entry(&dir, &entry)?;
+        self.handle.free_cluster_chain(&entry.cluster)
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> Result<()> {
+        let mut src = self.open_file(src)?;
+        let mut dst = self.create_file(dst)?;
+        let mut buf = [0u8; BLOCK_SIZE];
+
+        loop {
+            match src.read(&mut buf)? {
+                0 => break,
+                n => dst.write_all(&buf[..n])?,
+            }
+        }
+
+        dst.flush()
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> Result<()> {
+        self.handle.move_entry(src, dst, false)
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> Result<()> {
+        self.handle.move_entry(src, dst, true)
+    }
+
+    fn fs_type(&self) -> &'static str {
+        "fat16"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::*;
+    use super::*;
+
+    fn write_file(fs: &Fat16, path: &str, data: &[u8]) {
+        let mut file = fs.create_file(path).unwrap();
+        file.write_all(data).unwrap();
+    }
+
+    fn read_file(fs: &Fat16, path: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        fs.open_file(path).unwrap().read_all(&mut buf).unwrap();
+        buf
+    }
+
+    fn entry_slots(fs: &Fat16, path: &str) -> Vec<(usize, usize)> {
+        let (dir, name) = fs.handle.split_path(path).unwrap();
+        let entry = fs.handle.find_directory_entry(&dir, name).unwrap();
+        fs.handle.find_entry_slots(&dir, &entry.filename).unwrap()
+    }
+
+    fn is_free(handle: &Fat16Impl, cluster: &Cluster) -> bool {
+        handle.next_cluster(cluster) == Ok(Cluster::EMPTY)
+    }
+
+    #[test]
+    fn test_create_remove_slots() {
+        let fs = Fat16::new(format(0x4000, 1));
+
+        write_file(&fs, "/A.TXT", b"a");
+        write_file(&fs, "/a long.txt", b"b");
+
+        let short = entry_slots(&fs, "/A.TXT");
+        let long = entry_slots(&fs, "/a long.txt");
+        assert_eq!(short.len(), 1);
+        // one long name entry followed by the 8.3 entry
+        assert_eq!(long.len(), 2);
+        assert_eq!(long[0].1 + DirEntry::LEN, long[1].1);
+
+        // the removed slots are reused by the next entries of the same size
+        fs.remove_file("/A.TXT").unwrap();
+        fs.remove_file("/a long.txt").unwrap();
+        assert!(!fs.exists("/A.TXT").unwrap());
+        assert!(!fs.exists("/a long.txt").unwrap());
+
+        write_file(&fs, "/B.TXT", b"c");
+        write_file(&fs, "/b long.txt", b"d");
+        assert_eq!(entry_slots(&fs, "/B.TXT"), short);
+        assert_eq!(entry_slots(&fs, "/b long.txt"), long);
+
+        assert_eq!(read_file(&fs, "/B.TXT"), b"c");
+        assert_eq!(read_file(&fs, "/b long.txt"), b"d");
+        assert_eq!(fs.create_file("/B.TXT").err(), Some(FsError::AlreadyExists));
+    }
+
+    #[test]
+    fn test_remove_frees_chain() {
+        let fs = Fat16::new(format(0x4000, 1));
+
+        write_file(&fs, "/data.bin", &[0x5a; 1500]);
+        let entry = fs.handle.get_dir_entry("/data.bin").unwrap();
+        let chain = cluster_chain(&fs.handle, entry.cluster);
+        assert_eq!(chain.len(), 3);
+
+        fs.remove_file("/data.bin").unwrap();
+        assert!(chain.iter().all(|cluster| is_free(&fs.handle, cluster)));
+
+        // the freed clusters are allocated again
+        write_file(&fs, "/data.bin", &[0xa5; 1500]);
+        let entry = fs.handle.get_dir_entry("/data.bin").unwrap();
+        let mut new_chain = cluster_chain(&fs.handle, entry.cluster);
+        new_chain.sort_by_key(|cluster| cluster.0);
+        assert_eq!(new_chain, chain);
+    }
+
+    #[test]
+    fn test_set_len() {
+        let fs = Fat16::new(format(0x4000, 1));
+
+        write_file(&fs, "/data.bin", &[0x5a; 1500]);
+        let entry = fs.handle.get_dir_entry("/data.bin").unwrap();
+        let chain = cluster_chain(&fs.handle, entry.cluster);
+        assert_eq!(chain.len(), 3);
+
+        // the clusters beyond the new end are freed
+        fs.set_len("/data.bin", 600).unwrap();
+        assert_eq!(read_file(&fs, "/data.bin"), [0x5a; 600]);
+        assert_eq!(cluster_chain(&fs.handle, entry.cluster), chain[..2]);
+        assert!(is_free(&fs.handle, &chain[2]));
+
+        // extended with zeros
+        fs.set_len("/data.bin", 1100).unwrap();
+        let data = read_file(&fs, "/data.bin");
+        assert_eq!(data[..600], [0x5a; 600]);
+        assert!(data[600..].iter().all(|&b| b == 0));
+        assert_eq!(data.len(), 1100);
+
+        fs.set_len("/data.bin", 0).unwrap();
+        assert_eq!(fs.metadata("/data.bin").unwrap().len, 0);
+        assert_eq!(
+            fs.handle.get_dir_entry("/data.bin").unwrap().cluster,
+            Cluster::EMPTY
+        );
+        assert!(chain.iter().all(|cluster| is_free(&fs.handle, cluster)));
+
+        fs.create_dir("/dir").unwrap();
+        assert_eq!(fs.set_len("/dir", 0), Err(FsError::NotAFile));
+    }
+
+    #[test]
+    fn test_directory_growth() {
+        let fs = Fat16::new(format(0x4000, 1));
+        fs.create_dir("/dir").unwrap();
+
+        let dir = fs.handle.get_dir_entry("/dir").unwrap();
+        assert_eq!(cluster_chain(&fs.handle, dir.cluster).len(), 1);
+
+        // 16 slots per cluster, two of them are `.` and `..`
+        let names: Vec<String> = (0..20).map(|i| format!("/dir/F{}.TXT", i)).collect();
+        for (i, name) in names.iter().enumerate() {
+            write_file(&fs, name, &[i as u8]);
+        }
+
+        let chain = cluster_chain(&fs.handle, dir.cluster);
+        assert_eq!(chain.len(), 2);
+
+        let entries = fs.read_dir("/dir").unwrap().count();
+        assert_eq!(entries, names.len() + 2);
+        for (i, name) in names.iter().enumerate() {
+            assert_eq!(read_file(&fs, name), [i as u8]);
+        }
+
+        assert_eq!(
+            fs.remove_dir("/dir").err(),
+            Some(FsError::DirectoryNotEmpty)
+        );
+
+        for name in names.iter() {
+            fs.remove_file(name).unwrap();
+        }
+        fs.remove_dir("/dir").unwrap();
+
+        assert!(!fs.exists("/dir").unwrap());
+        assert!(chain.iter().all(|cluster| is_free(&fs.handle, cluster)));
+    }
+
+    #[test]
+    fn test_long_name_growth() {
+        let fs = Fat16::new(format(0x4000, 1));
+        fs.create_dir("/dir").unwrap();
+
+        // fill the first cluster of the directory
+        for i in 0..14 {
+            write_file(&fs, &format!("/dir/F{}.TXT", i), &[]);
+        }
+
+        // 20 long name entries and the 8.3 entry need two new clusters
+        let name = format!("/dir/{}", "x".repeat(255));
+        write_file(&fs, &name, b"long");
+
+        let dir = fs.handle.get_dir_entry("/dir").unwrap();
+        assert_eq!(cluster_chain(&fs.handle, dir.cluster).len(), 3);
+        assert_eq!(entry_slots(&fs, &name).len(), 21);
+        assert_eq!(read_file(&fs, &name), b"long");
+
+        let names: Vec<String> = fs.read_dir("/dir").unwrap().map(|meta| meta.name).collect();
+        assert!(names.contains(&"x".repeat(255)));
+    }
+
+    #[test]
+    fn test_move_file() {
+        let fs = Fat16::new(format(0x4000, 1));
+        let data = [0x42; 700];
+
+        write_file(&fs, "/A.TXT", &data);
+        fs.create_dir("/dir").unwrap();
+        let cluster = fs.handle.get_dir_entry("/A.TXT").unwrap().cluster;
+
+        fs.move_file("/A.TXT", "/dir/moved file.txt").unwrap();
+        assert!(!fs.exists("/A.TXT").unwrap());
+        assert_eq!(read_file(&fs, "/dir/moved file.txt"), data);
+
+        // the cluster chain is kept as is
+        let entry = fs.handle.get_dir_entry("/dir/moved file.txt").unwrap();
+        assert_eq!(entry.cluster, cluster);
+        assert_eq!(cluster_chain(&fs.handle, cluster).len(), 2);
+
+        write_file(&fs, "/B.TXT", b"b");
+        assert_eq!(
+            fs.move_file("/B.TXT", "/dir/moved file.txt").err(),
+            Some(FsError::AlreadyExists)
+        );
+        assert_eq!(fs.move_file("/dir", "/c").err(), Some(FsError::NotAFile));
+    }
+
+    #[test]
+    fn test_move_dir() {
+        let fs = Fat16::new(format(0x4000, 1));
+
+        fs.create_dir("/a").unwrap();
+        fs.create_dir("/a/b").unwrap();
+        write_file(&fs, "/a/b/c.txt", b"c");
+
+        assert_eq!(
+            fs.move_dir("/a", "/a/b/a").err(),
+            Some(FsError::InvalidOperation)
+        );
+
+        fs.move_dir("/a/b", "/b").unwrap();
+        assert!(!fs.exists("/a/b").unwrap());
+        assert_eq!(read_file(&fs, "/b/c.txt"), b"c");
+
+        // `..` points to the root directory now
+        let moved = Directory::from_entry(fs.handle.get_dir_entry("/b").unwrap());
+        let dotdot = fs.handle.find_directory_entry(&moved, "..").unwrap();
+        assert_eq!(dotdot.cluster, Cluster::EMPTY);
+
+        fs.remove_dir("/a").unwrap();
+    }
 }
@@ -0,0 +1,225 @@
+//! Long File Name (VFAT)
+//!
+//! reference:
+//! - <https://wiki.osdev.org/FAT#Long_File_Names>
+//! - <https://en.wikipedia.org/wiki/Design_of_the_FAT_file_system#VFAT_long_file_names>
+
+use super::*;
+
+/// Represents a VFAT long file name entry, which stores up to 13 UCS-2
+/// characters of the name and precedes the 8.3 entry it belongs to.
+pub struct LfnEntry {
+    data: [u8; DirEntry::LEN],
+}
+
+impl LfnEntry {
+    /// The number of characters stored in one entry
+    pub const CHARS: usize = 13;
+    /// Set in the sequence number of the last entry of a long name
+    pub const LAST_ENTRY: u8 = 0x40;
+    /// The longest name that can be stored in long name entries
+    pub const MAX_LEN: usize = 255;
+    /// Offsets of the characters in the entry
+    const CHAR_OFFSETS: [usize; Self::CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+    /// Attempt to parse a long name entry, returns `None` for other entries
+    pub fn parse(data: &[u8]) -> Option<LfnEntry> {
+        if data.get(11) != Some(&Attributes::LFN.bits()) {
+            return None;
+        }
+
+        Some(LfnEntry {
+            data: data[..DirEntry::LEN].try_into().ok()?,
+        })
+    }
+
+    define_field!(u8, 0x00, sequence);
+    define_field!(u8, 0x0d, checksum);
+
+    /// The 1-based index of this entry in the long name
+    pub fn index(&self) -> usize {
+        (self.sequence() & !Self::LAST_ENTRY) as usize
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.sequence() & Self::LAST_ENTRY != 0
+    }
+
+    pub fn chars(&self) -> [u16; Self::CHARS] {
+        Self::CHAR_OFFSETS.map(|off| u16::from_le_bytes([self.data[off], self.data[off + 1]]))
+    }
+}
+
+/// Calculates the checksum of the short name that is stored
+/// in every long name entry belonging to it
+pub fn checksum(sfn: &ShortFileName) -> u8 {
+    sfn.name
+        .iter()
+        .chain(sfn.ext.iter())
+        .fold(0u8, |sum, &ch| sum.rotate_right(1).wrapping_add(ch))
+}
+
+/// Collects long name entries until the 8.3 entry they belong to is reached
+#[derive(Default)]
+pub struct LfnBuffer {
+    parts: Vec<[u16; LfnEntry::CHARS]>,
+    checksum: u8,
+    /// The index of the next expected entry, 0 once all entries are collected
+    next: usize,
+    valid: bool,
+}
+
+impl LfnBuffer {
+    pub fn push(&mut self, entry: &LfnEntry) {
+        let index = entry.index();
+
+        if entry.is_last() {
+            self.parts = vec![[0; LfnEntry::CHARS]; index];
+            self.checksum = entry.checksum();
+            self.next = index;
+            self.valid = index > 0;
+        } else if !self.valid || index != self.next || entry.checksum() != self.checksum {
+            self.valid = false;
+            return;
+        }
+
+        if self.valid {
+            self.parts[index - 1] = entry.chars();
+            self.next -= 1;
+        }
+    }
+
+    /// Returns the long name if all of its entries have been collected
+    /// and they belong to the given short name, the buffer is reset.
+    pub fn take(&mut self, sfn: &ShortFileName) -> Option<String> {
+        let valid = self.valid && self.next == 0 && self.checksum == checksum(sfn);
+        self.valid = false;
+
+        if !valid {
+            return None;
+        }
+
+        let chars = self.parts.iter().flatten().copied();
+        let name = chars.take_while(|&ch| ch != 0x0000).collect::<Vec<_>>();
+
+        String::from_utf16(&name).ok()
+    }
+
+    pub fn clear(&mut self) {
+        self.valid = false;
+    }
+}
+
+/// Returns true if the name has to be stored in long name entries
+pub fn needs_long_name(name: &str) -> bool {
+    match ShortFileName::parse(name) {
+        Ok(sfn) => format!("{}", sfn) != name,
+        Err(_) => true,
+    }
+}
+
+/// Encodes the name into long name entries in the order they are stored on
+/// disk, which should be followed by the 8.3 entry of the short name.
+pub fn encode(name: &str, sfn: &ShortFileName) -> Result<Vec<[u8; DirEntry::LEN]>> {
+    if name
+        .chars()
+        .any(|ch| ch < ' ' || matches!(ch, '"' | '*' | '/' | ':' | '<' | '>' | '?' | '\\' | '|'))
+    {
+        return Err(FilenameError::InvalidCharacter.into());
+    }
+
+    let mut chars = name.encode_utf16().collect::<Vec<_>>();
+
+    if chars.is_empty() {
+        return Err(FilenameError::FilenameEmpty.into());
+    } else if chars.len() > LfnEntry::MAX_LEN {
+        return Err(FilenameError::NameTooLong.into());
+    }
+
+    // the name is terminated by 0x0000 and padded with 0xFFFF
+    if !chars.len().is_multiple_of(LfnEntry::CHARS) {
+        chars.push(0x0000);
+    }
+    while !chars.len().is_multiple_of(LfnEntry::CHARS) {
+        chars.push(0xFFFF);
+    }
+
+    let count = chars.len() / LfnEntry::CHARS;
+    let checksum = checksum(sfn);
+
+    let entries = chars
+        .chunks(LfnEntry::CHARS)
+        .enumerate()
+        .rev()
+        .map(|(idx, part)| {
+            let mut data = [0u8; DirEntry::LEN];
+
+            data[0] = idx as u8 + 1;
+            if idx + 1 == count {
+                data[0] |= LfnEntry::LAST_ENTRY;
+            }
+            data[11] = Attributes::LFN.bits();
+            data[13] = checksum;
+
+            for (ch, off) in part.iter().zip(LfnEntry::CHAR_OFFSETS) {
+                data[off..off + 2].copy_from_slice(&ch.to_le_bytes());
+            }
+
+            data
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lfn_checksum() {
+        // the 8.3 names and the checksums in their long name entries,
+        // written by macOS for `fseventsd-uuid` and `._501`
+        let sfn = ShortFileName::new(b"FSEVEN~1   ");
+        assert_eq!(checksum(&sfn), 0xDA);
+
+        let sfn = ShortFileName::new(b"_50~1      ");
+        assert_eq!(checksum(&sfn), 0x9F);
+    }
+
+    #[test]
+    fn test_lfn_roundtrip() {
+        let name = "hello_world_from_ggos.txt";
+        let sfn = ShortFileName::parse("HELLO_~1.TXT").unwrap();
+        let entries = encode(name, &sfn).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0][0], LfnEntry::LAST_ENTRY | 2);
+        assert_eq!(entries[1][0], 1);
+
+        let mut buffer = LfnBuffer::default();
+        for data in entries.iter() {
+            buffer.push(&LfnEntry::parse(data).unwrap());
+        }
+
+        assert_eq!(buffer.take(&sfn).as_deref(), Some(name));
+
+        // the checksum does not match another short name
+        for data in entries.iter() {
+            buffer.push(&LfnEntry::parse(data).unwrap());
+        }
+
+        assert_eq!(
+            buffer.take(&ShortFileName::parse("HELLO.TXT").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_needs_long_name() {
+        assert!(!needs_long_name("KERNEL.ELF"));
+        assert!(needs_long_name("kernel.elf"));
+        assert!(needs_long_name("hello_world"));
+        assert!(needs_long_name("archive.tar.gz"));
+    }
+}
@@ -3,11 +3,13 @@ pub mod directory;
 pub mod direntry;
 pub mod file;
 pub mod impls;
+pub mod lfn;
 
 use crate::*;
 use directory::Directory;
 use direntry::*;
 use file::File;
+use lfn::{LfnBuffer, LfnEntry};
 
 use bpb::Fat16Bpb;
 
@@ -24,16 +26,116 @@ impl Fat16 {
             handle: Arc::new(Fat16Impl::new(inner)),
         }
     }
+
+    pub(crate) fn from_handle(handle: Fat16Handle) -> Self {
+        Self { handle }
+    }
+
+    pub(crate) fn handle(&self) -> &Fat16Handle {
+        &self.handle
+    }
+}
+
+pub(crate) type Fat16Handle = Arc<Fat16Impl>;
+
+/// The FAT variants, they share the same directory and file layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat16,
+    Fat32,
 }
 
-type Fat16Handle = Arc<Fat16Impl>;
+impl FatType {
+    /// Determines the FAT type of the volume from its BPB,
+    /// which only depends on the count of clusters.
+    pub fn detect(inner: &impl BlockDevice<Block512>) -> Result<FatType> {
+        let mut block = Block::default();
+        inner.read_block(0, &mut block)?;
+
+        let bpb = Fat16Bpb::new(block.as_ref()).map_err(|_| FsError::NotSupported)?;
+
+        if bpb.bytes_per_sector() as usize != BLOCK_SIZE || bpb.sectors_per_cluster() == 0 {
+            return Err(FsError::NotSupported);
+        }
+
+        // the size of the FAT is only in the extended BPB of Fat32
+        if bpb.sectors_per_fat() == 0 {
+            return Ok(FatType::Fat32);
+        }
+
+        let root_dir_size =
+            (bpb.root_entries_count() as usize * DirEntry::LEN).div_ceil(BLOCK_SIZE);
+        let data_sectors = (bpb.total_sectors() as usize).saturating_sub(
+            bpb.reserved_sector_count() as usize
+                + bpb.fat_count() as usize * bpb.sectors_per_fat() as usize
+                + root_dir_size,
+        );
+
+        match data_sectors / bpb.sectors_per_cluster() as usize {
+            // Fat12 is not supported
+            0..4085 => Err(FsError::NotSupported),
+            4085..65525 => Ok(FatType::Fat16),
+            _ => Ok(FatType::Fat32),
+        }
+    }
+
+    /// The size of a FAT entry in bytes
+    pub fn entry_size(&self) -> usize {
+        match self {
+            FatType::Fat16 => 2,
+            FatType::Fat32 => 4,
+        }
+    }
+
+    /// The FAT entry value of a bad cluster,
+    /// any larger value marks the end of a cluster chain
+    pub fn bad_cluster(&self) -> u32 {
+        match self {
+            FatType::Fat16 => 0xFFF7,
+            FatType::Fat32 => 0x0FFF_FFF7,
+        }
+    }
+
+    /// The FAT entry value written at the end of a cluster chain
+    pub fn end_of_chain(&self) -> u32 {
+        match self {
+            FatType::Fat16 => 0xFFFF,
+            FatType::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+}
 
 pub struct Fat16Impl {
     pub(crate) inner: Box<dyn BlockDevice<Block512>>,
     pub bpb: Fat16Bpb,
+    pub fat_type: FatType,
+    pub sectors_per_fat: usize,
     pub fat_start: usize,
     pub first_data_sector: usize,
     pub first_root_dir_sector: usize,
+    /// The first cluster of the root directory,
+    /// `Cluster::ROOT_DIR` for the fixed root directory region of Fat16
+    pub root_cluster: Cluster,
+    /// The state specific to the FAT variant, kept by `Fat32`
+    pub ext: Option<Box<dyn FatExt>>,
+}
+
+/// The state of a volume specific to its FAT variant, the variant sets
+/// it up and passes it to `Fat16Impl`, which has none of it for Fat16
+pub trait FatExt: Send + Sync {
+    /// The only FAT copy in use, or None if all of them are mirrored
+    fn active_fat(&self) -> Option<usize>;
+
+    /// Returns the cluster to start looking for free clusters from
+    fn next_free_hint(&self, inner: &dyn BlockDevice<Block512>) -> usize;
+
+    /// Accounts `delta` free clusters, `allocated` is the last allocated one
+    fn update_free(
+        &self,
+        inner: &dyn BlockDevice<Block512>,
+        delta: isize,
+        allocated: Option<Cluster>,
+    ) -> Result<()>;
 }
 
 impl core::fmt::Debug for Fat16 {
@@ -49,3 +151,61 @@ impl core::fmt::Debug for Fat16Impl {
         f.debug_struct("Fat16Impl").field("bpb", &self.bpb).finish()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Formats a Fat16 volume with two FATs and 512 root entries
+    pub fn format(sectors: usize, sectors_per_cluster: u8) -> MemDisk {
+        let clusters = sectors / sectors_per_cluster as usize;
+        let sectors_per_fat = ((clusters + 2) * 2).div_ceil(BLOCK_SIZE);
+
+        let mut bpb = [0u8; BLOCK_SIZE];
+        bpb[0x00..0x03].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+        bpb[0x03..0x0b].copy_from_slice(b"GGOS    ");
+        bpb[0x0b..0x0d].copy_from_slice(&(BLOCK_SIZE as u16).to_le_bytes());
+        bpb[0x0d] = sectors_per_cluster;
+        bpb[0x0e..0x10].copy_from_slice(&1u16.to_le_bytes());
+        bpb[0x10] = 2;
+        bpb[0x11..0x13].copy_from_slice(&512u16.to_le_bytes());
+        bpb[0x15] = 0xF8;
+        bpb[0x16..0x18].copy_from_slice(&(sectors_per_fat as u16).to_le_bytes());
+        bpb[0x20..0x24].copy_from_slice(&(sectors as u32).to_le_bytes());
+        bpb[0x26] = 0x29;
+        bpb[0x2b..0x36].copy_from_slice(b"GGOS TEST  ");
+        bpb[0x36..0x3e].copy_from_slice(b"FAT16   ");
+        bpb[0x1fe..0x200].copy_from_slice(&[0x55, 0xAA]);
+
+        let mut blocks = vec![Block512::default(); sectors];
+        blocks[0] = Block512::new(&bpb);
+
+        // the two reserved entries of each FAT
+        for fat in 0..2 {
+            let block = blocks[1 + fat * sectors_per_fat].as_mut();
+            block[..4].copy_from_slice(&[0xF8, 0xFF, 0xFF, 0xFF]);
+        }
+
+        MemDisk::from_blocks(blocks)
+    }
+
+    /// Returns the clusters of the chain starting at `cluster`
+    pub fn cluster_chain(handle: &Fat16Impl, cluster: Cluster) -> Vec<Cluster> {
+        let mut chain = vec![cluster];
+
+        while let Ok(next) = handle.next_cluster(chain.last().unwrap()) {
+            chain.push(next);
+        }
+
+        chain
+    }
+
+    #[test]
+    fn test_fat_type_detect() {
+        assert_eq!(FatType::detect(&format(0x4000, 1)), Ok(FatType::Fat16));
+        assert_eq!(
+            FatType::detect(&format(0x800, 1)),
+            Err(FsError::NotSupported)
+        );
+    }
+}
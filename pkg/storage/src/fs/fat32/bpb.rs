@@ -0,0 +1,153 @@
+//! Fat32 BIOS Parameter Block
+//!
+//! reference:
+//! - <https://en.wikipedia.org/wiki/BIOS_parameter_block>
+//! - <https://wiki.osdev.org/FAT#FAT_32>
+//! - <https://github.com/rust-embedded-community/embedded-sdmmc-rs/blob/develop/src/fat.rs>
+
+/// Represents a Boot Parameter Block. This is the first sector of a FAT 32
+/// formatted partition, the fields before `sectors_per_fat_32` are shared
+/// with FAT 16, which are enough to tell the FAT type.
+pub struct Fat32Bpb {
+    data: [u8; 512],
+}
+
+impl Fat32Bpb {
+    /// Attempt to parse a Boot Parameter Block from a 512 byte sector.
+    pub fn new(data: &[u8]) -> Result<Fat32Bpb, &'static str> {
+        let data = data.try_into().map_err(|_| "Bad BPB size")?;
+        let bpb = Fat32Bpb { data };
+
+        if bpb.trail() != 0xAA55 {
+            return Err("Bad BPB format");
+        }
+
+        Ok(bpb)
+    }
+
+    pub fn total_sectors(&self) -> u32 {
+        if self.total_sectors_16() == 0 {
+            self.total_sectors_32()
+        } else {
+            self.total_sectors_16() as u32
+        }
+    }
+
+    pub fn sectors_per_fat(&self) -> u32 {
+        if self.sectors_per_fat_16() == 0 {
+            self.sectors_per_fat_32()
+        } else {
+            self.sectors_per_fat_16() as u32
+        }
+    }
+
+    define_field!([u8; 8], 0x03, oem_name);
+    define_field!(u16, 0x0b, bytes_per_sector);
+    define_field!(u8, 0x0d, sectors_per_cluster);
+    define_field!(u16, 0x0e, reserved_sector_count);
+    define_field!(u8, 0x10, fat_count);
+    define_field!(u16, 0x11, root_entries_count);
+    define_field!(u16, 0x13, total_sectors_16);
+    define_field!(u8, 0x15, media_descriptor);
+    define_field!(u16, 0x16, sectors_per_fat_16);
+    define_field!(u16, 0x18, sectors_per_track);
+    define_field!(u16, 0x1a, track_count);
+    define_field!(u32, 0x1c, hidden_sectors);
+    define_field!(u32, 0x20, total_sectors_32);
+    define_field!(u32, 0x24, sectors_per_fat_32);
+    define_field!(u16, 0x28, extended_flags);
+    define_field!(u16, 0x2a, fs_version);
+    define_field!(u32, 0x2c, root_cluster);
+    define_field!(u16, 0x30, fsinfo_sector);
+    define_field!(u16, 0x32, backup_boot_sector);
+    define_field!(u8, 0x40, drive_number);
+    define_field!(u8, 0x41, reserved_flags);
+    define_field!(u8, 0x42, boot_signature);
+    define_field!(u32, 0x43, volume_id);
+    define_field!([u8; 11], 0x47, volume_label);
+    define_field!([u8; 8], 0x52, system_identifier);
+    define_field!(u16, 0x1fe, trail);
+}
+
+impl core::fmt::Debug for Fat32Bpb {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Fat32 BPB")
+            .field("OEM Name", &self.oem_name_str())
+            .field("Bytes per Sector", &self.bytes_per_sector())
+            .field("Sectors per Cluster", &self.sectors_per_cluster())
+            .field("Reserved Sector Count", &self.reserved_sector_count())
+            .field("FAT Count", &self.fat_count())
+            .field("Total Sectors", &self.total_sectors())
+            .field("Media Descriptor", &self.media_descriptor())
+            .field("Sectors per FAT", &self.sectors_per_fat())
+            .field("Sectors per Track", &self.sectors_per_track())
+            .field("Track Count", &self.track_count())
+            .field("Hidden Sectors", &self.hidden_sectors())
+            .field("Extended Flags", &self.extended_flags())
+            .field("FS Version", &self.fs_version())
+            .field("Root Cluster", &self.root_cluster())
+            .field("FSInfo Sector", &self.fsinfo_sector())
+            .field("Backup Boot Sector", &self.backup_boot_sector())
+            .field("Drive Number", &self.drive_number())
+            .field("Reserved Flags", &self.reserved_flags())
+            .field("Boot Signature", &self.boot_signature())
+            .field("Volume ID", &self.volume_id())
+            .field("Volume Label", &self.volume_label_str())
+            .field("System Identifier", &self.system_identifier_str())
+            .field("Trail", &self.trail())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fat32_bpb() {
+        // Boot sector of a 512 MiB FAT32 volume
+        const DATA: [u8; 96] = hex_literal::hex!(
+            "EB 58 90 6D 6B 66 73 2E 66 61 74 00 02 08 20 00
+        02 00 00 00 00 F8 00 00 3F 00 FF 00 00 08 00 00
+        00 00 10 00 F8 03 00 00 00 00 00 00 02 00 00 00
+        01 00 06 00 00 00 00 00 00 00 00 00 00 00 00 00
+        80 00 29 A4 1B 3C 58 47 47 4F 53 20 20 20 20 20
+        20 20 46 41 54 33 32 20 20 20 0E 1F BE 77 7C AC"
+        );
+
+        const PADDING: &[u8] = concat_bytes!([0x00; 414], [0x55, 0xAA]);
+
+        let mut bpb_data = DATA.to_vec();
+        bpb_data.extend_from_slice(PADDING);
+
+        let bpb = Fat32Bpb::new(&bpb_data).unwrap();
+
+        assert_eq!(bpb.oem_name(), b"mkfs.fat");
+        assert_eq!(bpb.bytes_per_sector(), 512);
+        assert_eq!(bpb.sectors_per_cluster(), 8);
+        assert_eq!(bpb.reserved_sector_count(), 32);
+        assert_eq!(bpb.fat_count(), 2);
+        assert_eq!(bpb.root_entries_count(), 0);
+        assert_eq!(bpb.total_sectors_16(), 0);
+        assert_eq!(bpb.media_descriptor(), 0xf8);
+        assert_eq!(bpb.sectors_per_fat_16(), 0);
+        assert_eq!(bpb.hidden_sectors(), 0x800);
+        assert_eq!(bpb.total_sectors_32(), 0x100000);
+        assert_eq!(bpb.sectors_per_fat_32(), 0x3f8);
+        assert_eq!(bpb.extended_flags(), 0);
+        assert_eq!(bpb.fs_version(), 0);
+        assert_eq!(bpb.root_cluster(), 2);
+        assert_eq!(bpb.fsinfo_sector(), 1);
+        assert_eq!(bpb.backup_boot_sector(), 6);
+        assert_eq!(bpb.drive_number(), 0x80);
+        assert_eq!(bpb.boot_signature(), 0x29);
+        assert_eq!(bpb.volume_id(), 0x583c1ba4);
+        assert_eq!(bpb.volume_label(), b"GGOS       ");
+        assert_eq!(bpb.system_identifier(), b"FAT32   ");
+
+        assert_eq!(bpb.total_sectors(), 0x100000);
+        assert_eq!(bpb.sectors_per_fat(), 0x3f8);
+
+        println!("{:#?}", bpb);
+    }
+}
@@ -0,0 +1,62 @@
+//! Fat32 FSInfo Structure
+//!
+//! reference:
+//! - <https://wiki.osdev.org/FAT#FSInfo_Structure_.28FAT32_only.29>
+//! - <https://en.wikipedia.org/wiki/Design_of_the_FAT_file_system#FS_Information_Sector>
+
+/// Represents the FSInfo sector of a FAT 32 volume, which keeps hints
+/// of the free cluster count and the next free cluster.
+pub struct FsInfo {
+    data: [u8; 512],
+}
+
+impl FsInfo {
+    const LEAD_SIGNATURE: u32 = 0x4161_5252;
+    const STRUCT_SIGNATURE: u32 = 0x6141_7272;
+    const TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+
+    /// The value of a hint that is not known
+    pub const UNKNOWN: u32 = 0xFFFF_FFFF;
+
+    /// Attempt to parse a FSInfo structure from a 512 byte sector.
+    pub fn new(data: &[u8]) -> Result<FsInfo, &'static str> {
+        let data = data.try_into().map_err(|_| "Bad FSInfo size")?;
+        let fsinfo = FsInfo { data };
+
+        if fsinfo.lead_signature() != Self::LEAD_SIGNATURE
+            || fsinfo.struct_signature() != Self::STRUCT_SIGNATURE
+            || fsinfo.trail_signature() != Self::TRAIL_SIGNATURE
+        {
+            return Err("Bad FSInfo format");
+        }
+
+        Ok(fsinfo)
+    }
+
+    pub fn set_free_count(&mut self, count: u32) {
+        self.data[0x1e8..0x1ec].copy_from_slice(&count.to_le_bytes());
+    }
+
+    pub fn set_next_free(&mut self, cluster: u32) {
+        self.data[0x1ec..0x1f0].copy_from_slice(&cluster.to_le_bytes());
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 512] {
+        &self.data
+    }
+
+    define_field!(u32, 0x000, lead_signature);
+    define_field!(u32, 0x1e4, struct_signature);
+    define_field!(u32, 0x1e8, free_count);
+    define_field!(u32, 0x1ec, next_free);
+    define_field!(u32, 0x1fc, trail_signature);
+}
+
+impl core::fmt::Debug for FsInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Fat32 FSInfo")
+            .field("Free Count", &self.free_count())
+            .field("Next Free", &self.next_free())
+            .finish()
+    }
+}
@@ -0,0 +1,363 @@
+pub mod bpb;
+pub mod fsinfo;
+
+use crate::*;
+use bpb::Fat32Bpb;
+use fat16::bpb::Fat16Bpb;
+use fat16::direntry::Cluster;
+use fat16::*;
+use fsinfo::FsInfo;
+
+/// Identifies a Fat32 Volume on the disk.
+///
+/// Directories and files are laid out the same way as in Fat16, so the
+/// volume is driven by `Fat16Impl` set up for 28-bit FAT entries and a
+/// root directory that is a cluster chain, along with a `Fat32Impl`.
+pub struct Fat32 {
+    bpb: Fat32Bpb,
+    inner: Fat16,
+}
+
+/// The Fat32 specific parts of a volume, passed to `Fat16Impl`
+pub struct Fat32Impl {
+    /// The sector of the FSInfo structure
+    pub fsinfo_sector: Option<usize>,
+    /// The only FAT in use when mirroring is disabled
+    pub active_fat: Option<usize>,
+}
+
+impl Fat32Impl {
+    /// Bit 7 of the extended flags disables FAT mirroring
+    const NO_MIRRORING: u16 = 0x80;
+    /// The low 4 bits of the extended flags select the active FAT
+    const ACTIVE_FAT_MASK: u16 = 0x0F;
+
+    pub fn new(bpb: &Fat32Bpb, inner: &impl BlockDevice<Block512>) -> Self {
+        let mut block = Block::default();
+
+        let fsinfo_sector = match bpb.fsinfo_sector() as usize {
+            0 | 0xFFFF => None,
+            sector => inner
+                .read_block(sector, &mut block)
+                .ok()
+                .and_then(|_| FsInfo::new(block.as_ref()).ok())
+                .map(|fsinfo| {
+                    trace!("Fat32 FSInfo: {:?}", fsinfo);
+                    sector
+                }),
+        };
+
+        let flags = bpb.extended_flags();
+        let active_fat = (flags & Self::NO_MIRRORING != 0)
+            .then_some((flags & Self::ACTIVE_FAT_MASK) as usize)
+            .filter(|&fat| fat < bpb.fat_count() as usize);
+
+        Self {
+            fsinfo_sector,
+            active_fat,
+        }
+    }
+
+    fn read_fsinfo(&self, inner: &dyn BlockDevice<Block512>) -> Option<FsInfo> {
+        let mut block = Block::default();
+        inner.read_block(self.fsinfo_sector?, &mut block).ok()?;
+        FsInfo::new(block.as_ref()).ok()
+    }
+}
+
+impl FatExt for Fat32Impl {
+    fn active_fat(&self) -> Option<usize> {
+        self.active_fat
+    }
+
+    fn next_free_hint(&self, inner: &dyn BlockDevice<Block512>) -> usize {
+        self.read_fsinfo(inner)
+            .map(|fsinfo| fsinfo.next_free())
+            .filter(|&next| next != FsInfo::UNKNOWN && next >= 2)
+            .map_or(2, |next| next as usize)
+    }
+
+    /// Updates the free cluster count and the next free cluster
+    /// hint in the FSInfo structure, if the volume has one
+    fn update_free(
+        &self,
+        inner: &dyn BlockDevice<Block512>,
+        delta: isize,
+        allocated: Option<Cluster>,
+    ) -> Result<()> {
+        let (Some(sector), Some(mut fsinfo)) = (self.fsinfo_sector, self.read_fsinfo(inner)) else {
+            return Ok(());
+        };
+
+        let free_count = fsinfo.free_count();
+        if free_count != FsInfo::UNKNOWN {
+            fsinfo.set_free_count(free_count.saturating_add_signed(delta as i32));
+        }
+
+        if let Some(cluster) = allocated {
+            fsinfo.set_next_free(cluster.0 + 1);
+        }
+
+        let mut block = Block::default();
+        block.as_mut().copy_from_slice(fsinfo.as_bytes());
+        inner.write_block(sector, &block)
+    }
+}
+
+impl Fat32 {
+    pub fn new(inner: impl BlockDevice<Block512>) -> Self {
+        let mut block = Block::default();
+
+        inner.read_block(0, &mut block).unwrap();
+        let bpb = Fat32Bpb::new(block.as_ref()).unwrap();
+        // the fields shared with Fat16
+        let common_bpb = Fat16Bpb::new(block.as_ref()).unwrap();
+
+        trace!("Loading Fat32 Volume: {:#?}", bpb);
+
+        let sectors_per_fat = bpb.sectors_per_fat() as usize;
+        let fat_start = bpb.reserved_sector_count() as usize;
+        let first_data_sector = fat_start + bpb.fat_count() as usize * sectors_per_fat;
+        let root_cluster = Cluster(bpb.root_cluster());
+        let fat32 = Fat32Impl::new(&bpb, &inner);
+
+        let handle = Fat16Impl {
+            bpb: common_bpb,
+            inner: Box::new(inner),
+            fat_type: FatType::Fat32,
+            sectors_per_fat,
+            fat_start,
+            first_data_sector,
+            // there is no fixed root directory region in Fat32
+            first_root_dir_sector: first_data_sector,
+            root_cluster,
+            ext: Some(Box::new(fat32)),
+        };
+
+        Self {
+            bpb,
+            inner: Fat16::from_handle(Arc::new(handle)),
+        }
+    }
+}
+
+impl core::fmt::Debug for Fat32 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Fat32").field("bpb", &self.bpb).finish()
+    }
+}
+
+impl FileSystem for Fat32 {
+    fn read_dir(&self, path: &str) -> Result<Box<dyn Iterator<Item = Metadata> + Send>> {
+        self.inner.read_dir(path)
+    }
+
+    fn open_file(&self, path: &str) -> Result<FileHandle> {
+        self.inner.open_file(path)
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        self.inner.exists(path)
+    }
+
+    fn create_file(&self, path: &str) -> Result<FileHandle> {
+        self.inner.create_file(path)
+    }
+
+    fn create_dir(&self, path: &str) -> Result<()> {
+        self.inner.create_dir(path)
+    }
+
+    fn append_file(&self, path: &str) -> Result<FileHandle> {
+        self.inner.append_file(path)
+    }
+
+    fn set_len(&self, path: &str, len: usize) -> Result<()> {
+        self.inner.set_len(path, len)
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> Result<()> {
+        self.inner.copy_file(src, dst)
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> Result<()> {
+        self.inner.move_file(src, dst)
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> Result<()> {
+        self.inner.move_dir(src, dst)
+    }
+
+    fn fs_type(&self) -> &'static str {
+        "fat32"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESERVED_SECTORS: usize = 32;
+    const FSINFO_SECTOR: usize = 1;
+
+    /// Formats a Fat32 volume with two FATs, an FSInfo structure
+    /// and a root directory of one cluster
+    fn format(sectors: usize, extended_flags: u16) -> MemDisk {
+        let clusters = sectors - RESERVED_SECTORS;
+        let sectors_per_fat = ((clusters + 2) * 4).div_ceil(512);
+        let data_clusters = sectors - RESERVED_SECTORS - 2 * sectors_per_fat;
+
+        let mut bpb = [0u8; 512];
+        bpb[0x00..0x03].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        bpb[0x03..0x0b].copy_from_slice(b"GGOS    ");
+        bpb[0x0b..0x0d].copy_from_slice(&512u16.to_le_bytes());
+        bpb[0x0d] = 1;
+        bpb[0x0e..0x10].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+        bpb[0x10] = 2;
+        bpb[0x15] = 0xF8;
+        bpb[0x20..0x24].copy_from_slice(&(sectors as u32).to_le_bytes());
+        bpb[0x24..0x28].copy_from_slice(&(sectors_per_fat as u32).to_le_bytes());
+        bpb[0x28..0x2a].copy_from_slice(&extended_flags.to_le_bytes());
+        bpb[0x2c..0x30].copy_from_slice(&2u32.to_le_bytes());
+        bpb[0x30..0x32].copy_from_slice(&(FSINFO_SECTOR as u16).to_le_bytes());
+        bpb[0x42] = 0x29;
+        bpb[0x47..0x52].copy_from_slice(b"GGOS TEST  ");
+        bpb[0x52..0x5a].copy_from_slice(b"FAT32   ");
+        bpb[0x1fe..0x200].copy_from_slice(&[0x55, 0xAA]);
+
+        let mut fsinfo = [0u8; 512];
+        fsinfo[0x000..0x004].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+        fsinfo[0x1e4..0x1e8].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+        // the root directory takes the first cluster
+        fsinfo[0x1e8..0x1ec].copy_from_slice(&(data_clusters as u32 - 1).to_le_bytes());
+        fsinfo[0x1ec..0x1f0].copy_from_slice(&3u32.to_le_bytes());
+        fsinfo[0x1fc..0x200].copy_from_slice(&0xAA55_0000u32.to_le_bytes());
+
+        let mut blocks = vec![Block512::default(); sectors];
+        blocks[0] = Block512::new(&bpb);
+        blocks[FSINFO_SECTOR] = Block512::new(&fsinfo);
+
+        // the two reserved entries and the root directory
+        for fat in 0..2 {
+            let block = blocks[RESERVED_SECTORS + fat * sectors_per_fat].as_mut();
+            block[0x0..0x4].copy_from_slice(&0x0FFF_FFF8u32.to_le_bytes());
+            block[0x4..0x8].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+            block[0x8..0xc].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+        }
+
+        MemDisk::from_blocks(blocks)
+    }
+
+    fn read_fsinfo(disk: &MemDisk) -> FsInfo {
+        FsInfo::new(disk.0.lock()[FSINFO_SECTOR].as_ref()).unwrap()
+    }
+
+    /// Returns the first sector of each FAT
+    fn fat_sectors(disk: &MemDisk) -> (Block512, Block512) {
+        let blocks = disk.0.lock();
+        let bpb = Fat32Bpb::new(blocks[0].as_ref()).unwrap();
+        let second = RESERVED_SECTORS + bpb.sectors_per_fat() as usize;
+        (blocks[RESERVED_SECTORS].clone(), blocks[second].clone())
+    }
+
+    fn write_file(fs: &Fat32, path: &str, data: &[u8]) {
+        let mut file = fs.create_file(path).unwrap();
+        file.write_all(data).unwrap();
+    }
+
+    fn read_file(fs: &Fat32, path: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        fs.open_file(path).unwrap().read_all(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_fat32_files() {
+        let disk = format(70000, 0);
+        assert_eq!(FatType::detect(&disk), Ok(FatType::Fat32));
+
+        let fs = Fat32::new(disk);
+        let handle = fs.inner.handle();
+
+        fs.create_dir("/dir").unwrap();
+        write_file(&fs, "/dir/long file name.txt", &[0x33; 1500]);
+        assert_eq!(read_file(&fs, "/dir/long file name.txt"), [0x33; 1500]);
+
+        // the root directory is a cluster chain which grows as well
+        for i in 0..20 {
+            write_file(&fs, &format!("/F{}.TXT", i), &[i as u8]);
+        }
+        let root = crate::fat16::tests::cluster_chain(handle, handle.root_cluster);
+        assert_eq!(root.len(), 2);
+
+        for i in 0..20 {
+            assert_eq!(read_file(&fs, &format!("/F{}.TXT", i)), [i as u8]);
+        }
+        assert_eq!(fs.read_dir("/").unwrap().count(), 21);
+
+        fs.remove_file("/dir/long file name.txt").unwrap();
+        fs.remove_dir("/dir").unwrap();
+        assert!(!fs.exists("/dir").unwrap());
+    }
+
+    #[test]
+    fn test_fat32_fsinfo() {
+        let disk = format(70000, 0);
+        let fs = Fat32::new(disk.clone());
+
+        let free_count = read_fsinfo(&disk).free_count();
+
+        // three clusters from the next free hint on
+        write_file(&fs, "/DATA.BIN", &[0x5a; 1500]);
+        let fsinfo = read_fsinfo(&disk);
+        assert_eq!(fsinfo.free_count(), free_count - 3);
+        assert_eq!(fsinfo.next_free(), 6);
+
+        fs.remove_file("/DATA.BIN").unwrap();
+        assert_eq!(read_fsinfo(&disk).free_count(), free_count);
+
+        // the allocation starts from the hint
+        let mut fsinfo = read_fsinfo(&disk);
+        fsinfo.set_next_free(1000);
+        disk.0.lock()[FSINFO_SECTOR] = Block512::new(fsinfo.as_bytes());
+
+        write_file(&fs, "/DATA.BIN", &[0x5a; 10]);
+        let entry = fs.metadata("/DATA.BIN").unwrap();
+        assert_eq!(entry.len, 10);
+        assert_eq!(read_fsinfo(&disk).next_free(), 1001);
+    }
+
+    #[test]
+    fn test_fat32_mirroring() {
+        // both FATs are kept in sync by default
+        let disk = format(70000, 0);
+        let fs = Fat32::new(disk.clone());
+        write_file(&fs, "/DATA.BIN", &[0x5a; 1500]);
+
+        let (first, second) = fat_sectors(&disk);
+        assert_eq!(first.as_ref(), second.as_ref());
+
+        // only the second FAT is in use when mirroring is disabled
+        let disk = format(70000, 0x81);
+        let (first, _) = fat_sectors(&disk);
+        let fs = Fat32::new(disk.clone());
+        write_file(&fs, "/DATA.BIN", &[0x5a; 1500]);
+
+        let (unused, active) = fat_sectors(&disk);
+        assert_eq!(unused.as_ref(), first.as_ref());
+        assert_ne!(active.as_ref(), first.as_ref());
+        assert_eq!(read_file(&fs, "/DATA.BIN"), [0x5a; 1500]);
+    }
+}
@@ -1,2 +1,6 @@
+pub mod cpio;
+pub mod devfs;
 pub mod fat16;
+pub mod fat32;
+pub mod ramfs;
 pub mod random;
@@ -0,0 +1,80 @@
+//! File of the RamFs, opened handles share the data with the filesystem
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub struct File {
+    /// The current offset in the file.
+    pub offset: usize,
+    /// The inode of this file, kept alive even if the file is removed
+    inode: Arc<RwLock<Inode>>,
+}
+
+impl File {
+    pub fn new(inode: Arc<RwLock<Inode>>) -> Self {
+        Self { offset: 0, inode }
+    }
+
+    pub fn length(&self) -> usize {
+        self.inode.read().data.len()
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut inode = self.inode.write();
+        let data = &inode.data;
+
+        if self.offset >= data.len() {
+            return Ok(0);
+        }
+
+        let to_read = buf.len().min(data.len() - self.offset);
+        buf[..to_read].copy_from_slice(&data[self.offset..self.offset + to_read]);
+
+        self.offset += to_read;
+        inode.times.accessed = current_time();
+
+        Ok(to_read)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut inode = self.inode.write();
+        let end = self.offset + buf.len();
+
+        // writing beyond the end fills the gap with zeros
+        if inode.data.len() < end {
+            inode.data.resize(end, 0);
+        }
+
+        inode.data[self.offset..end].copy_from_slice(buf);
+
+        self.offset = end;
+        inode.times.modified = current_time();
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> Result<usize> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::End(offset) => self.length() as isize + offset,
+            SeekFrom::Current(offset) => self.offset as isize + offset,
+        };
+
+        if offset < 0 {
+            return Err(FsError::InvalidOffset);
+        }
+
+        self.offset = offset as usize;
+        Ok(self.offset)
+    }
+}
@@ -0,0 +1,435 @@
+//! In-memory Filesystem
+//!
+//! Keeps the whole tree in the heap, nothing survives a reboot.
+//! It is usually mounted at `/tmp` to exchange files between processes.
+
+mod file;
+
+use crate::*;
+use alloc::collections::BTreeMap;
+use file::File;
+use spin::RwLock;
+
+#[derive(Debug, Clone, Copy)]
+struct Times {
+    created: FsTime,
+    modified: FsTime,
+    accessed: FsTime,
+}
+
+impl Times {
+    fn now() -> Self {
+        let now = current_time();
+        Self {
+            created: now,
+            modified: now,
+            accessed: now,
+        }
+    }
+
+    fn as_meta(&self, name: &str, entry_type: FileType, len: usize) -> Metadata {
+        Metadata::new(
+            name.into(),
+            entry_type,
+            len,
+            Some(self.created),
+            Some(self.modified),
+            Some(self.accessed),
+        )
+    }
+}
+
+/// The content of a file, shared by the opened handles
+#[derive(Debug)]
+pub struct Inode {
+    data: Vec<u8>,
+    times: Times,
+}
+
+#[derive(Debug)]
+enum Entry {
+    File(Arc<RwLock<Inode>>),
+    Directory(Directory),
+}
+
+impl Entry {
+    fn as_meta(&self, name: &str) -> Metadata {
+        match self {
+            Entry::File(inode) => {
+                let inode = inode.read();
+                inode.times.as_meta(name, FileType::File, inode.data.len())
+            }
+            Entry::Directory(dir) => dir.times.as_meta(name, FileType::Directory, 0),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Directory {
+    entries: BTreeMap<String, Entry>,
+    times: Times,
+}
+
+impl Directory {
+    fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            times: Times::now(),
+        }
+    }
+
+    fn touch(&mut self) {
+        self.times.modified = current_time();
+    }
+
+    /// Walks down to the directory of the components
+    fn walk(&self, components: &[&str]) -> Result<&Directory> {
+        components.iter().try_fold(self, |dir, name| {
+            match dir.entries.get(*name).ok_or(FsError::FileNotFound)? {
+                Entry::Directory(dir) => Ok(dir),
+                Entry::File(_) => Err(FsError::NotADirectory),
+            }
+        })
+    }
+
+    fn walk_mut(&mut self, components: &[&str]) -> Result<&mut Directory> {
+        components.iter().try_fold(self, |dir, name| {
+            match dir.entries.get_mut(*name).ok_or(FsError::FileNotFound)? {
+                Entry::Directory(dir) => Ok(dir),
+                Entry::File(_) => Err(FsError::NotADirectory),
+            }
+        })
+    }
+
+    fn get(&self, path: &str) -> Result<&Entry> {
+        let (parent, name) = split_path(path)?;
+
+        self.walk(&parent)?
+            .entries
+            .get(name)
+            .ok_or(FsError::FileNotFound)
+    }
+
+    /// Inserts the entry at the path, fails if it already exists
+    fn insert(&mut self, path: &str, entry: Entry) -> Result<()> {
+        let (parent, name) = split_path(path)?;
+        let dir = self.walk_mut(&parent)?;
+
+        if dir.entries.contains_key(name) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        dir.entries.insert(name.into(), entry);
+        dir.touch();
+
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &str) -> Result<Entry> {
+        let (parent, name) = split_path(path)?;
+        let dir = self.walk_mut(&parent)?;
+        let entry = dir.entries.remove(name).ok_or(FsError::FileNotFound)?;
+
+        dir.touch();
+
+        Ok(entry)
+    }
+
+    /// Moves the entry at `src` to `dst`, fails if `dst` already exists
+    fn move_entry(&mut self, src: &str, dst: &str, is_dir: bool) -> Result<()> {
+        match self.get(src)? {
+            Entry::Directory(_) if !is_dir => return Err(FsError::NotAFile),
+            Entry::File(_) if is_dir => return Err(FsError::NotADirectory),
+            _ => {}
+        }
+
+        let (mut src_components, src_name) = split_path(src)?;
+        let (dst_parent, dst_name) = split_path(dst)?;
+
+        if self.walk(&dst_parent)?.entries.contains_key(dst_name) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        // cannot move a directory into itself
+        src_components.push(src_name);
+        if is_dir && dst_parent.starts_with(&src_components) {
+            return Err(FsError::InvalidOperation);
+        }
+
+        let entry = self.remove(src)?;
+        self.insert(dst, entry)
+    }
+}
+
+/// Splits the path into the parent components and the entry name
+fn split_path(path: &str) -> Result<(Vec<&str>, &str)> {
+    let path = path.trim_end_matches(PATH_SEPARATOR);
+    let (parent, name) = path.rsplit_once(PATH_SEPARATOR).unwrap_or(("", path));
+
+    if matches!(name, "" | "." | "..") {
+        return Err(FsError::InvalidPath(path.into()));
+    }
+
+    let parent = parent
+        .split(PATH_SEPARATOR)
+        .filter(|c| !c.is_empty())
+        .collect();
+
+    Ok((parent, name))
+}
+
+pub struct RamFs {
+    root: RwLock<Directory>,
+}
+
+impl RamFs {
+    pub fn new() -> Self {
+        Self {
+            root: RwLock::new(Directory::new()),
+        }
+    }
+}
+
+impl Default for RamFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for RamFs {
+    fn read_dir(&self, path: &str) -> Result<Box<dyn Iterator<Item = Metadata> + Send>> {
+        let components: Vec<_> = path
+            .split(PATH_SEPARATOR)
+            .filter(|c| !c.is_empty())
+            .collect();
+
+        let root = self.root.read();
+        let entries: Vec<_> = root
+            .walk(&components)?
+            .entries
+            .iter()
+            .map(|(name, entry)| entry.as_meta(name))
+            .collect();
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn open_file(&self, path: &str) -> Result<FileHandle> {
+        let (_, name) = split_path(path)?;
+        let root = self.root.read();
+        let entry = root.get(path)?;
+
+        match entry {
+            Entry::File(inode) => Ok(FileHandle::new(
+                entry.as_meta(name),
+                Box::new(File::new(inode.clone())),
+            )),
+            Entry::Directory(_) => Err(FsError::NotAFile),
+        }
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata> {
+        let root = self.root.read();
+
+        if path.trim_matches(PATH_SEPARATOR).is_empty() {
+            return Ok(root.times.as_meta("", FileType::Directory, 0));
+        }
+
+        let (_, name) = split_path(path)?;
+        Ok(root.get(path)?.as_meta(name))
+    }
+
+    fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.metadata(path).is_ok())
+    }
+
+    fn create_file(&self, path: &str) -> Result<FileHandle> {
+        let inode = Arc::new(RwLock::new(Inode {
+            data: Vec::new(),
+            times: Times::now(),
+        }));
+
+        self.root.write().insert(path, Entry::File(inode.clone()))?;
+
+        let (_, name) = split_path(path)?;
+        let meta = Entry::File(inode.clone()).as_meta(name);
+
+        Ok(FileHandle::new(meta, Box::new(File::new(inode))))
+    }
+
+    fn create_dir(&self, path: &str) -> Result<()> {
+        self.root
+            .write()
+            .insert(path, Entry::Directory(Directory::new()))
+    }
+
+    fn append_file(&self, path: &str) -> Result<FileHandle> {
+        let mut file = self.open_file(path)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(file)
+    }
+
+    fn set_len(&self, path: &str, len: usize) -> Result<()> {
+        match self.root.read().get(path)? {
+            Entry::File(inode) => {
+                let mut inode = inode.write();
+                inode.data.resize(len, 0);
+                inode.times.modified = current_time();
+                Ok(())
+            }
+            Entry::Directory(_) => Err(FsError::NotAFile),
+        }
+    }
+
+    fn remove_file(&self, path: &str) -> Result<()> {
+        let mut root = self.root.write();
+
+        if let Entry::Directory(_) = root.get(path)? {
+            return Err(FsError::NotAFile);
+        }
+
+        // opened handles keep the inode alive
+        root.remove(path)?;
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<()> {
+        let mut root = self.root.write();
+
+        match root.get(path)? {
+            Entry::File(_) => return Err(FsError::NotADirectory),
+            Entry::Directory(dir) if !dir.entries.is_empty() => {
+                return Err(FsError::DirectoryNotEmpty);
+            }
+            _ => {}
+        }
+
+        root.remove(path)?;
+        Ok(())
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> Result<()> {
+        let mut root = self.root.write();
+
+        let data = match root.get(src)? {
+            Entry::File(inode) => inode.read().data.clone(),
+            Entry::Directory(_) => return Err(FsError::NotAFile),
+        };
+
+        let inode = Inode {
+            data,
+            times: Times::now(),
+        };
+
+        root.insert(dst, Entry::File(Arc::new(RwLock::new(inode))))
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> Result<()> {
+        self.root.write().move_entry(src, dst, false)
+    }
+
+    fn move_dir(&self, src: &str, dst: &str) -> Result<()> {
+        self.root.write().move_entry(src, dst, true)
+    }
+
+    fn fs_type(&self) -> &'static str {
+        "ramfs"
+    }
+}
+
+impl core::fmt::Debug for RamFs {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RamFs")
+            .field("entries", &self.root.read().entries.keys())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_to_string(fs: &RamFs, path: &str) -> String {
+        let mut buf = Vec::new();
+        fs.open_file(path).unwrap().read_all(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_ramfs_files() {
+        let fs = RamFs::new();
+
+        let mut file = fs.create_file("/hello.txt").unwrap();
+        file.write_all(b"Hello, ").unwrap();
+        assert_eq!(
+            fs.create_file("/hello.txt").err(),
+            Some(FsError::AlreadyExists)
+        );
+
+        // opened handles see the same data
+        let mut file = fs.append_file("/hello.txt").unwrap();
+        file.write_all(b"GGOS!").unwrap();
+        assert_eq!(read_to_string(&fs, "/hello.txt"), "Hello, GGOS!");
+        assert_eq!(fs.metadata("/hello.txt").unwrap().len, 12);
+
+        fs.copy_file("/hello.txt", "/copy.txt").unwrap();
+        fs.move_file("/hello.txt", "/moved.txt").unwrap();
+        assert!(!fs.exists("/hello.txt").unwrap());
+        assert_eq!(read_to_string(&fs, "/moved.txt"), "Hello, GGOS!");
+
+        fs.remove_file("/moved.txt").unwrap();
+        assert_eq!(
+            fs.open_file("/moved.txt").err(),
+            Some(FsError::FileNotFound)
+        );
+
+        // the removed file is still usable through the handle
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut buf = [0u8; 5];
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"Hello");
+
+        let names: Vec<_> = fs.read_dir("/").unwrap().map(|m| m.name).collect();
+        assert_eq!(names, vec!["copy.txt"]);
+
+        fs.set_len("/copy.txt", 5).unwrap();
+        assert_eq!(read_to_string(&fs, "/copy.txt"), "Hello");
+        fs.set_len("/copy.txt", 7).unwrap();
+        assert_eq!(read_to_string(&fs, "/copy.txt"), "Hello\0\0");
+    }
+
+    #[test]
+    fn test_ramfs_dirs() {
+        let fs = RamFs::new();
+
+        fs.create_dir("/a").unwrap();
+        fs.create_dir("/a/b/").unwrap();
+        fs.create_file("/a/b/file").unwrap();
+
+        assert!(fs.metadata("/a/b").unwrap().is_dir());
+        assert_eq!(fs.create_dir("/x/y").err(), Some(FsError::FileNotFound));
+        assert_eq!(
+            fs.create_file("/a/b/file/c").err(),
+            Some(FsError::NotADirectory)
+        );
+        assert_eq!(
+            fs.remove_dir("/a/b").err(),
+            Some(FsError::DirectoryNotEmpty)
+        );
+        assert_eq!(fs.remove_file("/a").err(), Some(FsError::NotAFile));
+        assert_eq!(fs.open_file("/a").err(), Some(FsError::NotAFile));
+
+        assert_eq!(
+            fs.move_dir("/a", "/a/b/c").err(),
+            Some(FsError::InvalidOperation)
+        );
+        fs.move_dir("/a/b", "/c").unwrap();
+        assert!(fs.exists("/c/file").unwrap());
+        assert_eq!(fs.read_dir("/a").unwrap().count(), 0);
+
+        fs.remove_file("/c/file").unwrap();
+        fs.remove_dir("/c").unwrap();
+        fs.remove_dir("/a").unwrap();
+        assert_eq!(fs.read_dir("/").unwrap().count(), 0);
+    }
+}
@@ -15,7 +15,7 @@ impl Device<u8> for Random {
             for i in (0..size).step_by(8) {
                 if let Some(num) = rng.get_u64() {
                     for j in (i..min(i + 8, size)).rev() {
-                        buf[offset + j] = (num >> (j * 8)) as u8;
+                        buf[offset + j] = (num >> ((j - i) * 8)) as u8;
                     }
                 } else {
                     return Err(DeviceError::ReadError.into());
@@ -35,6 +35,29 @@ impl Device<u8> for Random {
     }
 }
 
+impl Read for Random {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Device::read(self, buf, 0, buf.len())
+    }
+}
+
+impl Write for Random {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        // written data is discarded
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Random {
+    fn seek(&mut self, _pos: SeekFrom) -> Result<usize> {
+        Ok(0)
+    }
+}
+
 impl Random {
     pub fn new() -> Self {
         if !GLOBAL_RNG.is_completed() {
@@ -39,3 +39,11 @@ use alloc::vec::Vec;
 //     The BPB contains information about the filesystem.
 //
 //     [ Fat16 BPB ] [ Data ]
+//
+// 3. The partition structure (in Fat32)
+//
+//    - Same as Fat16, but the FAT entries are 28-bit wide and the root
+//     directory is a cluster chain in the data region.
+//     The FSInfo sector keeps hints of the free clusters.
+//
+//     [ Fat32 BPB ] [ FSInfo ] [ FATs ] [ Data ]
@@ -0,0 +1,73 @@
+//! GPT Partition Entry
+//!
+//! Entries are at least 128 bytes, any extra bytes are ignored.
+
+use super::*;
+
+#[derive(Clone, Copy)]
+pub struct GptPartition {
+    data: [u8; GptPartition::LEN],
+}
+
+impl GptPartition {
+    pub const LEN: usize = 128;
+
+    pub fn parse(data: &[u8; GptPartition::LEN]) -> GptPartition {
+        GptPartition {
+            data: data.to_owned(),
+        }
+    }
+
+    define_field!([u8; 16], 0x00, type_guid_bytes);
+    define_field!([u8; 16], 0x10, unique_guid_bytes);
+    define_field!(u64, 0x20, first_lba);
+    define_field!(u64, 0x28, last_lba);
+    define_field!(u64, 0x30, attributes);
+    define_field!([u8; 72], 0x38, name_bytes);
+
+    pub fn type_guid(&self) -> Guid {
+        Guid::from_bytes(self.type_guid_bytes())
+    }
+
+    pub fn unique_guid(&self) -> Guid {
+        Guid::from_bytes(self.unique_guid_bytes())
+    }
+
+    /// Unused entries have a zeroed type GUID
+    pub fn is_used(&self) -> bool {
+        !self.type_guid().is_unused()
+    }
+
+    /// The number of blocks in the partition, the last LBA is inclusive
+    pub fn total_lba(&self) -> u64 {
+        (self.last_lba() + 1).saturating_sub(self.first_lba())
+    }
+
+    /// The partition name, stored in UTF-16LE
+    pub fn name(&self) -> String {
+        let chars = self
+            .name_bytes()
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .map(|&ch| u16::from_le_bytes(ch))
+            .take_while(|&ch| ch != 0);
+
+        char::decode_utf16(chars)
+            .map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+}
+
+impl core::fmt::Debug for GptPartition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GptPartition")
+            .field("type_guid", &self.type_guid())
+            .field("unique_guid", &self.unique_guid())
+            .field("first_lba", &self.first_lba())
+            .field("last_lba", &self.last_lba())
+            .field("attributes", &format!("0x{:016x}", self.attributes()))
+            .field("name", &self.name())
+            .finish()
+    }
+}
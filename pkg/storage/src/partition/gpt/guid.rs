@@ -0,0 +1,94 @@
+//! Globally Unique Identifier
+//!
+//! The first three fields are stored in little endian,
+//! the remaining 8 bytes are stored as is.
+
+use core::fmt::{Debug, Display, Formatter};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Guid([u8; 16]);
+
+impl Guid {
+    pub const UNUSED: Guid = Guid([0; 16]);
+
+    pub const EFI_SYSTEM: Guid = Guid::new(
+        0xC12A7328,
+        0xF81F,
+        0x11D2,
+        [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B],
+    );
+    pub const MICROSOFT_BASIC_DATA: Guid = Guid::new(
+        0xEBD0A0A2,
+        0xB9E5,
+        0x4433,
+        [0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7],
+    );
+    pub const LINUX_FILESYSTEM: Guid = Guid::new(
+        0x0FC63DAF,
+        0x8483,
+        0x4772,
+        [0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4],
+    );
+
+    pub const fn new(d1: u32, d2: u16, d3: u16, d4: [u8; 8]) -> Self {
+        let d1 = d1.to_le_bytes();
+        let d2 = d2.to_le_bytes();
+        let d3 = d3.to_le_bytes();
+
+        Self([
+            d1[0], d1[1], d1[2], d1[3], d2[0], d2[1], d3[0], d3[1], d4[0], d4[1], d4[2], d4[3],
+            d4[4], d4[5], d4[6], d4[7],
+        ])
+    }
+
+    pub fn from_bytes(bytes: &[u8; 16]) -> Self {
+        Self(*bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    pub fn is_unused(&self) -> bool {
+        *self == Self::UNUSED
+    }
+}
+
+impl Display for Guid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            u16::from_le_bytes([b[4], b[5]]),
+            u16::from_le_bytes([b[6], b[7]]),
+            b[8],
+            b[9]
+        )?;
+
+        b[10..]
+            .iter()
+            .try_for_each(|byte| write!(f, "{:02X}", byte))
+    }
+}
+
+impl Debug for Guid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guid() {
+        let data = hex_literal::hex!("28 73 2a c1 1f f8 d2 11 ba 4b 00 a0 c9 3e c9 3b");
+        let guid = Guid::from_bytes(&data);
+
+        assert_eq!(guid, Guid::EFI_SYSTEM);
+        assert_eq!(format!("{}", guid), "C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+    }
+}
@@ -0,0 +1,79 @@
+//! GPT Header
+//!
+//! The primary header is stored at LBA 1, the backup header at the last LBA.
+
+use super::*;
+
+pub struct GptHeader {
+    data: [u8; GptHeader::LEN],
+}
+
+impl GptHeader {
+    /// The size of the header defined by the revision 1.0
+    pub const LEN: usize = 92;
+    pub const SIGNATURE: &'static [u8; 8] = b"EFI PART";
+
+    /// Attempt to parse and validate the header from a block
+    pub fn parse(data: &[u8]) -> Result<GptHeader> {
+        let header = GptHeader {
+            data: data
+                .get(..Self::LEN)
+                .and_then(|data| data.try_into().ok())
+                .ok_or(FsError::InvalidOperation)?,
+        };
+
+        if header.signature() != Self::SIGNATURE {
+            return Err(FsError::InvalidOperation);
+        }
+
+        let header_size = header.header_size() as usize;
+        if header_size < Self::LEN || header_size > data.len() {
+            return Err(FsError::InvalidOperation);
+        }
+
+        // the CRC is calculated with the CRC field zeroed
+        let mut bytes = data[..header_size].to_vec();
+        bytes[16..20].fill(0);
+
+        if crc32(&bytes) != header.header_crc32() {
+            warn!("GPT header CRC mismatch at LBA {}", header.my_lba());
+            return Err(FsError::InvalidOperation);
+        }
+
+        Ok(header)
+    }
+
+    define_field!([u8; 8], 0x00, signature);
+    define_field!(u32, 0x08, revision);
+    define_field!(u32, 0x0c, header_size);
+    define_field!(u32, 0x10, header_crc32);
+    define_field!(u64, 0x18, my_lba);
+    define_field!(u64, 0x20, alternate_lba);
+    define_field!(u64, 0x28, first_usable_lba);
+    define_field!(u64, 0x30, last_usable_lba);
+    define_field!([u8; 16], 0x38, disk_guid_bytes);
+    define_field!(u64, 0x48, partition_entry_lba);
+    define_field!(u32, 0x50, num_partition_entries);
+    define_field!(u32, 0x54, partition_entry_size);
+    define_field!(u32, 0x58, partition_entry_array_crc32);
+
+    pub fn disk_guid(&self) -> Guid {
+        Guid::from_bytes(self.disk_guid_bytes())
+    }
+}
+
+impl core::fmt::Debug for GptHeader {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GptHeader")
+            .field("revision", &format!("0x{:08x}", self.revision()))
+            .field("my_lba", &self.my_lba())
+            .field("alternate_lba", &self.alternate_lba())
+            .field("first_usable_lba", &self.first_usable_lba())
+            .field("last_usable_lba", &self.last_usable_lba())
+            .field("disk_guid", &self.disk_guid())
+            .field("partition_entry_lba", &self.partition_entry_lba())
+            .field("num_partition_entries", &self.num_partition_entries())
+            .field("partition_entry_size", &self.partition_entry_size())
+            .finish()
+    }
+}
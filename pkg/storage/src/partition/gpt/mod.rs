@@ -0,0 +1,239 @@
+//! GptTable
+//!
+//! reference: <https://uefi.org/specs/UEFI/2.10/05_GUID_Partition_Table_Format.html>
+//! reference: <https://wiki.osdev.org/GPT>
+
+mod entry;
+mod guid;
+mod header;
+
+use core::marker::PhantomData;
+
+use crate::*;
+pub use entry::*;
+pub use guid::*;
+pub use header::*;
+
+pub struct GptTable<T, B>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    inner: T,
+    header: GptHeader,
+    entries: Vec<GptPartition>,
+    _block: PhantomData<B>,
+}
+
+impl<T, B> GptTable<T, B>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    pub fn header(&self) -> &GptHeader {
+        &self.header
+    }
+
+    /// Returns the used partition entries
+    pub fn entries(&self) -> &[GptPartition] {
+        &self.entries
+    }
+
+    /// Reads the header at `lba` and the partition entries it points to
+    fn read_table(inner: &T, lba: u64) -> Result<(GptHeader, Vec<GptPartition>)> {
+        let mut block = B::default();
+        inner.read_block(lba as usize, &mut block)?;
+
+        let header = GptHeader::parse(block.as_ref())?;
+
+        if header.my_lba() != lba {
+            return Err(FsError::InvalidOperation);
+        }
+
+        let entry_size = header.partition_entry_size() as usize;
+        if entry_size < GptPartition::LEN {
+            return Err(FsError::InvalidOperation);
+        }
+
+        let array_size = header.num_partition_entries() as usize * entry_size;
+        let mut array = Vec::with_capacity(array_size.next_multiple_of(B::size()));
+
+        for i in 0..array_size.div_ceil(B::size()) {
+            inner.read_block(header.partition_entry_lba() as usize + i, &mut block)?;
+            array.extend_from_slice(block.as_ref());
+        }
+
+        if crc32(&array[..array_size]) != header.partition_entry_array_crc32() {
+            warn!("GPT partition entries CRC mismatch at LBA {}", lba);
+            return Err(FsError::InvalidOperation);
+        }
+
+        let entries = array[..array_size]
+            .chunks_exact(entry_size)
+            .map(|data| GptPartition::parse(data[..GptPartition::LEN].try_into().unwrap()))
+            .filter(|entry| entry.is_used())
+            .collect();
+
+        Ok((header, entries))
+    }
+}
+
+impl<T, B> PartitionTable<T, B> for GptTable<T, B>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    fn parse(inner: T) -> Result<Self> {
+        let (header, entries) = match Self::read_table(&inner, 1) {
+            Ok(table) => table,
+            Err(err) => {
+                warn!("Invalid primary GPT ({:?}), trying the backup", err);
+                let last_lba = inner.block_count()?.saturating_sub(1);
+                Self::read_table(&inner, last_lba as u64)?
+            }
+        };
+
+        trace!("GPT header: {:#?}", header);
+
+        for (i, entry) in entries.iter().enumerate() {
+            trace!("Partition {}: {:#?}", i, entry);
+        }
+
+        Ok(Self {
+            inner,
+            header,
+            entries,
+            _block: PhantomData,
+        })
+    }
+
+    fn partitions(&self) -> Result<Vec<Partition<T, B>>> {
+        Ok(self
+            .entries
+            .iter()
+            .map(|entry| {
+                Partition::new(
+                    self.inner.clone(),
+                    entry.first_lba() as usize,
+                    entry.total_lba() as usize,
+                )
+            })
+            .collect())
+    }
+}
+
+/// The CRC32 used by GPT, the same one as in zlib and Ethernet
+pub fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut j = 0;
+            while j < 8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+                j += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !data.iter().fold(!0u32, |crc, &byte| {
+        TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    const BLOCKS: usize = 64;
+
+    /// Writes a GPT header at `lba` with 4 entries at `entry_lba`
+    fn write_header(disk: &mut [u8], lba: u64, alternate: u64, entry_lba: u64, entries: &[u8]) {
+        let mut header = [0u8; GptHeader::LEN];
+        header[0..8].copy_from_slice(GptHeader::SIGNATURE);
+        header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        header[12..16].copy_from_slice(&(GptHeader::LEN as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&lba.to_le_bytes());
+        header[32..40].copy_from_slice(&alternate.to_le_bytes());
+        header[40..48].copy_from_slice(&34u64.to_le_bytes());
+        header[48..56].copy_from_slice(&(BLOCKS as u64 - 34).to_le_bytes());
+        header[72..80].copy_from_slice(&entry_lba.to_le_bytes());
+        header[80..84].copy_from_slice(&4u32.to_le_bytes());
+        header[84..88].copy_from_slice(&(GptPartition::LEN as u32).to_le_bytes());
+        header[88..92].copy_from_slice(&crc32(entries).to_le_bytes());
+        let crc = crc32(&header);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+
+        let lba = lba as usize * 512;
+        let entry_lba = entry_lba as usize * 512;
+        disk[lba..lba + GptHeader::LEN].copy_from_slice(&header);
+        disk[entry_lba..entry_lba + entries.len()].copy_from_slice(entries);
+    }
+
+    fn gpt_disk() -> Vec<u8> {
+        let mut disk = vec![0u8; BLOCKS * 512];
+
+        // protective MBR
+        disk[0x1be + 4] = 0xEE;
+        disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        let mut entries = [0u8; 4 * GptPartition::LEN];
+        let entry = &mut entries[GptPartition::LEN..2 * GptPartition::LEN];
+        entry[0..16].copy_from_slice(Guid::MICROSOFT_BASIC_DATA.as_bytes());
+        entry[32..40].copy_from_slice(&40u64.to_le_bytes());
+        entry[40..48].copy_from_slice(&49u64.to_le_bytes());
+        for (i, ch) in "GGOS".encode_utf16().enumerate() {
+            entry[56 + i * 2..58 + i * 2].copy_from_slice(&ch.to_le_bytes());
+        }
+
+        let last = BLOCKS as u64 - 1;
+        write_header(&mut disk, 1, last, 2, &entries);
+        write_header(&mut disk, last, 1, last - 1, &entries);
+
+        disk
+    }
+
+    #[test]
+    fn test_gpt() {
+        let disk = MemDisk::from_bytes(&gpt_disk());
+        let gpt = GptTable::parse(disk.clone()).unwrap();
+
+        assert_eq!(gpt.header().my_lba(), 1);
+        assert_eq!(gpt.entries().len(), 1);
+
+        let entry = &gpt.entries()[0];
+        assert_eq!(entry.type_guid(), Guid::MICROSOFT_BASIC_DATA);
+        assert_eq!(entry.name(), "GGOS");
+        assert_eq!(entry.total_lba(), 10);
+
+        let parts = read_partitions(disk).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].block_count(), Ok(10));
+    }
+
+    #[test]
+    fn test_gpt_backup() {
+        let mut disk = gpt_disk();
+        // corrupt the primary header
+        disk[512 + 0x20] ^= 0xFF;
+
+        let gpt = GptTable::parse(MemDisk::from_bytes(&disk)).unwrap();
+
+        assert_eq!(gpt.header().my_lba(), BLOCKS as u64 - 1);
+        assert_eq!(gpt.entries()[0].name(), "GGOS");
+    }
+}
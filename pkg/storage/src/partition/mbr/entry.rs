@@ -36,6 +36,11 @@ impl MbrPartition {
         self.filesystem_flag() == 0x05
     }
 
+    /// The partition of a protective MBR that covers the whole GPT disk
+    pub fn is_protective(&self) -> bool {
+        self.filesystem_flag() == 0xEE
+    }
+
     pub fn begin_sector(&self) -> u8 {
         self.data[2] & 0x3f
     }
@@ -20,6 +20,17 @@ where
     _block: PhantomData<B>,
 }
 
+impl<T, B> MbrTable<T, B>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    /// Returns true if the disk is partitioned with GPT
+    pub fn is_protective(&self) -> bool {
+        self.partitions.iter().any(|part| part.is_protective())
+    }
+}
+
 impl<T, B> PartitionTable<T, B> for MbrTable<T, B>
 where
     T: BlockDevice<B> + Clone,
@@ -2,6 +2,7 @@ use core::marker::PhantomData;
 
 use crate::*;
 
+pub mod gpt;
 pub mod mbr;
 
 /// Partition table trait
@@ -18,6 +19,22 @@ where
     fn partitions(&self) -> Result<Vec<Partition<T, B>>>;
 }
 
+/// Reads the partitions of the disk, from the GPT if
+/// the MBR is a protective one, otherwise from the MBR.
+pub fn read_partitions<T, B>(inner: T) -> Result<Vec<Partition<T, B>>>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    let mbr = mbr::MbrTable::parse(inner.clone())?;
+
+    if mbr.is_protective() {
+        gpt::GptTable::parse(inner)?.partitions()
+    } else {
+        mbr.partitions()
+    }
+}
+
 /// Identifies a partition on the disk.
 pub struct Partition<T, B>
 where
@@ -45,6 +62,16 @@ where
     }
 }
 
+impl<T, B> Clone for Partition<T, B>
+where
+    T: BlockDevice<B> + Clone,
+    B: BlockTrait,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone(), self.offset, self.size)
+    }
+}
+
 impl<T, B> core::fmt::Debug for Partition<T, B>
 where
     T: BlockDevice<B>,
@@ -64,7 +91,7 @@ where
     B: BlockTrait,
 {
     fn block_count(&self) -> Result<usize> {
-        self.inner.block_count()
+        Ok(self.size)
     }
 
     fn read_block(&self, offset: usize, block: &mut B) -> Result<()> {
@@ -84,4 +111,22 @@ where
         let offset = offset + self.offset;
         self.inner.write_block(offset, block)
     }
+
+    fn read_blocks(&self, offset: usize, blocks: &mut [B]) -> Result<()> {
+        if offset + blocks.len() > self.size {
+            return Err(FsError::InvalidOffset);
+        }
+
+        let offset = offset + self.offset;
+        self.inner.read_blocks(offset, blocks)
+    }
+
+    fn write_blocks(&self, offset: usize, blocks: &[B]) -> Result<()> {
+        if offset + blocks.len() > self.size {
+            return Err(FsError::InvalidOffset);
+        }
+
+        let offset = offset + self.offset;
+        self.inner.write_blocks(offset, blocks)
+    }
 }
@@ -1,6 +1,6 @@
 #![no_std]
 
-use num_enum::FromPrimitive;
+use num_enum::{FromPrimitive, TryFromPrimitive};
 
 pub mod macros;
 
@@ -11,11 +11,17 @@ pub enum Syscall {
     Write = 1,
     Open = 2,
     Close = 3,
+    StatPath = 4,
+    FStat = 5,
+    Seek = 8,
 
     Brk = 12,
 
+    Sleep = 35,
+
     GetPid = 39,
 
+    Fork = 57,
     VFork = 58,
     Spawn = 59,
     Exit = 60,
@@ -23,10 +29,19 @@ pub enum Syscall {
     Kill = 62,
 
     Sem = 66,
+
+    GetDents = 78,
+
+    SetPriority = 141,
+
+    Sync = 162,
     Time = 201,
+    ClockGetTime = 228,
 
+    Exec = 65527,
+    ListPci = 65528,
+    ListMounts = 65529,
     Stat = 65530,
-    ListDir = 65531,
     Draw = 65532,
     Allocate = 65533,
     Deallocate = 65534,
@@ -34,3 +49,280 @@ pub enum Syscall {
     #[num_enum(default)]
     None = 65535,
 }
+
+/// The different ways we can open a file.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub enum FileMode {
+    /// Open a file for reading, if it exists.
+    ReadOnly = 0,
+    /// Open a file for appending (writing to the end of the existing file), if it exists.
+    ReadWriteAppend = 1,
+    /// Open a file and remove all contents, before writing to the start of the existing file, if it exists.
+    ReadWriteTruncate = 2,
+    /// Create a new empty file. Fail if it exists.
+    ReadWriteCreate = 3,
+    /// Create a new empty file, or truncate an existing file.
+    ReadWriteCreateOrTruncate = 4,
+    /// Create a new empty file, or append to an existing file.
+    ReadWriteCreateOrAppend = 5,
+    /// Open a directory for reading its entries with `GetDents`.
+    Directory = 6,
+}
+
+/// The metadata of a file shared with the user space by `FStat` and `StatPath`
+#[repr(C)]
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct FileStat {
+    /// `FileStat::FILE` or `FileStat::DIRECTORY`
+    pub file_type: u32,
+    /// Always zero, takes the place of the padding before `len`
+    pub reserved: u32,
+    /// Length of the file in bytes, 0 for directories
+    pub len: u64,
+    /// Times in nanoseconds since the unix epoch,
+    /// 0 if not supported by the filesystem
+    pub created: i64,
+    pub modified: i64,
+    pub accessed: i64,
+}
+
+impl FileStat {
+    pub const FILE: u32 = 0;
+    pub const DIRECTORY: u32 = 1;
+
+    #[inline]
+    pub fn is_file(&self) -> bool {
+        self.file_type == Self::FILE
+    }
+
+    #[inline]
+    pub fn is_dir(&self) -> bool {
+        self.file_type == Self::DIRECTORY
+    }
+}
+
+/// The origin of `Seek`, passed along with the offset
+#[derive(Debug, PartialEq, Eq, Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub enum SeekWhence {
+    Start = 0,
+    Current = 1,
+    End = 2,
+}
+
+/// The clocks read by `ClockGetTime`, in nanoseconds
+#[derive(Debug, PartialEq, Eq, Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub enum ClockId {
+    /// Since the unix epoch, based on the firmware time at boot
+    Realtime = 0,
+    /// Since boot, never goes backwards
+    Monotonic = 1,
+}
+
+/// The longest name in a `DirEntry`, longer names are truncated
+pub const DIR_ENTRY_NAME_LEN: usize = 256;
+
+/// A fixed-size record of a directory entry filled by `GetDents`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DirEntry {
+    /// `FileStat::FILE` or `FileStat::DIRECTORY`
+    pub file_type: u32,
+    /// Length of the name in bytes
+    pub name_len: u32,
+    /// Length of the file in bytes, 0 for directories
+    pub len: u64,
+    /// Modification time in nanoseconds since the unix epoch,
+    /// 0 if not supported by the filesystem
+    pub modified: i64,
+    /// The name in UTF-8, only the first `name_len` bytes are valid
+    pub name: [u8; DIR_ENTRY_NAME_LEN],
+}
+
+impl Default for DirEntry {
+    fn default() -> Self {
+        Self {
+            file_type: FileStat::FILE,
+            name_len: 0,
+            len: 0,
+            modified: 0,
+            name: [0; DIR_ENTRY_NAME_LEN],
+        }
+    }
+}
+
+/// Decodes the valid UTF-8 prefix of a truncated string,
+/// which may end in the middle of a character
+fn truncated_str(bytes: &[u8]) -> &str {
+    match core::str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(err) => core::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap_or_default(),
+    }
+}
+
+impl DirEntry {
+    #[inline]
+    pub fn name(&self) -> &str {
+        truncated_str(&self.name[..self.name_len as usize])
+    }
+
+    #[inline]
+    pub fn is_file(&self) -> bool {
+        self.file_type == FileStat::FILE
+    }
+
+    #[inline]
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FileStat::DIRECTORY
+    }
+}
+
+/// The longest mount point in a `MountEntry`, longer ones are truncated
+pub const MOUNT_POINT_LEN: usize = 128;
+
+/// The longest filesystem type in a `MountEntry`
+pub const FS_TYPE_LEN: usize = 16;
+
+/// A fixed-size record of a mounted filesystem filled by `ListMounts`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MountEntry {
+    /// Length of the filesystem type in bytes
+    pub fs_type_len: u32,
+    /// Length of the mount point in bytes
+    pub mount_point_len: u32,
+    /// The filesystem type in UTF-8, e.g. `fat16`
+    pub fs_type: [u8; FS_TYPE_LEN],
+    /// The mount point in UTF-8, only the first `mount_point_len` bytes are valid
+    pub mount_point: [u8; MOUNT_POINT_LEN],
+}
+
+impl Default for MountEntry {
+    fn default() -> Self {
+        Self {
+            fs_type_len: 0,
+            mount_point_len: 0,
+            fs_type: [0; FS_TYPE_LEN],
+            mount_point: [0; MOUNT_POINT_LEN],
+        }
+    }
+}
+
+impl MountEntry {
+    #[inline]
+    pub fn fs_type(&self) -> &str {
+        truncated_str(&self.fs_type[..self.fs_type_len as usize])
+    }
+
+    #[inline]
+    pub fn mount_point(&self) -> &str {
+        truncated_str(&self.mount_point[..self.mount_point_len as usize])
+    }
+}
+
+/// A fixed-size record of a PCI function filled by `ListPci`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PciEntry {
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub prog_if: u8,
+    pub revision: u8,
+    pub header_type: u8,
+    pub irq_line: u8,
+    /// No interrupt is routed if it is 0
+    pub irq_pin: u8,
+}
+
+impl PciEntry {
+    /// The name of the class, e.g. "Mass storage controller"
+    pub fn class_name(&self) -> &'static str {
+        match (self.class, self.subclass) {
+            (0x01, 0x01) => "IDE interface",
+            (0x01, 0x06) => "SATA controller",
+            (0x01, 0x08) => "Non-Volatile memory controller",
+            (0x01, _) => "Mass storage controller",
+            (0x02, 0x00) => "Ethernet controller",
+            (0x02, _) => "Network controller",
+            (0x03, 0x00) => "VGA compatible controller",
+            (0x03, _) => "Display controller",
+            (0x04, _) => "Multimedia controller",
+            (0x05, _) => "Memory controller",
+            (0x06, 0x00) => "Host bridge",
+            (0x06, 0x01) => "ISA bridge",
+            (0x06, 0x04) => "PCI bridge",
+            (0x06, _) => "Bridge",
+            (0x07, _) => "Communication controller",
+            (0x08, _) => "System peripheral",
+            (0x09, _) => "Input device controller",
+            (0x0C, 0x03) => "USB controller",
+            (0x0C, 0x05) => "SMBus",
+            (0x0C, _) => "Serial bus controller",
+            (0x00, _) => "Unclassified device",
+            _ => "Unknown device",
+        }
+    }
+}
+
+/// Formats the function like `lspci`
+impl core::fmt::Display for PciEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{:02x}:{:02x}.{} {} [{:02x}{:02x}]: {:04x}:{:04x} (rev {:02x})",
+            self.bus,
+            self.device,
+            self.function,
+            self.class_name(),
+            self.class,
+            self.subclass,
+            self.vendor_id,
+            self.device_id,
+            self.revision
+        )
+    }
+}
+
+/// A string shared with the kernel, the pointer and length of its UTF-8 bytes
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct StrRef {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+impl StrRef {
+    #[inline]
+    pub fn new(s: &str) -> Self {
+        Self {
+            ptr: s.as_ptr(),
+            len: s.len(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The bytes must be valid UTF-8 and live for `'a`
+    #[inline]
+    pub unsafe fn as_str<'a>(&self) -> &'a str {
+        unsafe { core::str::from_utf8_unchecked(core::slice::from_raw_parts(self.ptr, self.len)) }
+    }
+}
+
+/// The arguments and environment of `Exec`, the kernel copies them
+/// to the new stack and passes them to the entry point in `rdi`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExecArgs {
+    pub argv: *const StrRef,
+    pub argc: usize,
+    pub envp: *const StrRef,
+    pub envc: usize,
+}